@@ -0,0 +1,269 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Speaker turns within the text of a subtitle.
+//!
+//! Subtitles containing lines from more than one speaker usually start each line with a dash,
+//! or with the name of the speaker followed by a colon. A [`Dialogue`] splits such a text into
+//! its individual [`Turn`]s, and can write it back using a consistent [`DashStyle`].
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitle;
+//! use srtlib::dialogue::DashStyle;
+//!
+//! let mut sub = Subtitle::parse("1\n00:00:01,000 --> 00:00:02,000\n-Are you coming?\n–  ANNA: Yes.".to_string()).unwrap();
+//!
+//! let dialogue = sub.dialogue();
+//! assert_eq!(dialogue.turns.len(), 2);
+//! assert_eq!(dialogue.turns[1].speaker.as_deref(), Some("ANNA"));
+//! assert_eq!(dialogue.turns[1].text, "Yes.");
+//!
+//! sub.normalize_dashes(DashStyle::HyphenSpace);
+//! assert_eq!(sub.text, "- Are you coming?\n- ANNA: Yes.");
+//! ```
+//!
+//! [`Dialogue`]: struct.Dialogue.html
+//! [`Turn`]: struct.Turn.html
+//! [`DashStyle`]: enum.DashStyle.html
+
+use crate::{Subtitle, Subtitles};
+
+/// The characters recognised as dialogue dashes.
+const DASHES: [char; 3] = ['-', '\u{2013}', '\u{2014}'];
+
+/// The way dialogue dashes are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DashStyle {
+    /// "-Hello"
+    Hyphen,
+    /// "- Hello"
+    HyphenSpace,
+    /// "–Hello"
+    EnDash,
+    /// "– Hello"
+    EnDashSpace,
+}
+
+impl DashStyle {
+    /// Returns the text put in front of every turn of a dialogue.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            DashStyle::Hyphen => "-",
+            DashStyle::HyphenSpace => "- ",
+            DashStyle::EnDash => "\u{2013}",
+            DashStyle::EnDashSpace => "\u{2013} ",
+        }
+    }
+}
+
+/// The part of a dialogue spoken by a single speaker.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Turn {
+    /// The formatting tags in front of the dash and speaker label of the turn, e.g. "<i>" in
+    /// "<i>- Yes.</i>". Tags written after the dash are part of the text instead.
+    pub tags: String,
+    /// Whether the turn is introduced by a dash.
+    pub dashed: bool,
+    /// The name of the speaker, if the turn is labelled with one, e.g. "ANNA" in "ANNA: Yes.".
+    pub speaker: Option<String>,
+    /// The spoken text, which may span multiple lines.
+    pub text: String,
+}
+
+/// The text of a subtitle split into speaker turns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Dialogue {
+    pub turns: Vec<Turn>,
+    /// Whether any of the turns is introduced by a dash.
+    pub dashed: bool,
+}
+
+/// Splits a speaker label written in uppercase (e.g. "JOHN:" or "MAN #2:") from the start of a
+/// text, returning the label and the remaining text. If **uppercase_only** is false, labels with
+/// lowercase letters are accepted as well.
+pub(crate) fn split_speaker_label(text: &str, uppercase_only: bool) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    let is_label = !label.is_empty()
+        && label.chars().count() <= 30
+        && label.chars().any(char::is_alphabetic)
+        && label
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '.' | '\'' | '-' | '#'))
+        && (!uppercase_only || !label.chars().any(char::is_lowercase));
+    is_label.then(|| (label.trim(), rest.trim_start()))
+}
+
+/// Splits the formatting tags at the start of a line (e.g. "<i>") from the rest of it.
+pub(crate) fn split_leading_tags(line: &str) -> (&str, &str) {
+    let mut end = 0;
+    while line[end..].starts_with('<') {
+        match line[end..].find('>') {
+            Some(close) => end += close + 1,
+            None => break,
+        }
+    }
+    line.split_at(end)
+}
+
+/// Removes a leading dialogue dash from a line, unless it is a "--" used for interrupted speech.
+fn strip_dash(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix(DASHES)?;
+    if rest.starts_with(DASHES) {
+        return None;
+    }
+    Some(rest.trim_start())
+}
+
+impl Dialogue {
+    /// Splits a text into speaker turns.
+    ///
+    /// A new turn starts at every line beginning with a dash or an uppercase speaker label. Any
+    /// other line continues the current turn.
+    pub fn parse(text: &str) -> Dialogue {
+        let mut dialogue = Dialogue::default();
+        for line in text.lines() {
+            let (tags, content) = split_leading_tags(line.trim_start());
+            let (content, dashed) = match strip_dash(content) {
+                Some(rest) => (rest, true),
+                None => (content, false),
+            };
+            let (speaker, content) = match split_speaker_label(content, true) {
+                Some((label, rest)) => (Some(label.to_string()), rest),
+                None => (None, content),
+            };
+            dialogue.dashed |= dashed;
+            match dialogue.turns.last_mut() {
+                Some(turn) if !dashed && speaker.is_none() => {
+                    turn.text.push('\n');
+                    turn.text.push_str(tags);
+                    turn.text.push_str(content);
+                }
+                _ => dialogue.turns.push(Turn {
+                    tags: tags.to_string(),
+                    dashed,
+                    speaker,
+                    text: content.to_string(),
+                }),
+            }
+        }
+        dialogue
+    }
+
+    /// Writes the dialogue back to text, introducing the dashed turns with the given dash style.
+    /// Turns keep their dashes on the same side of their leading tags, so a text parsed and
+    /// written back with its own style is left unchanged.
+    pub fn to_text(&self, style: DashStyle) -> String {
+        self.turns
+            .iter()
+            .map(|turn| {
+                let dash = if turn.dashed { style.prefix() } else { "" };
+                match &turn.speaker {
+                    Some(speaker) => format!("{}{}{}: {}", turn.tags, dash, speaker, turn.text),
+                    None => format!("{}{}{}", turn.tags, dash, turn.text),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the names of the labelled speakers, in order of appearance.
+    pub fn speakers(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = Vec::new();
+        for name in self.turns.iter().filter_map(|t| t.speaker.as_deref()) {
+            if !res.contains(&name) {
+                res.push(name);
+            }
+        }
+        res
+    }
+}
+
+impl Subtitle {
+    /// Splits the text of the subtitle into speaker turns.
+    pub fn dialogue(&self) -> Dialogue {
+        Dialogue::parse(&self.text)
+    }
+
+    /// Rewrites the dialogue dashes of the subtitle in the given style. Subtitles without
+    /// dashes are left unchanged.
+    pub fn normalize_dashes(&mut self, style: DashStyle) {
+        let dialogue = self.dialogue();
+        if dialogue.dashed {
+            self.set_text(dialogue.to_text(style));
+        }
+    }
+}
+
+impl Subtitles {
+    /// Rewrites the dialogue dashes of every subtitle in the given style.
+    pub fn normalize_dashes(&mut self, style: DashStyle) {
+        for sub in self {
+            sub.normalize_dashes(style);
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn parse_turns() {
+        let dialogue = Dialogue::parse("JOHN: I told you\nit was late.\nMARY: I know.");
+        assert!(!dialogue.dashed);
+        assert_eq!(
+            dialogue.turns,
+            vec![
+                Turn {
+                    tags: String::new(),
+                    dashed: false,
+                    speaker: Some("JOHN".to_string()),
+                    text: "I told you\nit was late.".to_string()
+                },
+                Turn {
+                    tags: String::new(),
+                    dashed: false,
+                    speaker: Some("MARY".to_string()),
+                    text: "I know.".to_string()
+                },
+            ]
+        );
+        assert_eq!(dialogue.speakers(), vec!["JOHN", "MARY"]);
+        assert_eq!(
+            dialogue.to_text(DashStyle::Hyphen),
+            "JOHN: I told you\nit was late.\nMARY: I know."
+        );
+    }
+
+    #[test]
+    fn dash_styles() {
+        let dialogue = Dialogue::parse("<i>- Where?</i>\n-- Over there.\n- Note: 10:30");
+        assert_eq!(dialogue.turns.len(), 2);
+        assert_eq!(
+            dialogue.to_text(DashStyle::EnDashSpace),
+            "<i>\u{2013} Where?</i>\n-- Over there.\n\u{2013} Note: 10:30"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for text in [
+            "<i>- Where?</i>\n- <i>There.</i>",
+            "Are you coming?\n- Yes.",
+            "- No.\n<i>JOHN: Why?</i>\nBecause.",
+        ] {
+            assert_eq!(Dialogue::parse(text).to_text(DashStyle::HyphenSpace), text);
+        }
+        assert_eq!(
+            Dialogue::parse("Are you coming?\n- Yes.").to_text(DashStyle::Hyphen),
+            "Are you coming?\n-Yes."
+        );
+    }
+}
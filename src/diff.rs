@@ -0,0 +1,375 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Structural comparison of two subtitle collections.
+//!
+//! Unlike a textual diff of two .srt files, the comparison is performed on the subtitles
+//! themselves, so renumbering a file does not cause every subtitle to show up as changed.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::diff::Change;
+//!
+//! let old = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nHello world\n\n\
+//!                                      2\n00:00:03,000 --> 00:00:04,000\nGoodbye".to_string()).unwrap();
+//! let new = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nHello there world\n\n\
+//!                                      2\n00:00:03,500 --> 00:00:04,500\nGoodbye".to_string()).unwrap();
+//!
+//! let diff = old.diff(&new);
+//! assert!(matches!(diff.changes[0], Change::TextChanged { .. }));
+//! assert!(matches!(diff.changes[1], Change::Retimed { start_delta: 500, end_delta: 500, .. }));
+//! assert_eq!(diff.to_string(),
+//!            "* #1 00:00:01,000 --> 00:00:02,000: Hello {+there+} world\n\
+//!             ~ #2 retimed: 00:00:03,000 --> 00:00:04,000 became 00:00:03,500 --> 00:00:04,500 \
+//!             (start +500 ms, end +500 ms)");
+//! ```
+
+use crate::{Subtitle, Subtitles};
+use std::fmt;
+
+/// A single difference between two words of a subtitle's text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordChange {
+    /// The word is present in both texts.
+    Equal(String),
+    /// The word is only present in the new text.
+    Inserted(String),
+    /// The word is only present in the old text.
+    Deleted(String),
+}
+
+/// The way a subtitle changed between the old and the new collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The subtitle only exists in the new collection.
+    Added(Subtitle),
+    /// The subtitle only exists in the old collection.
+    Removed(Subtitle),
+    /// The subtitle kept its text but was moved in time. The deltas are in milliseconds.
+    Retimed {
+        old: Subtitle,
+        new: Subtitle,
+        start_delta: i64,
+        end_delta: i64,
+    },
+    /// The subtitle occupies (roughly) the same time but its text changed.
+    TextChanged {
+        old: Subtitle,
+        new: Subtitle,
+        words: Vec<WordChange>,
+    },
+    /// The subtitle has the same timing and text in both collections.
+    Unchanged { old: Subtitle, new: Subtitle },
+}
+
+impl Change {
+    /// Returns the subtitle this change refers to in the new collection, or in the old one if it
+    /// was removed.
+    pub fn subtitle(&self) -> &Subtitle {
+        match self {
+            Change::Added(sub) | Change::Removed(sub) => sub,
+            Change::Retimed { new, .. }
+            | Change::TextChanged { new, .. }
+            | Change::Unchanged { new, .. } => new,
+        }
+    }
+
+    /// Checks if this change represents a subtitle that is identical in both collections.
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Change::Unchanged { .. })
+    }
+}
+
+/// Formats the text of a subtitle on a single line.
+fn one_line(text: &str) -> String {
+    text.replace('\n', " | ")
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(sub) => write!(
+                f,
+                "+ #{} {} --> {}: {}",
+                sub.num,
+                sub.start_time,
+                sub.end_time,
+                one_line(&sub.text)
+            ),
+            Change::Removed(sub) => write!(
+                f,
+                "- #{} {} --> {}: {}",
+                sub.num,
+                sub.start_time,
+                sub.end_time,
+                one_line(&sub.text)
+            ),
+            Change::Retimed {
+                old,
+                new,
+                start_delta,
+                end_delta,
+            } => write!(
+                f,
+                "~ #{} retimed: {} --> {} became {} --> {} (start {:+} ms, end {:+} ms)",
+                new.num,
+                old.start_time,
+                old.end_time,
+                new.start_time,
+                new.end_time,
+                start_delta,
+                end_delta
+            ),
+            Change::TextChanged { new, words, .. } => {
+                let words: Vec<String> = words
+                    .iter()
+                    .map(|w| match w {
+                        WordChange::Equal(w) => w.clone(),
+                        WordChange::Inserted(w) => format!("{{+{}+}}", w),
+                        WordChange::Deleted(w) => format!("[-{}-]", w),
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "* #{} {} --> {}: {}",
+                    new.num,
+                    new.start_time,
+                    new.end_time,
+                    words.join(" ")
+                )
+            }
+            Change::Unchanged { new, .. } => write!(
+                f,
+                "  #{} {} --> {}: {}",
+                new.num,
+                new.start_time,
+                new.end_time,
+                one_line(&new.text)
+            ),
+        }
+    }
+}
+
+/// The result of comparing two [`Subtitles`] collections, ordered by time.
+///
+/// Its `Display` implementation renders a human-readable report with one line per changed
+/// subtitle, leaving out unchanged ones.
+///
+/// [`Subtitles`]: ../struct.Subtitles.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubtitlesDiff {
+    pub changes: Vec<Change>,
+}
+
+impl SubtitlesDiff {
+    /// Checks if the two compared collections contain the same subtitles.
+    pub fn is_identical(&self) -> bool {
+        self.changes.iter().all(Change::is_unchanged)
+    }
+}
+
+impl fmt::Display for SubtitlesDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self
+            .changes
+            .iter()
+            .filter(|c| !c.is_unchanged())
+            .map(|c| c.to_string())
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Computes the longest common subsequence of two slices, returning the matched index pairs.
+fn lcs<T, F>(old: &[T], new: &[T], eq: F) -> Vec<(usize, usize)>
+where
+    F: Fn(&T, &T) -> bool,
+{
+    let width = new.len() + 1;
+    let mut table = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if eq(&old[i], &new[j]) {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                std::cmp::max(table[(i + 1) * width + j], table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut res = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if eq(&old[i], &new[j]) {
+            res.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    res
+}
+
+/// Computes a word-level diff between two texts.
+pub fn diff_words(old: &str, new: &str) -> Vec<WordChange> {
+    let old: Vec<&str> = old.split_whitespace().collect();
+    let new: Vec<&str> = new.split_whitespace().collect();
+    let mut res = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (oi, nj) in lcs(&old, &new, |a, b| a == b) {
+        res.extend(
+            old[i..oi]
+                .iter()
+                .map(|w| WordChange::Deleted(w.to_string())),
+        );
+        res.extend(
+            new[j..nj]
+                .iter()
+                .map(|w| WordChange::Inserted(w.to_string())),
+        );
+        res.push(WordChange::Equal(old[oi].to_string()));
+        i = oi + 1;
+        j = nj + 1;
+    }
+    res.extend(old[i..].iter().map(|w| WordChange::Deleted(w.to_string())));
+    res.extend(new[j..].iter().map(|w| WordChange::Inserted(w.to_string())));
+    res
+}
+
+/// Checks if the two subtitles are displayed on screen at the same time.
+fn overlaps(a: &Subtitle, b: &Subtitle) -> bool {
+    a.start_time < b.end_time && b.start_time < a.end_time
+}
+
+/// Pairs up the unmatched subtitles found between two matched ones.
+fn diff_gap(old: &[Subtitle], new: &[Subtitle], changes: &mut Vec<Change>) {
+    let mut used = vec![false; new.len()];
+    for o in old {
+        let pos = (0..new.len()).find(|&j| !used[j] && overlaps(o, &new[j]));
+        if let Some(j) = pos {
+            used[j] = true;
+            changes.push(Change::TextChanged {
+                old: o.clone(),
+                new: new[j].clone(),
+                words: diff_words(&o.text, &new[j].text),
+            });
+        } else {
+            changes.push(Change::Removed(o.clone()));
+        }
+    }
+    for (j, n) in new.iter().enumerate() {
+        if !used[j] {
+            changes.push(Change::Added(n.clone()));
+        }
+    }
+}
+
+impl Subtitles {
+    /// Compares this collection (the old version) with **other** (the new version).
+    ///
+    /// Subtitles are matched by their text, keeping their relative order, so subtitle numbers do
+    /// not affect the result. Matched subtitles are reported as unchanged or retimed. The
+    /// remaining subtitles are paired up with the ones occupying the same time and reported as
+    /// having their text changed, while anything left over is reported as added or removed.
+    pub fn diff(&self, other: &Subtitles) -> SubtitlesDiff {
+        let mut old = self.0.clone();
+        let mut new = other.0.clone();
+        old.sort_by_key(|s| (s.start_time, s.end_time));
+        new.sort_by_key(|s| (s.start_time, s.end_time));
+
+        let mut changes = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        for (oi, nj) in lcs(&old, &new, |a, b| a.text == b.text) {
+            diff_gap(&old[i..oi], &new[j..nj], &mut changes);
+            let (o, n) = (old[oi].clone(), new[nj].clone());
+            if o.start_time == n.start_time && o.end_time == n.end_time {
+                changes.push(Change::Unchanged { old: o, new: n });
+            } else {
+                let start_delta =
+                    n.start_time.to_milliseconds() as i64 - o.start_time.to_milliseconds() as i64;
+                let end_delta =
+                    n.end_time.to_milliseconds() as i64 - o.end_time.to_milliseconds() as i64;
+                changes.push(Change::Retimed {
+                    old: o,
+                    new: n,
+                    start_delta,
+                    end_delta,
+                });
+            }
+            i = oi + 1;
+            j = nj + 1;
+        }
+        diff_gap(&old[i..], &new[j..], &mut changes);
+
+        changes.sort_by_key(|c| c.subtitle().start_time);
+        SubtitlesDiff { changes }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn renumbering_is_not_a_change() {
+        let old = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo"
+                .to_string(),
+        )
+        .unwrap();
+        let mut new = old.clone();
+        for s in &mut new {
+            s.num += 10;
+        }
+
+        let diff = old.diff(&new);
+        assert!(diff.is_identical());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo"
+                .to_string(),
+        )
+        .unwrap();
+        let new = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:05,000 --> 00:00:06,000\nThree"
+                .to_string(),
+        )
+        .unwrap();
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.changes.len(), 3);
+        assert!(diff.changes[0].is_unchanged());
+        assert_eq!(diff.changes[1], Change::Removed(old[1].clone()));
+        assert_eq!(diff.changes[2], Change::Added(new[1].clone()));
+    }
+
+    #[test]
+    fn word_diff() {
+        assert_eq!(
+            diff_words("Hello big world", "Hello small world"),
+            vec![
+                WordChange::Equal("Hello".to_string()),
+                WordChange::Deleted("big".to_string()),
+                WordChange::Inserted("small".to_string()),
+                WordChange::Equal("world".to_string()),
+            ]
+        );
+    }
+}
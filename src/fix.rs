@@ -0,0 +1,451 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Automatic fixes for common subtitle errors.
+//!
+//! A [`FixConfig`] holds the fixers to run, in order. Running it over a [`Subtitles`] collection
+//! modifies the subtitles in place and returns a [`FixChange`] for every subtitle that was
+//! changed or removed. Errors that keep a .srt file from being parsed at all, such as a missing
+//! space around the "-->" arrow, are fixed by [`FixConfig::parse`] instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::fix::FixConfig;
+//!
+//! let mut subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\n \n\n\
+//!                                           2\n00:00:03,000 --> 00:00:04,000\n<i>l  think so.  ".to_string()).unwrap();
+//! let changes = subs.fix(&FixConfig::all());
+//!
+//! assert_eq!(subs.to_string(), "1\n00:00:03,000 --> 00:00:04,000\n<i>I think so.</i>");
+//! assert_eq!(changes.len(), 7);
+//! ```
+//!
+//! [`FixConfig`]: struct.FixConfig.html
+//! [`Subtitles`]: ../struct.Subtitles.html
+//! [`FixChange`]: struct.FixChange.html
+//! [`FixConfig::parse`]: struct.FixConfig.html#method.parse
+
+use crate::{ParsingError, Subtitle, Subtitles};
+
+/// A single automatic fix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixer {
+    /// Repairs the spacing around the "-->" arrow of timing lines. Since parsed subtitles are
+    /// always written with the correct spacing, this fixer only runs in [`FixConfig::parse`],
+    /// before the subtitles are parsed.
+    ///
+    /// [`FixConfig::parse`]: struct.FixConfig.html#method.parse
+    ArrowSpacing,
+    /// Removes whitespace at the start and end of every line, as well as empty leading and
+    /// trailing lines.
+    TrimWhitespace,
+    /// Removes subtitles without any text.
+    RemoveEmpty,
+    /// Closes unclosed `<i>` tags and removes stray closing ones.
+    RepairItalics,
+    /// Replaces consecutive spaces with a single space.
+    CollapseSpaces,
+    /// Puts a single space after dialogue dashes, and adds the missing dash to the first line of
+    /// a two-line dialogue.
+    DialogueDashes,
+    /// Replaces a lowercase "l" that was mistaken by OCR for an uppercase "I", as in "l'm" or
+    /// "l think".
+    OcrLowercaseL,
+    /// Renumbers the subtitles sequentially starting from 1.
+    Renumber,
+}
+
+impl Fixer {
+    /// Returns the name of the fixer in kebab-case, e.g. "trim-whitespace".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fixer::ArrowSpacing => "arrow-spacing",
+            Fixer::TrimWhitespace => "trim-whitespace",
+            Fixer::RemoveEmpty => "remove-empty",
+            Fixer::RepairItalics => "repair-italics",
+            Fixer::CollapseSpaces => "collapse-spaces",
+            Fixer::DialogueDashes => "dialogue-dashes",
+            Fixer::OcrLowercaseL => "ocr-lowercase-l",
+            Fixer::Renumber => "renumber",
+        }
+    }
+
+    /// Applies the fixer to the text of a subtitle, returning the fixed text.
+    fn fix_text(&self, text: &str) -> String {
+        match self {
+            Fixer::TrimWhitespace => text
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim_matches('\n')
+                .to_string(),
+            Fixer::RepairItalics => repair_italics(text),
+            Fixer::CollapseSpaces => {
+                let mut res = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if !(c == ' ' && res.ends_with(' ')) {
+                        res.push(c);
+                    }
+                }
+                res
+            }
+            Fixer::DialogueDashes => fix_dialogue_dashes(text),
+            Fixer::OcrLowercaseL => fix_lowercase_l(text),
+            Fixer::ArrowSpacing | Fixer::RemoveEmpty | Fixer::Renumber => text.to_string(),
+        }
+    }
+}
+
+/// A subtitle that was changed by a fixer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixChange {
+    pub fixer: Fixer,
+    /// The subtitle before the fix. For [`Fixer::ArrowSpacing`], whose input can't be parsed,
+    /// this is the subtitle as parsed after the fix.
+    ///
+    /// [`Fixer::ArrowSpacing`]: enum.Fixer.html#variant.ArrowSpacing
+    pub before: Subtitle,
+    /// The subtitle after the fix, or None if it was removed.
+    pub after: Option<Subtitle>,
+}
+
+/// An ordered list of fixers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixConfig {
+    pub fixers: Vec<Fixer>,
+}
+
+impl FixConfig {
+    /// Constructs a new FixConfig without any fixers.
+    pub fn new() -> FixConfig {
+        Default::default()
+    }
+
+    /// Constructs a FixConfig with every fixer enabled, in an order where no fixer undoes the
+    /// work of another.
+    pub fn all() -> FixConfig {
+        FixConfig::new()
+            .with_fixer(Fixer::ArrowSpacing)
+            .with_fixer(Fixer::TrimWhitespace)
+            .with_fixer(Fixer::CollapseSpaces)
+            .with_fixer(Fixer::RepairItalics)
+            .with_fixer(Fixer::DialogueDashes)
+            .with_fixer(Fixer::OcrLowercaseL)
+            .with_fixer(Fixer::RemoveEmpty)
+            .with_fixer(Fixer::Renumber)
+    }
+
+    /// Adds a fixer to the configuration, returning the updated configuration.
+    pub fn with_fixer(mut self, fixer: Fixer) -> FixConfig {
+        self.fixers.push(fixer);
+        self
+    }
+
+    /// Checks if the given fixer is enabled.
+    pub fn is_enabled(&self, fixer: Fixer) -> bool {
+        self.fixers.contains(&fixer)
+    }
+
+    /// Parses the contents of a .srt file and runs every fixer over the parsed subtitles,
+    /// returning them along with the changes that were made.
+    ///
+    /// [`Fixer::ArrowSpacing`] runs on the contents before they are parsed, whatever its position
+    /// in the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`Subtitles::parse_from_str`] if the contents can't be parsed even
+    /// after the fix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::fix::{FixConfig, Fixer};
+    ///
+    /// let input = "1\n00:00:01,000-->00:00:02,000\nHello";
+    /// let (subs, changes) = FixConfig::all().parse(input).unwrap();
+    /// assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:02,000\nHello");
+    /// assert_eq!(changes[0].fixer, Fixer::ArrowSpacing);
+    /// ```
+    ///
+    /// [`Fixer::ArrowSpacing`]: enum.Fixer.html#variant.ArrowSpacing
+    /// [`Subtitles::parse_from_str`]: ../struct.Subtitles.html#method.parse_from_str
+    pub fn parse(&self, input: &str) -> Result<(Subtitles, Vec<FixChange>), ParsingError> {
+        if !self.is_enabled(Fixer::ArrowSpacing) {
+            let mut subs = Subtitles::parse_from_str(input.to_string())?;
+            let changes = self.apply(&mut subs);
+            return Ok((subs, changes));
+        }
+
+        let input = input.trim_start_matches('\u{feff}').replace('\r', "");
+        let mut subs = Subtitles::new();
+        let mut changes = Vec::new();
+        for block in input
+            .split_terminator("\n\n")
+            .filter(|&x| x.contains(char::is_alphanumeric))
+        {
+            let mut changed = false;
+            let fixed: Vec<String> = block
+                .split('\n')
+                .map(|line| match fix_arrow_spacing(line) {
+                    Some(fixed) => {
+                        changed = true;
+                        fixed
+                    }
+                    None => line.to_string(),
+                })
+                .collect();
+            let sub = Subtitle::parse(fixed.join("\n"))?;
+            if changed {
+                changes.push(FixChange {
+                    fixer: Fixer::ArrowSpacing,
+                    before: sub.clone(),
+                    after: Some(sub.clone()),
+                });
+            }
+            subs.push(sub);
+        }
+        changes.extend(self.apply(&mut subs));
+        Ok((subs, changes))
+    }
+
+    /// Runs every fixer over the subtitles in order, returning the changes that were made.
+    pub fn apply(&self, subs: &mut Subtitles) -> Vec<FixChange> {
+        let mut changes = Vec::new();
+        for &fixer in &self.fixers {
+            match fixer {
+                Fixer::RemoveEmpty => subs.0.retain(|sub| {
+                    let empty = sub.plain_text().trim().is_empty();
+                    if empty {
+                        changes.push(FixChange {
+                            fixer,
+                            before: sub.clone(),
+                            after: None,
+                        });
+                    }
+                    !empty
+                }),
+                Fixer::Renumber => {
+                    for (i, sub) in subs.0.iter_mut().enumerate() {
+                        if sub.num != i + 1 {
+                            let before = sub.clone();
+                            sub.num = i + 1;
+                            changes.push(FixChange {
+                                fixer,
+                                before,
+                                after: Some(sub.clone()),
+                            });
+                        }
+                    }
+                }
+                _ => {
+                    for sub in &mut subs.0 {
+                        let text = fixer.fix_text(&sub.text);
+                        if text != sub.text {
+                            let before = sub.clone();
+                            sub.set_text(text);
+                            changes.push(FixChange {
+                                fixer,
+                                before,
+                                after: Some(sub.clone()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Closes unclosed `<i>` tags at the end of the text and removes closing tags without a matching
+/// opening tag.
+fn repair_italics(text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 4);
+    let mut depth = 0;
+    let mut rest = text;
+    while let Some(pos) = rest.find('<') {
+        res.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let tag = rest.get(..4).map(str::to_lowercase);
+        if rest.get(..3).map(str::to_lowercase).as_deref() == Some("<i>") {
+            depth += 1;
+            res.push_str(&rest[..3]);
+            rest = &rest[3..];
+        } else if tag.as_deref() == Some("</i>") {
+            if depth > 0 {
+                depth -= 1;
+                res.push_str(&rest[..4]);
+            }
+            rest = &rest[4..];
+        } else {
+            res.push('<');
+            rest = &rest[1..];
+        }
+    }
+    res.push_str(rest);
+    for _ in 0..depth {
+        res.push_str("</i>");
+    }
+    res
+}
+
+/// Returns the line without its leading dialogue dash, if it has one.
+fn strip_dash(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let rest = trimmed
+        .strip_prefix('-')
+        .or_else(|| trimmed.strip_prefix('\u{2013}'))
+        .or_else(|| trimmed.strip_prefix('\u{2014}'))?;
+    // Lines starting with "--", used for interrupted speech, and negative numbers such as
+    // "-5 degrees" are left alone.
+    if rest.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+        return None;
+    }
+    Some(rest.trim_start())
+}
+
+fn fix_dialogue_dashes(text: &str) -> String {
+    let mut lines: Vec<String> = text
+        .lines()
+        .map(|line| match strip_dash(line) {
+            Some(rest) => format!("- {}", rest),
+            None => line.to_string(),
+        })
+        .collect();
+    if lines.len() == 2
+        && strip_dash(&lines[0]).is_none()
+        && strip_dash(&lines[1]).is_some()
+        && lines[0].trim_end().ends_with(['.', '?', '!'])
+    {
+        lines[0] = format!("- {}", lines[0]);
+    }
+    lines.join("\n")
+}
+
+fn fix_lowercase_l(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut res = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1).copied();
+        let standalone = c == 'l'
+            && !prev.is_some_and(|p| p.is_alphanumeric() || p == '\'')
+            && match next {
+                None => true,
+                Some('\'') => {
+                    let suffix: String = chars[i + 2..]
+                        .iter()
+                        .take_while(|c| c.is_alphabetic())
+                        .collect();
+                    matches!(suffix.as_str(), "m" | "ll" | "ve" | "d")
+                }
+                Some(n) => !n.is_alphanumeric(),
+            };
+        res.push(if standalone { 'I' } else { c });
+    }
+    res
+}
+
+/// Repairs the spacing around the "-->" arrow of a timing line, returning None if the line isn't
+/// a timing line or is already spaced correctly.
+fn fix_arrow_spacing(line: &str) -> Option<String> {
+    let (start, end) = line.split_once("-->")?;
+    let (start, end) = (start.trim(), end.trim());
+    let is_time = |s: &str| s.chars().next().is_some_and(|c| c.is_ascii_digit());
+    if !is_time(start) || !is_time(end) {
+        return None;
+    }
+    let fixed = format!("{} --> {}", start, end);
+    (fixed != line).then_some(fixed)
+}
+
+impl Subtitles {
+    /// Runs the fixers of a [`FixConfig`] over the subtitles, returning the changes that were
+    /// made.
+    ///
+    /// [`FixConfig`]: fix/struct.FixConfig.html
+    pub fn fix(&mut self, config: &FixConfig) -> Vec<FixChange> {
+        config.apply(self)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn italics() {
+        assert_eq!(repair_italics("<i>Hello"), "<i>Hello</i>");
+        assert_eq!(
+            repair_italics("Hello</i> <i>world</i>"),
+            "Hello <i>world</i>"
+        );
+        assert_eq!(repair_italics("a < b"), "a < b");
+    }
+
+    #[test]
+    fn dialogue_dashes() {
+        assert_eq!(
+            fix_dialogue_dashes("Are you coming?\n-Yes."),
+            "- Are you coming?\n- Yes."
+        );
+        assert_eq!(
+            fix_dialogue_dashes("\u{2013}  No.\n- Why?"),
+            "- No.\n- Why?"
+        );
+        assert_eq!(
+            fix_dialogue_dashes("I was going\n-- to say"),
+            "I was going\n-- to say"
+        );
+        assert_eq!(
+            fix_dialogue_dashes("It's -5 degrees.\n-5 or -10?"),
+            "It's -5 degrees.\n-5 or -10?"
+        );
+    }
+
+    #[test]
+    fn arrow_spacing() {
+        let input = "1\r\n00:00:01,000-->  00:00:02,000\r\nHello -->world\r\n\r\n\
+                     2\r\n00:00:03,000 --> 00:00:04,000\r\nWorld";
+        assert!(Subtitles::parse_from_str(input.to_string()).is_err());
+        assert!(FixConfig::new().parse(input).is_err());
+
+        let (subs, changes) = FixConfig::new()
+            .with_fixer(Fixer::ArrowSpacing)
+            .parse(input)
+            .unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, "Hello -->world");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].fixer, Fixer::ArrowSpacing);
+        assert_eq!(changes[0].after.as_ref(), Some(&subs[0]));
+    }
+
+    #[test]
+    fn lowercase_l() {
+        assert_eq!(fix_lowercase_l("l'm sure l know"), "I'm sure I know");
+        assert_eq!(fix_lowercase_l("l'ocean, all well"), "l'ocean, all well");
+    }
+
+    #[test]
+    fn individually_toggleable() {
+        let mut subs = Subtitles::parse_from_str(
+            "5\n00:00:01,000 --> 00:00:02,000\n  Hello  there  ".to_string(),
+        )
+        .unwrap();
+        let changes = subs.fix(&FixConfig::new().with_fixer(Fixer::CollapseSpaces));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].fixer, Fixer::CollapseSpaces);
+        assert_eq!(subs[0].text, " Hello there ");
+        assert_eq!(subs[0].num, 5);
+    }
+}
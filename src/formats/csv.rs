@@ -0,0 +1,421 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! CSV and TSV tables, for working on subtitles in spreadsheets.
+//!
+//! Every subtitle is a row, with the [`Column`]s chosen by a [`CsvConfig`]. Times are written
+//! as `00:00:01.000` and durations in seconds. Fields holding the delimiter, quotes or line
+//! breaks are quoted, so subtitles with several lines stay in a single cell.
+//!
+//! When reading, columns are mapped by the names in the header row, ignoring case and accepting
+//! common alternatives such as "Start Time" or "#". Unknown columns are ignored, and the end
+//! time may be given by a duration instead. Times may also be plain numbers of seconds.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::csv::{self, Column, CsvConfig};
+//!
+//! let input = "Start Time;Duration;Subtitle\n0:00:01.5;2;\"Hello;\nworld\"\n";
+//! let subs = csv::parse(input, None).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,500 --> 00:00:03,500\nHello;\nworld");
+//!
+//! let config = CsvConfig {
+//!     columns: vec![Column::Start, Column::PlainText, Column::Cps],
+//!     ..CsvConfig::tsv()
+//! };
+//! assert_eq!(csv::format(&subs, &config), "start\tplain_text\tcps\n00:00:01.500\t\"Hello;\nworld\"\t5.5\n");
+//! ```
+//!
+//! [`Column`]: enum.Column.html
+//! [`CsvConfig`]: struct.CsvConfig.html
+
+use super::parse_fraction;
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+use std::cmp;
+
+/// A column of a table of subtitles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    /// The number of the subtitle.
+    Num,
+    Start,
+    End,
+    /// The duration in seconds.
+    Duration,
+    /// The text, including formatting tags.
+    Text,
+    /// The text without formatting tags.
+    PlainText,
+    /// The reading speed, in characters per second. Ignored when reading.
+    Cps,
+}
+
+impl Column {
+    /// Returns the name of the column used in header rows.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Num => "num",
+            Column::Start => "start",
+            Column::End => "end",
+            Column::Duration => "duration",
+            Column::Text => "text",
+            Column::PlainText => "plain_text",
+            Column::Cps => "cps",
+        }
+    }
+
+    /// Returns the column with the given name, ignoring case, spaces and punctuation, and
+    /// accepting common alternatives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::formats::csv::Column;
+    ///
+    /// assert_eq!(Column::from_name("Start Time"), Some(Column::Start));
+    /// assert_eq!(Column::from_name("#"), Some(Column::Num));
+    /// assert_eq!(Column::from_name("Notes"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Column> {
+        let name: String = name
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '#')
+            .flat_map(char::to_lowercase)
+            .collect();
+        match name.as_str() {
+            "#" | "num" | "number" | "no" | "index" | "id" => Some(Column::Num),
+            "start" | "starttime" | "begin" | "in" | "timein" => Some(Column::Start),
+            "end" | "endtime" | "stop" | "out" | "timeout" => Some(Column::End),
+            "duration" | "length" => Some(Column::Duration),
+            "text" | "subtitle" | "caption" | "dialogue" => Some(Column::Text),
+            "plaintext" => Some(Column::PlainText),
+            "cps" | "charspersecond" | "characterspersecond" | "readingspeed" => Some(Column::Cps),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the column for a subtitle.
+    fn value(&self, sub: &Subtitle) -> String {
+        let millis =
+            sub.end_time.to_milliseconds() as i64 - sub.start_time.to_milliseconds() as i64;
+        match self {
+            Column::Num => sub.num.to_string(),
+            Column::Start => timestamp(&sub.start_time),
+            Column::End => timestamp(&sub.end_time),
+            Column::Duration => format!("{:.3}", millis as f64 / 1000.0),
+            Column::Text => sub.text.clone(),
+            Column::PlainText => sub.plain_text(),
+            Column::Cps => {
+                let chars = sub.plain_text().chars().filter(|&c| c != '\n').count();
+                if millis > 0 {
+                    format!("{:.1}", chars as f64 * 1000.0 / millis as f64)
+                } else {
+                    String::new()
+                }
+            }
+        }
+    }
+}
+
+/// The layout of a table of subtitles.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CsvConfig {
+    /// The character separating fields, usually `,` or a tab.
+    pub delimiter: char,
+    /// The columns to write, in order.
+    pub columns: Vec<Column>,
+    /// Start with a header row holding the names of the columns.
+    pub header: bool,
+}
+
+impl Default for CsvConfig {
+    fn default() -> CsvConfig {
+        CsvConfig {
+            delimiter: ',',
+            columns: vec![Column::Num, Column::Start, Column::End, Column::Text],
+            header: true,
+        }
+    }
+}
+
+impl CsvConfig {
+    /// Returns the default configuration with tabs separating fields.
+    pub fn tsv() -> CsvConfig {
+        CsvConfig {
+            delimiter: '\t',
+            ..Default::default()
+        }
+    }
+}
+
+/// Writes a timestamp in the format "hh:mm:ss.mmm".
+fn timestamp(time: &Timestamp) -> String {
+    time.to_string().replace(',', ".")
+}
+
+/// Parses a time written as "h:mm:ss.mmm", "mm:ss.mmm" or a number of seconds, with a fraction of
+/// any length after a dot or a comma.
+fn parse_time(s: &str) -> Result<Timestamp, ParsingError> {
+    let s = s.trim();
+    if !s.contains(':') {
+        let seconds: f64 = s
+            .replace(',', ".")
+            .parse()
+            .map_err(|_| ParsingError::MalformedTimestamp)?;
+        if !(0.0..=Timestamp::MAX_TIMESTAMP_MILLIS as f64 / 1000.0).contains(&seconds) {
+            return Err(ParsingError::MalformedTimestamp);
+        }
+        return Ok(Timestamp::from_milliseconds(
+            (seconds * 1000.0).round() as u32
+        ));
+    }
+    let (time, fraction) = s.rsplit_once(['.', ',']).unwrap_or((s, ""));
+    let hours = if time.matches(':').count() == 1 {
+        "0:"
+    } else {
+        ""
+    };
+    let fraction = parse_fraction(fraction).ok_or(ParsingError::MalformedTimestamp)?;
+    Timestamp::parse(&format!("{}{},{}", hours, time, fraction))
+}
+
+/// Quotes a field if it holds the delimiter, quotes or line breaks.
+fn escape(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits the contents of a table into records of fields. A field starting with a quote lasts
+/// until the closing quote, with doubled quotes standing for a single one, while other quotes
+/// are kept as they are. Empty records are skipped.
+fn records(input: &str, delimiter: char) -> Result<Vec<Vec<String>>, ParsingError> {
+    let mut res = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().filter(|&c| c != '\r').peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                field.push(c);
+            } else if chars.next_if_eq(&'"').is_some() {
+                field.push('"');
+            } else {
+                quoted = false;
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' {
+            record.push(std::mem::take(&mut field));
+            res.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if quoted {
+        return Err(ParsingError::MalformedFile(
+            "unterminated quoted field".to_string(),
+        ));
+    }
+    record.push(field);
+    res.push(record);
+    res.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    Ok(res)
+}
+
+/// Guesses the delimiter of a table from its first line: a tab if there is one, otherwise the
+/// more frequent of `;` and `,`.
+pub(crate) fn detect_delimiter(input: &str) -> char {
+    let first = input.lines().next().unwrap_or_default();
+    if first.contains('\t') {
+        '\t'
+    } else if first.matches(';').count() > first.matches(',').count() {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Maps the fields of a header row to columns.
+fn header_columns(header: &[String]) -> Vec<Option<Column>> {
+    header.iter().map(|name| Column::from_name(name)).collect()
+}
+
+/// Checks if the first line of a table is a header row naming the columns needed to read
+/// subtitles.
+pub(crate) fn has_header(input: &str, delimiter: char) -> bool {
+    let first = input
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .next()
+        .unwrap_or_default();
+    let fields: Vec<String> = first.split(delimiter).map(str::to_string).collect();
+    let columns = header_columns(&fields);
+    let has = |column| columns.contains(&Some(column));
+    has(Column::Start)
+        && (has(Column::End) || has(Column::Duration))
+        && (has(Column::Text) || has(Column::PlainText))
+}
+
+/// Constructs a new Subtitles collection by parsing a table of subtitles whose first row names
+/// its columns, separated by **delimiter**, or by the delimiter detected from the header row if
+/// None. Subtitles without a number column are numbered starting from 1.
+///
+/// A row needs a start time, an end time or duration, and text, which is taken from the text
+/// column if there is one, or the plain text one otherwise.
+///
+/// # Errors
+///
+/// If the header lacks any of the needed columns, or a quoted field is never closed, a
+/// MalformedFile error variant will be returned. If a row lacks a needed value, a
+/// BadSubtitleStructure variant with its position will be returned, while malformed values
+/// result in a MalformedTimestamp or ParseIntError variant.
+pub fn parse(input: &str, delimiter: Option<char>) -> Result<Subtitles, ParsingError> {
+    let input = input.trim_start_matches('\u{feff}');
+    let delimiter = delimiter.unwrap_or_else(|| detect_delimiter(input));
+    let mut rows = records(input, delimiter)?.into_iter();
+    let columns = header_columns(&rows.next().unwrap_or_default());
+    let position = |column| columns.iter().position(|c| *c == Some(column));
+    let missing = |name: &str| ParsingError::MalformedFile(format!("no {} column", name));
+
+    let start = position(Column::Start).ok_or_else(|| missing("start"))?;
+    let end = position(Column::End);
+    let duration = position(Column::Duration);
+    if end.is_none() && duration.is_none() {
+        return Err(missing("end or duration"));
+    }
+    let text = position(Column::Text)
+        .or_else(|| position(Column::PlainText))
+        .ok_or_else(|| missing("text"))?;
+    let num = position(Column::Num);
+
+    let mut subs = Subtitles::new();
+    for row in rows {
+        let index = subs.len() + 1;
+        let field = |i: Option<usize>| {
+            i.and_then(|i| row.get(i))
+                .map(|field| field.trim())
+                .filter(|field| !field.is_empty())
+        };
+        let err = || ParsingError::BadSubtitleStructure(index);
+        let start_time = parse_time(field(Some(start)).ok_or_else(err)?)?;
+        let end_time = match (field(end), field(duration)) {
+            (Some(end), _) => parse_time(end)?,
+            (None, Some(duration)) => {
+                let millis = start_time.to_milliseconds() + parse_time(duration)?.to_milliseconds();
+                Timestamp::from_milliseconds(cmp::min(millis, Timestamp::MAX_TIMESTAMP_MILLIS))
+            }
+            (None, None) => return Err(err()),
+        };
+        let num = match field(num) {
+            Some(num) => num.parse()?,
+            None => index,
+        };
+        let text = row.get(text).map_or("", |text| text.trim()).to_string();
+        subs.push(Subtitle::new(num, start_time, end_time, text));
+    }
+    Ok(subs)
+}
+
+/// Returns the subtitles as a table laid out as described by **config**.
+pub fn format(subs: &Subtitles, config: &CsvConfig) -> String {
+    let mut res = String::new();
+    let mut push_row = |fields: Vec<String>| {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| escape(field, config.delimiter))
+            .collect();
+        res.push_str(&fields.join(&config.delimiter.to_string()));
+        res.push('\n');
+    };
+    if config.header {
+        push_row(
+            config
+                .columns
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect(),
+        );
+    }
+    for sub in subs {
+        push_row(config.columns.iter().map(|c| c.value(sub)).collect());
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,500\n<i>\"Quoted\"</i>, really\nsecond line\n\n\
+             2\n00:00:03,000 --> 00:00:03,000\nEmpty"
+                .to_string(),
+        )
+        .unwrap();
+        let config = CsvConfig {
+            columns: vec![
+                Column::Num,
+                Column::Start,
+                Column::End,
+                Column::Duration,
+                Column::Text,
+                Column::Cps,
+            ],
+            ..Default::default()
+        };
+        let output = format(&subs, &config);
+        assert_eq!(
+            output,
+            "num,start,end,duration,text,cps\n\
+             1,00:00:01.000,00:00:02.500,1.500,\"<i>\"\"Quoted\"\"</i>, really\nsecond line\",18.0\n\
+             2,00:00:03.000,00:00:03.000,0.000,Empty,\n"
+        );
+        assert_eq!(parse(&output, None).unwrap(), subs);
+        let tsv = format(&subs, &CsvConfig::tsv());
+        assert_eq!(parse(&tsv, Some('\t')).unwrap(), subs);
+    }
+
+    #[test]
+    fn tolerant_import() {
+        let input = "\u{feff}Notes,In,Out,Plain Text,Caption\r\n\
+                     ,1.25,00:03.5,x,\"Say \"\"hi\"\"\"\r\n\
+                     ,,,,\r\n\
+                     nice,0:00:04,0:00:05.25,,Bye \"now\"\r\n";
+        let subs = parse(input, None).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].start_time, Timestamp::new(0, 0, 1, 250));
+        assert_eq!(subs[0].end_time, Timestamp::new(0, 0, 3, 500));
+        assert_eq!(subs[0].text, "Say \"hi\"");
+        assert_eq!(subs[1].num, 2);
+        assert_eq!(subs[1].end_time, Timestamp::new(0, 0, 5, 250));
+        assert_eq!(subs[1].text, "Bye \"now\"");
+
+        assert!(matches!(
+            parse("start,text\n1,Hi", None),
+            Err(ParsingError::MalformedFile(_))
+        ));
+        assert!(matches!(
+            parse("start,end,text\n1,2,Hi\n,3,Bye", None),
+            Err(ParsingError::BadSubtitleStructure(2))
+        ));
+        assert!(matches!(
+            parse("start,end,text\n1,2,\"Hi", None),
+            Err(ParsingError::MalformedFile(_))
+        ));
+        assert!(parse("start,end,text\n0:00:01.ab€,2,Hi", None).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! LRC synchronized lyrics.
+//!
+//! Every line of an LRC file starts with one or more `[mm:ss.xx]` tags, the line being sung at
+//! each of those times, and lasts until the next line starts. A line without any text clears the
+//! screen. ID tags such as `[ar:Artist]` are described by [`Header`], and the `[offset:]` tag,
+//! in milliseconds, makes the lyrics appear earlier when positive.
+//!
+//! In enhanced LRC, `<mm:ss.xx>` tags within a line give the time every word is sung at. They
+//! are read into the [`words`] of subtitles, and written back when asked to.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::lrc::{self, Header};
+//!
+//! let input = "[ar:Someone]\n[00:12.00][00:30.00]<00:12.00>La <00:12.50>la\n[00:15.00]Oh\n[00:17.20]";
+//! let (header, subs) = lrc::parse_with_header(input).unwrap();
+//! assert_eq!(header.artist, "Someone");
+//! assert_eq!(subs.to_string(), "1\n00:00:12,000 --> 00:00:15,000\nLa la\n\n\
+//!                               2\n00:00:15,000 --> 00:00:17,200\nOh\n\n\
+//!                               3\n00:00:30,000 --> 00:00:33,000\nLa la");
+//! assert_eq!(subs[2].words()[1].start_time.to_string(), "00:00:30,500");
+//!
+//! let output = lrc::format(&subs, &header, true);
+//! assert!(output.starts_with("[ar:Someone]\n[00:12.00]<00:12.00>La <00:12.50>la <00:15.00>\n"));
+//! ```
+//!
+//! [`Header`]: struct.Header.html
+//! [`words`]: ../../struct.Subtitle.html#method.words
+
+use super::parse_fraction;
+use crate::words::Word;
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+
+/// The duration in milliseconds of the last line, unless the header gives the length of the song.
+const LAST_LINE_DURATION: i64 = 3000;
+
+/// The ID tags read into a [`Header`](struct.Header.html).
+const ID_TAGS: [&str; 10] = [
+    "ti", "ar", "al", "au", "lr", "length", "by", "re", "ve", "offset",
+];
+
+/// The ID tags of an LRC file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    /// The author of the song.
+    pub author: String,
+    pub lyricist: String,
+    /// The length of the song, e.g. "03:45".
+    pub length: String,
+    /// The creator of the LRC file.
+    pub creator: String,
+    /// The program the file was made with.
+    pub tool: String,
+    pub version: String,
+    /// The offset in milliseconds added to every time tag, making the lyrics appear earlier when
+    /// positive.
+    pub offset: i32,
+}
+
+impl Header {
+    /// Returns the text tags of the header along with their values.
+    fn fields(&self) -> [(&'static str, &String); 9] {
+        [
+            ("ti", &self.title),
+            ("ar", &self.artist),
+            ("al", &self.album),
+            ("au", &self.author),
+            ("lr", &self.lyricist),
+            ("length", &self.length),
+            ("by", &self.creator),
+            ("re", &self.tool),
+            ("ve", &self.version),
+        ]
+    }
+
+    /// Sets the field of an ID tag, ignoring unknown tags.
+    fn set(&mut self, tag: &str, value: &str) -> Result<(), ParsingError> {
+        let value = value.trim();
+        let field = match tag.trim().to_ascii_lowercase().as_str() {
+            "ti" => &mut self.title,
+            "ar" => &mut self.artist,
+            "al" => &mut self.album,
+            "au" => &mut self.author,
+            "lr" => &mut self.lyricist,
+            "length" => &mut self.length,
+            "by" => &mut self.creator,
+            "re" => &mut self.tool,
+            "ve" => &mut self.version,
+            "offset" => {
+                self.offset = value.parse()?;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        *field = value.to_string();
+        Ok(())
+    }
+}
+
+/// Parses a time such as "01:02.50" into milliseconds. Fractions of any length, a colon before
+/// the fraction and no fraction at all are accepted too.
+fn parse_time(s: &str) -> Option<i64> {
+    let (minutes, rest) = s.trim().split_once(':')?;
+    let (seconds, fraction) = rest.split_once(['.', ':']).unwrap_or((rest, ""));
+    let is_number = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if minutes.is_empty()
+        || seconds.is_empty()
+        || ![minutes, seconds, fraction]
+            .iter()
+            .all(|part| is_number(part))
+    {
+        return None;
+    }
+    let millis = parse_fraction(fraction)? as i64;
+    let seconds: i64 = seconds.parse().ok().filter(|s| *s < 60)?;
+    minutes
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(60_000)?
+        .checked_add(seconds * 1000 + millis)
+}
+
+/// Checks if a line starts with a time tag or a known ID tag.
+pub(crate) fn starts_with_tag(line: &str) -> bool {
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .is_some_and(|(tag, _)| {
+            parse_time(tag).is_some()
+                || tag.split_once(':').is_some_and(|(key, _)| {
+                    ID_TAGS.contains(&key.trim().to_ascii_lowercase().as_str())
+                })
+        })
+}
+
+/// Writes a number of milliseconds as a time in the format "mm:ss.xx", rounded to the nearest
+/// centisecond.
+fn time_tag(millis: i64) -> String {
+    let centis = (millis.max(0) + 5) / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// A part of a line along with the time in milliseconds of its word tag, if any.
+type Segment = (Option<i64>, String);
+
+/// Converts a number of milliseconds to a timestamp.
+fn to_timestamp(millis: i64) -> Timestamp {
+    Timestamp::from_milliseconds(millis.clamp(0, Timestamp::MAX_TIMESTAMP_MILLIS as i64) as u32)
+}
+
+/// Splits the text of a line at its `<mm:ss.xx>` word tags. The first part has no time.
+fn segments(text: &str) -> Vec<Segment> {
+    let mut res = vec![(None, String::new())];
+    let mut rest = text;
+    while let Some(pos) = rest.find('<') {
+        let tag = rest[pos + 1..]
+            .split_once('>')
+            .and_then(|(tag, after)| Some((parse_time(tag)?, after)));
+        let last = &mut res.last_mut().unwrap().1;
+        match tag {
+            Some((time, after)) => {
+                last.push_str(&rest[..pos]);
+                res.push((Some(time), String::new()));
+                rest = after;
+            }
+            None => {
+                last.push_str(&rest[..=pos]);
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    res.last_mut().unwrap().1.push_str(rest);
+    res
+}
+
+/// Returns the timed words of a line sung from **start** to **end**, shifting the times of its
+/// word tags by **shift** milliseconds.
+fn words(segments: &[Segment], start: i64, end: i64, shift: i64) -> Vec<Word> {
+    if segments.len() < 2 {
+        return Vec::new();
+    }
+    let mut res = Vec::new();
+    for (i, (time, text)) in segments.iter().enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let word_start = time
+            .map_or(start, |time| time.saturating_add(shift))
+            .clamp(start, end);
+        let word_end = segments
+            .get(i + 1)
+            .and_then(|(next, _)| *next)
+            .map_or(end, |next| next.saturating_add(shift))
+            .clamp(word_start, end);
+        res.push(Word::new(
+            to_timestamp(word_start),
+            to_timestamp(word_end),
+            text.trim().to_string(),
+        ));
+    }
+    res
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an LRC file, along with its
+/// header. A line sung at several times becomes a subtitle for each of them. Subtitles are
+/// sorted by start time and numbered starting from 1.
+///
+/// Every line ends when the next one starts, while the last one lasts until the end of the song
+/// if the header gives its length, or for 3 seconds otherwise. Lines without time tags are
+/// ignored.
+///
+/// # Errors
+///
+/// If the `[offset:]` tag doesn't hold a whole number, a ParseIntError error variant will be
+/// returned.
+pub fn parse_with_header(input: &str) -> Result<(Header, Subtitles), ParsingError> {
+    let mut header = Header::default();
+    // the time tags of every line along with its text
+    let mut lines = Vec::new();
+    for line in input.trim_start_matches('\u{feff}').lines().map(str::trim) {
+        let mut times = Vec::new();
+        let mut rest = line;
+        while let Some((tag, remaining)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            match parse_time(tag) {
+                Some(time) => times.push(time),
+                None if times.is_empty() => {
+                    if let Some((key, value)) = tag.split_once(':') {
+                        header.set(key, value)?;
+                    }
+                }
+                None => (),
+            }
+            rest = remaining;
+        }
+        if !times.is_empty() {
+            lines.push((times, segments(rest.trim())));
+        }
+    }
+
+    // every time a line is sung, with the amount its word tags are shifted by
+    let offset = header.offset as i64;
+    let mut entries: Vec<(i64, i64, &[Segment])> = Vec::new();
+    for (times, segments) in &lines {
+        for time in times {
+            entries.push((
+                time.saturating_sub(offset),
+                (time - times[0]).saturating_sub(offset),
+                segments,
+            ));
+        }
+    }
+    entries.sort_by_key(|(start, ..)| *start);
+
+    let length = parse_time(&header.length);
+    let mut subs = Subtitles::new();
+    for (i, (start, shift, segments)) in entries.iter().enumerate() {
+        let text: String = segments.iter().map(|(_, text)| text.as_str()).collect();
+        if text.trim().is_empty() {
+            continue;
+        }
+        let end = match entries.get(i + 1) {
+            Some((next, ..)) => *next,
+            None => length
+                .filter(|length| length > start)
+                .unwrap_or(start.saturating_add(LAST_LINE_DURATION)),
+        };
+        let mut sub = Subtitle::new(
+            subs.len() + 1,
+            to_timestamp(*start),
+            to_timestamp(end),
+            text.trim().to_string(),
+        );
+        sub.words = words(segments, *start, end, *shift);
+        subs.push(sub);
+    }
+    Ok((header, subs))
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an LRC file.
+///
+/// # Errors
+///
+/// See [`parse_with_header`](fn.parse_with_header.html).
+pub fn parse(input: &str) -> Result<Subtitles, ParsingError> {
+    parse_with_header(input).map(|(_, subs)| subs)
+}
+
+/// Inserts a word tag into the text of a line before each of its words, followed by a tag
+/// holding the end time of the last word. Returns None if there are no words or they can't all
+/// be found in the text, in order.
+fn with_word_tags(
+    text: &str,
+    words: &[Word],
+    tag: &dyn Fn(&Timestamp) -> String,
+) -> Option<String> {
+    let last = words.last()?;
+    let mut res = String::new();
+    let mut rest = text;
+    for word in words {
+        let pos = rest.find(word.text.as_str())?;
+        res.push_str(&rest[..pos]);
+        res.push_str(&format!("<{}>", tag(&word.start_time)));
+        res.push_str(&word.text);
+        rest = &rest[pos + word.text.len()..];
+    }
+    res.push_str(rest);
+    res.push_str(&format!(" <{}>", tag(&last.end_time)));
+    Some(res)
+}
+
+/// Returns the contents of an LRC file holding the subtitles, without any formatting tags and
+/// with their lines joined by spaces, using the given header. Times are rounded to the nearest
+/// centisecond, and a subtitle is followed by a line clearing the screen unless the next one
+/// starts when it ends.
+///
+/// If **enhanced** is true, a `<mm:ss.xx>` tag is inserted into the text of a subtitle where each
+/// of its words occurs, and the last one is followed by a tag holding its end time. Subtitles whose
+/// words can't all be found in their text, in order, are written without word tags.
+pub fn format(subs: &Subtitles, header: &Header, enhanced: bool) -> String {
+    let mut res = String::new();
+    for (tag, value) in header.fields() {
+        if !value.is_empty() {
+            res.push_str(&format!("[{}:{}]\n", tag, value));
+        }
+    }
+    if header.offset != 0 {
+        res.push_str(&format!("[offset:{:+}]\n", header.offset));
+    }
+
+    let tag = |time: &Timestamp| time_tag(time.to_milliseconds() as i64 + header.offset as i64);
+    let subs: Vec<_> = subs.into_iter().collect();
+    for (i, sub) in subs.iter().enumerate() {
+        let mut text = sub.plain_text().replace('\n', " ");
+        if enhanced {
+            text = with_word_tags(&text, &sub.words, &tag).unwrap_or(text);
+        }
+        res.push_str(&format!("[{}]{}\n", tag(&sub.start_time), text));
+        let end = tag(&sub.end_time);
+        if subs
+            .get(i + 1)
+            .is_none_or(|next| tag(&next.start_time) != end)
+        {
+            res.push_str(&format!("[{}]\n", end));
+        }
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn tags_and_offset() {
+        assert_eq!(parse_time("01:02.5"), Some(62_500));
+        assert_eq!(parse_time("61:02:345"), Some(3_662_345));
+        assert_eq!(parse_time("00:02"), Some(2000));
+        assert_eq!(parse_time("ar:Someone"), None);
+        assert_eq!(parse_time("999999999999999999:00.00"), None);
+
+        let input = "\u{feff}[ti:Song]\n[length: 00:20]\n[offset:+500]\n\
+                     # not a lyric\n\
+                     [00:01.00]First <b>line\n\
+                     [00:04.00]\n\
+                     [00:05.00][00:10.00]<00:05.00>Re<00:05.40>peat<00:06.00>ed\n";
+        let (header, subs) = parse_with_header(input).unwrap();
+        assert_eq!(header.title, "Song");
+        assert_eq!(header.offset, 500);
+        assert_eq!(subs.len(), 3);
+        assert_eq!(subs[0].text, "First <b>line");
+        assert_eq!(subs[0].start_time, Timestamp::new(0, 0, 0, 500));
+        assert_eq!(subs[0].end_time, Timestamp::new(0, 0, 3, 500));
+        assert_eq!(subs[1].end_time, subs[2].start_time);
+        // the last line lasts until the end of the song
+        assert_eq!(subs[2].end_time, Timestamp::new(0, 0, 20, 0));
+        assert_eq!(subs[2].text, "Repeated");
+        let timings: Vec<(u32, u32)> = subs[2]
+            .words
+            .iter()
+            .map(|w| (w.start_time.to_milliseconds(), w.end_time.to_milliseconds()))
+            .collect();
+        assert_eq!(timings, [(9500, 9900), (9900, 10_500), (10_500, 20_000)]);
+        assert!(matches!(
+            parse("[offset:soon]"),
+            Err(ParsingError::ParseIntError(_))
+        ));
+
+        let output = format(&subs, &header, true);
+        assert!(output.starts_with("[ti:Song]\n[length:00:20]\n[offset:+500]\n"));
+        assert!(output.contains("[00:01.00]First line\n[00:04.00]\n"));
+        let (_, parsed) = parse_with_header(&output).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[2].words, subs[2].words);
+        assert_eq!(parsed[2].text, "Repeated");
+        assert!(output.contains("[00:10.00]<00:10.00>Re<00:10.40>peat<00:11.00>ed <00:20.50>\n"));
+
+        // edited text no longer matching the words is kept
+        let edited = |text: &str| {
+            let mut edited = subs.clone();
+            for sub in &mut edited {
+                if sub.num == 3 {
+                    sub.text = text.to_string();
+                }
+            }
+            edited
+        };
+        let output = format(&edited("Repeated twice"), &Header::default(), true);
+        assert!(
+            output.contains("[00:09.50]<00:09.50>Re<00:09.90>peat<00:10.50>ed twice <00:20.00>\n")
+        );
+        let output = format(&edited("Something else"), &Header::default(), true);
+        assert!(output.contains("[00:09.50]Something else\n"));
+        assert!(
+            format(&subs, &Header::default(), false).ends_with("[00:09.50]Repeated\n[00:20.00]\n")
+        );
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! MicroDVD .sub files.
+//!
+//! Every line of a MicroDVD file is a subtitle, starting with its start and end frame, such as
+//! `{25}{50}`. The end frame may be left empty, in which case the end time is inferred as
+//! described by an [`EndTimeConfig`]. Lines of text are separated by `|`. `{y:i}`, `{y:b}` and
+//! `{y:u}` codes, or combinations such as `{y:i,b}`, style the line they start, while their `{Y:...}`
+//! versions style the following lines too. Other codes are removed.
+//!
+//! Frames are converted at a given frame rate, unless the file declares its own with a
+//! `{1}{1}23.976` line.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::{microdvd, EndTimeConfig};
+//!
+//! let subs = microdvd::parse("{25}{50}{y:i}Hello|world\n{75}{}Bye", 25.0, &EndTimeConfig::default()).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:02,000\n<i>Hello</i>\nworld\n\n\
+//!                               2\n00:00:03,000 --> 00:00:06,000\nBye");
+//!
+//! assert_eq!(microdvd::format(&subs, 25.0), "{25}{50}{y:i}Hello|world\n{75}{150}Bye\n");
+//! ```
+//!
+//! [`EndTimeConfig`]: ../struct.EndTimeConfig.html
+
+use super::{line_styles, to_markup, EndTimeConfig, Style};
+use crate::{ParsingError, Subtitles, Timestamp};
+
+/// Converts a frame number to a timestamp.
+fn from_frame(frame: u64, fps: f64) -> Timestamp {
+    let millis = (frame as f64 * 1000.0 / fps).round();
+    Timestamp::from_milliseconds(millis.min(Timestamp::MAX_TIMESTAMP_MILLIS as f64) as u32)
+}
+
+/// Converts a timestamp to the nearest frame number.
+fn to_frame(time: &Timestamp, fps: f64) -> u64 {
+    (time.to_milliseconds() as f64 * fps / 1000.0).round() as u64
+}
+
+/// Applies the style letters of a `{y:...}` code.
+fn apply_style(style: &mut Style, letters: &str) {
+    for letter in letters.split(',') {
+        match letter.trim() {
+            "i" => style.italic = true,
+            "b" => style.bold = true,
+            "u" => style.underline = true,
+            _ => (),
+        }
+    }
+}
+
+/// Converts the text of a subtitle into .srt markup.
+fn decode_text(text: &str) -> String {
+    let mut all_lines = Style::default();
+    let lines: Vec<_> = text
+        .split('|')
+        .map(|mut line| {
+            let mut style = all_lines;
+            while let Some((code, rest)) = line.strip_prefix('{').and_then(|l| l.split_once('}')) {
+                match code.split_once(':') {
+                    Some(("y", letters)) => apply_style(&mut style, letters),
+                    Some(("Y", letters)) => {
+                        apply_style(&mut all_lines, letters);
+                        apply_style(&mut style, letters);
+                    }
+                    _ => (),
+                }
+                line = rest;
+            }
+            vec![(style, line.to_string())]
+        })
+        .collect();
+    to_markup(&lines)
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of a MicroDVD file, converting
+/// frames at **fps** frames per second unless the file declares its frame rate. Subtitles are
+/// sorted by start time and numbered starting from 1.
+///
+/// # Errors
+///
+/// If a line doesn't start with the frames of a subtitle, a BadSubtitleStructure error variant
+/// will be returned.
+///
+/// # Panics
+///
+/// Panics if **fps** is not positive.
+pub fn parse(input: &str, fps: f64, config: &EndTimeConfig) -> Result<Subtitles, ParsingError> {
+    assert!(fps > 0.0, "the frame rate must be positive");
+    let mut fps = fps;
+    let mut cues = Vec::new();
+    for line in input
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let num = cues.len() + 1;
+        let err = || ParsingError::BadSubtitleStructure(num);
+        let mut frames = [None, None];
+        let mut rest = line;
+        for frame in &mut frames {
+            let (value, remaining) = rest
+                .strip_prefix('{')
+                .and_then(|rest| rest.split_once('}'))
+                .ok_or_else(err)?;
+            if !value.trim().is_empty() {
+                *frame = Some(value.trim().parse::<u64>()?);
+            }
+            rest = remaining;
+        }
+        let start = frames[0].ok_or_else(err)?;
+        if cues.is_empty() && start <= 1 && frames[1] == Some(start) {
+            if let Some(declared) = rest.trim().parse::<f64>().ok().filter(|f| *f > 0.0) {
+                fps = declared;
+                continue;
+            }
+        }
+        cues.push((start, frames[1], rest));
+    }
+
+    let cues = cues
+        .into_iter()
+        .map(|(start, end, text)| {
+            (
+                from_frame(start, fps),
+                end.map(|end| from_frame(end, fps)),
+                decode_text(text),
+            )
+        })
+        .collect();
+    Ok(config.subtitles(cues))
+}
+
+/// Returns the contents of a MicroDVD file holding the subtitles, converting times to frames at
+/// **fps** frames per second. Lines with a single style get a `{y:...}` code, and other formatting
+/// is removed.
+pub fn format(subs: &Subtitles, fps: f64) -> String {
+    let mut res = String::new();
+    for sub in subs {
+        let lines: Vec<String> = line_styles(&sub.text)
+            .into_iter()
+            .map(|(style, line)| {
+                let letters: Vec<&str> = [
+                    (style.italic, "i"),
+                    (style.bold, "b"),
+                    (style.underline, "u"),
+                ]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, letter)| *letter)
+                .collect();
+                if letters.is_empty() {
+                    line
+                } else {
+                    format!("{{y:{}}}{}", letters.join(","), line)
+                }
+            })
+            .collect();
+        res.push_str(&format!(
+            "{{{}}}{{{}}}{}\n",
+            to_frame(&sub.start_time, fps),
+            to_frame(&sub.end_time, fps),
+            lines.join("|")
+        ));
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn frame_rate_and_styles() {
+        let input = "{1}{1}23.976\n{24}{48}{Y:b}{c:$0000FF}Bold|{y:i,u}all three\n{72}{}{P:0}Next";
+        let subs = parse(input, 25.0, &EndTimeConfig::default()).unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].start_time, Timestamp::new(0, 0, 1, 1));
+        assert_eq!(subs[0].text, "<b>Bold</b>\n<i><b><u>all three</u></b></i>");
+        assert_eq!(subs[1].text, "Next");
+        assert_eq!(subs[1].end_time, Timestamp::new(0, 0, 6, 3));
+
+        let output = format(&subs, 23.976);
+        assert_eq!(
+            output,
+            "{24}{48}{y:b}Bold|{y:i,b,u}all three\n{72}{144}Next\n"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversion from and to subtitle formats other than .srt.
+//!
+//! Every format has its own module with a `parse` function, constructing a [`Subtitles`]
+//! collection, and a `format` function writing one. Formatting that the format supports is
+//! mapped to the usual .srt markup: `<i>`, `<b>` and `<u>` tags, and `{\an8}` for subtitles
+//! placed at the top of the screen.
+//!
+//! | Module        | Format                      | Feature |
+//! |---------------|-----------------------------|---------|
+//! | [`csv`]       | CSV and TSV tables          |         |
+//! | [`lrc`]       | LRC lyrics                  |         |
+//! | [`microdvd`]  | MicroDVD                    |         |
+//! | [`mpl2`]      | MPL2                        |         |
+//! | [`sami`]      | SAMI (.smi)                 |         |
+//! | [`sbv`]       | YouTube SBV                 |         |
+//! | [`scc`]       | Scenarist SCC (CEA-608)     |         |
+//! | [`stl`]       | EBU STL (Tech 3264)         |         |
+//! | [`subviewer`] | SubViewer 2.0               |         |
+//! | [`tmplayer`]  | TMPlayer                    |         |
+//! | [`ttml`]      | TTML, DFXP and IMSC1 (text) | `ttml`  |
+//!
+//! Formats where end times are optional or missing take an [`EndTimeConfig`] describing how to
+//! infer them.
+//!
+//! When the format of a file isn't known in advance, [`Format`] detects it and reads or writes
+//! any of them with default options, which is what [`Subtitles::load`] and [`Subtitles::save`]
+//! use. Formats defined outside of the crate implement [`SubtitleReader`] and [`SubtitleWriter`]
+//! and are added to a [`Registry`] next to the built-in ones.
+//!
+//! [`Subtitles`]: ../struct.Subtitles.html
+//! [`EndTimeConfig`]: struct.EndTimeConfig.html
+//! [`Format`]: enum.Format.html
+//! [`SubtitleReader`]: trait.SubtitleReader.html
+//! [`SubtitleWriter`]: trait.SubtitleWriter.html
+//! [`Registry`]: struct.Registry.html
+//! [`Subtitles::load`]: ../struct.Subtitles.html#method.load
+//! [`Subtitles::save`]: ../struct.Subtitles.html#method.save
+//! [`csv`]: csv/index.html
+//! [`lrc`]: lrc/index.html
+//! [`microdvd`]: microdvd/index.html
+//! [`mpl2`]: mpl2/index.html
+//! [`sami`]: sami/index.html
+//! [`sbv`]: sbv/index.html
+//! [`scc`]: scc/index.html
+//! [`stl`]: stl/index.html
+//! [`subviewer`]: subviewer/index.html
+//! [`tmplayer`]: tmplayer/index.html
+//! [`ttml`]: ttml/index.html
+
+pub mod csv;
+pub mod lrc;
+pub mod microdvd;
+pub mod mpl2;
+pub mod sami;
+pub mod sbv;
+pub mod scc;
+pub mod stl;
+pub mod subviewer;
+pub mod tmplayer;
+#[cfg(feature = "ttml")]
+pub mod ttml;
+
+mod registry;
+mod traits;
+
+pub use registry::{Format, Registry};
+pub use traits::{Capabilities, SubtitleFormat, SubtitleReader, SubtitleWriter};
+
+use crate::{Subtitle, Subtitles, Timestamp};
+use std::cmp;
+
+/// The way end times are inferred in formats where they are optional or missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EndTimeConfig {
+    /// The duration in milliseconds of a subtitle without an end time.
+    pub default_duration: u32,
+    /// Make subtitles without an end time last until the next subtitle starts, instead of for the
+    /// default duration. The last subtitle still gets the default duration.
+    pub until_next: bool,
+}
+
+impl Default for EndTimeConfig {
+    fn default() -> EndTimeConfig {
+        EndTimeConfig {
+            default_duration: 3000,
+            until_next: false,
+        }
+    }
+}
+
+impl EndTimeConfig {
+    /// Constructs subtitles numbered from 1 out of cues with optional end times, sorting them by
+    /// start time. A missing end time never goes past the start of the next subtitle.
+    pub(crate) fn subtitles(
+        &self,
+        mut cues: Vec<(Timestamp, Option<Timestamp>, String)>,
+    ) -> Subtitles {
+        cues.sort_by_key(|(start, _, _)| *start);
+        let mut res = Subtitles::new();
+        for i in 0..cues.len() {
+            let (start, end, text) = &cues[i];
+            let next = cues.get(i + 1).map(|(next, _, _)| next.to_milliseconds());
+            let end = end.unwrap_or_else(|| {
+                let default = start.to_milliseconds() + self.default_duration;
+                let end = match next {
+                    Some(next) if self.until_next => next,
+                    Some(next) => cmp::min(default, next),
+                    None => default,
+                };
+                Timestamp::from_milliseconds(cmp::min(end, Timestamp::MAX_TIMESTAMP_MILLIS))
+            });
+            res.push(Subtitle::new(res.len() + 1, *start, end, text.clone()));
+        }
+        res
+    }
+}
+
+/// Parses the digits after the decimal point of a time as milliseconds, so that "5" stands for
+/// 500 and "05" for 50. Digits past the milliseconds are ignored. Returns None unless the
+/// fraction consists of ASCII digits only.
+pub(crate) fn parse_fraction(fraction: &str) -> Option<u32> {
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = format!("{:0<3}", fraction);
+    digits[..3].parse().ok()
+}
+
+/// The text styles that can be expressed with .srt markup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Style {
+    pub italic: bool,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+/// A line of text split into runs of the same style.
+pub(crate) type StyledLine = Vec<(Style, String)>;
+
+/// Splits a text with .srt markup into lines of styled runs. Unknown tags and `{...}` overrides
+/// are dropped, and styles carry over to the following lines until closed.
+pub(crate) fn styled_lines(text: &str) -> Vec<StyledLine> {
+    let mut lines = vec![StyledLine::new()];
+    let mut style = Style::default();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let close = match c {
+            '<' => rest.find('>'),
+            '{' => rest.find('}'),
+            _ => None,
+        };
+        if let Some(close) = close {
+            let tag = rest[1..close].trim().to_ascii_lowercase();
+            let enabled = !tag.starts_with('/');
+            match tag.trim_start_matches('/') {
+                "i" => style.italic = enabled,
+                "b" => style.bold = enabled,
+                "u" => style.underline = enabled,
+                _ => (),
+            }
+            rest = &rest[close + 1..];
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        if c == '\n' {
+            lines.push(StyledLine::new());
+            continue;
+        }
+        let line = lines.last_mut().expect("there is always a line");
+        match line.last_mut() {
+            Some((run_style, run)) if *run_style == style => run.push(c),
+            _ => line.push((style, c.to_string())),
+        }
+    }
+    lines
+}
+
+/// Writes lines of styled runs as text with .srt markup, opening and closing the tags on every
+/// line.
+pub(crate) fn to_markup(lines: &[StyledLine]) -> String {
+    let mut res = Vec::with_capacity(lines.len());
+    for line in lines {
+        let mut text = String::new();
+        let mut open: Vec<&str> = Vec::new();
+        for (style, run) in line.iter().filter(|(_, run)| !run.is_empty()) {
+            let wanted: Vec<&str> = [
+                (style.italic, "i"),
+                (style.bold, "b"),
+                (style.underline, "u"),
+            ]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, tag)| *tag)
+            .collect();
+            // close everything from the outermost tag that is no longer wanted
+            if let Some(pos) = open.iter().position(|tag| !wanted.contains(tag)) {
+                for tag in open.drain(pos..).rev() {
+                    text.push_str(&format!("</{}>", tag));
+                }
+            }
+            for tag in wanted {
+                if !open.contains(&tag) {
+                    text.push_str(&format!("<{}>", tag));
+                    open.push(tag);
+                }
+            }
+            text.push_str(run);
+        }
+        for tag in open.iter().rev() {
+            text.push_str(&format!("</{}>", tag));
+        }
+        res.push(text);
+    }
+    res.join("\n")
+}
+
+/// Removes the whitespace around every line, dropping the lines left empty.
+pub(crate) fn trim_lines(lines: &mut Vec<StyledLine>) {
+    for line in lines.iter_mut() {
+        while line.first().is_some_and(|(_, run)| run.trim().is_empty()) {
+            line.remove(0);
+        }
+        while line.last().is_some_and(|(_, run)| run.trim().is_empty()) {
+            line.pop();
+        }
+        if let Some((_, run)) = line.first_mut() {
+            *run = run.trim_start().to_string();
+        }
+        if let Some((_, run)) = line.last_mut() {
+            *run = run.trim_end().to_string();
+        }
+    }
+    lines.retain(|line| !line.is_empty());
+}
+
+/// Splits a text into lines without formatting, along with the style of every line. Lines mixing
+/// styles get the default one.
+pub(crate) fn line_styles(text: &str) -> Vec<(Style, String)> {
+    styled_lines(text)
+        .into_iter()
+        .map(|line| {
+            let mut styles = line
+                .iter()
+                .filter(|(_, run)| !run.trim().is_empty())
+                .map(|(style, _)| *style);
+            let first = styles.next().unwrap_or_default();
+            let style = if styles.all(|style| style == first) {
+                first
+            } else {
+                Style::default()
+            };
+            (style, line.into_iter().map(|(_, run)| run).collect())
+        })
+        .collect()
+}
+
+/// Checks if a text starts with an `{\an7}`, `{\an8}` or `{\an9}` tag placing it at the top of
+/// the screen.
+pub(crate) fn is_top_aligned(text: &str) -> bool {
+    ["{\\an7}", "{\\an8}", "{\\an9}"]
+        .iter()
+        .any(|tag| text.trim_start().starts_with(tag))
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn markup_round_trip() {
+        let lines =
+            styled_lines("{\\an8}<i>Hello <b>you</b>\nthere</i> <font color=\"red\">!</font>");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0][1],
+            (
+                Style {
+                    italic: true,
+                    bold: true,
+                    underline: false
+                },
+                "you".to_string()
+            )
+        );
+        assert_eq!(to_markup(&lines), "<i>Hello <b>you</b></i>\n<i>there</i> !");
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! MPL2 files.
+//!
+//! Every line of an MPL2 file is a subtitle, starting with its start and end time in deciseconds,
+//! such as `[10][25]`. The end time may be left empty, in which case it is inferred as described
+//! by an [`EndTimeConfig`]. Lines of text are separated by `|`, and lines starting with `/` are in
+//! italics.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::{mpl2, EndTimeConfig};
+//!
+//! let subs = mpl2::parse("[10][25]Hello|/world\n[30][]Bye", &EndTimeConfig::default()).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:02,500\nHello\n<i>world</i>\n\n\
+//!                               2\n00:00:03,000 --> 00:00:06,000\nBye");
+//!
+//! assert_eq!(mpl2::format(&subs), "[10][25]Hello|/world\n[30][60]Bye\n");
+//! ```
+//!
+//! [`EndTimeConfig`]: ../struct.EndTimeConfig.html
+
+use super::{line_styles, EndTimeConfig};
+use crate::{ParsingError, Subtitles, Timestamp};
+use std::cmp;
+
+/// Converts a number of deciseconds to a timestamp.
+fn from_deciseconds(deciseconds: u64) -> Timestamp {
+    let millis = cmp::min(
+        deciseconds.saturating_mul(100),
+        Timestamp::MAX_TIMESTAMP_MILLIS as u64,
+    );
+    Timestamp::from_milliseconds(millis as u32)
+}
+
+/// Converts a timestamp to a number of deciseconds, rounding to the nearest one.
+fn to_deciseconds(time: &Timestamp) -> u32 {
+    (time.to_milliseconds() + 50) / 100
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an MPL2 file. Subtitles are
+/// sorted by start time and numbered starting from 1.
+///
+/// # Errors
+///
+/// If a line doesn't start with the timing of a subtitle, a BadSubtitleStructure error variant
+/// will be returned.
+pub fn parse(input: &str, config: &EndTimeConfig) -> Result<Subtitles, ParsingError> {
+    let mut cues = Vec::new();
+    for line in input
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let num = cues.len() + 1;
+        let err = || ParsingError::BadSubtitleStructure(num);
+        let mut times = [None, None];
+        let mut rest = line;
+        for time in &mut times {
+            let (value, remaining) = rest
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+                .ok_or_else(err)?;
+            if !value.trim().is_empty() {
+                *time = Some(from_deciseconds(value.trim().parse()?));
+            }
+            rest = remaining;
+        }
+        let start = times[0].ok_or_else(err)?;
+        let text: Vec<String> = rest
+            .split('|')
+            .map(|line| match line.trim_start().strip_prefix('/') {
+                Some(italic) => format!("<i>{}</i>", italic.trim_start()),
+                None => line.to_string(),
+            })
+            .collect();
+        cues.push((start, times[1], text.join("\n")));
+    }
+    Ok(config.subtitles(cues))
+}
+
+/// Returns the contents of an MPL2 file holding the subtitles. Lines entirely in italics are
+/// marked as such, and other formatting is removed.
+pub fn format(subs: &Subtitles) -> String {
+    let mut res = String::new();
+    for sub in subs {
+        let lines: Vec<String> = line_styles(&sub.text)
+            .into_iter()
+            .map(|(style, line)| {
+                if style.italic {
+                    format!("/{}", line)
+                } else {
+                    line
+                }
+            })
+            .collect();
+        res.push_str(&format!(
+            "[{}][{}]{}\n",
+            to_deciseconds(&sub.start_time),
+            to_deciseconds(&sub.end_time),
+            lines.join("|")
+        ));
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn implied_end_times() {
+        let input = "[50][]Second\r\n[10][]First\r\n[20][35]/Both|/lines\r\n";
+        let subs = parse(input, &EndTimeConfig::default()).unwrap();
+        assert_eq!(subs.len(), 3);
+        assert_eq!(subs[0].text, "First");
+        // cut short by the next subtitle
+        assert_eq!(subs[0].end_time, Timestamp::new(0, 0, 2, 0));
+        assert_eq!(subs[1].text, "<i>Both</i>\n<i>lines</i>");
+        assert_eq!(subs[2].end_time, Timestamp::new(0, 0, 8, 0));
+
+        let config = EndTimeConfig {
+            default_duration: 1000,
+            until_next: true,
+        };
+        let subs = parse("[0][]One\n[100][]Two", &config).unwrap();
+        assert_eq!(subs[0].end_time, Timestamp::new(0, 0, 10, 0));
+        assert_eq!(subs[1].end_time, Timestamp::new(0, 0, 11, 0));
+        assert!(matches!(
+            parse("[0][10]One\nTwo", &config),
+            Err(ParsingError::BadSubtitleStructure(2))
+        ));
+        let subs = parse("[999999999999999999][]x", &config).unwrap();
+        assert_eq!(
+            subs[0].start_time.to_milliseconds(),
+            Timestamp::MAX_TIMESTAMP_MILLIS
+        );
+    }
+}
@@ -0,0 +1,691 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::traits::best_reader;
+#[cfg(feature = "ttml")]
+use super::ttml;
+use super::{
+    csv, lrc, microdvd, mpl2, sami, sbv, scc, stl, subviewer, tmplayer, Capabilities,
+    EndTimeConfig, SubtitleFormat, SubtitleReader, SubtitleWriter,
+};
+use crate::{ParsingError, Subtitles};
+use encoding_rs::Encoding;
+use std::cmp;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The number of bytes looked at when detecting the format of a file.
+const SNIFF_LEN: usize = 4096;
+
+/// The frame rate MicroDVD files are read at when they don't declare their own.
+const MICRODVD_FPS: f64 = 23.976;
+
+/// A subtitle format that can be read and written.
+///
+/// Formats are read and written with the defaults of their modules: end times are inferred with
+/// the default [`EndTimeConfig`], MicroDVD frames are converted at 23.976 frames per second
+/// unless the file declares its frame rate, SAMI files are read in their first language and
+/// written in English, and headers are left empty. The functions of a format's module give full
+/// control over these.
+///
+/// Formats behind optional features, such as TTML, are only available when their feature is
+/// enabled, so matches on a `Format` need a wildcard arm.
+///
+/// # Examples
+///
+/// ```
+/// use srtlib::formats::Format;
+///
+/// let input = b"{25}{50}Hello|world\n";
+/// let format = Format::detect(input, Some("sub")).unwrap();
+/// assert_eq!(format, Format::MicroDvd);
+///
+/// let subs = format.parse(input, None).unwrap();
+/// let output = Format::Mpl2.format(&subs, None).unwrap();
+/// assert_eq!(output, b"[10][21]Hello|world\n");
+/// ```
+///
+/// [`EndTimeConfig`]: struct.EndTimeConfig.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    Srt,
+    Csv,
+    Lrc,
+    MicroDvd,
+    Mpl2,
+    Sami,
+    Sbv,
+    Scc,
+    Stl,
+    SubViewer,
+    TmPlayer,
+    Tsv,
+    #[cfg(feature = "ttml")]
+    Ttml,
+}
+
+impl Format {
+    /// Every supported format.
+    pub const ALL: &'static [Format] = &[
+        Format::Srt,
+        Format::Csv,
+        Format::Lrc,
+        Format::MicroDvd,
+        Format::Mpl2,
+        Format::Sami,
+        Format::Sbv,
+        Format::Scc,
+        Format::Stl,
+        Format::SubViewer,
+        Format::TmPlayer,
+        Format::Tsv,
+        #[cfg(feature = "ttml")]
+        Format::Ttml,
+    ];
+
+    /// Returns the name of the format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Srt => "SubRip",
+            Format::Csv => "CSV",
+            Format::Lrc => "LRC",
+            Format::MicroDvd => "MicroDVD",
+            Format::Mpl2 => "MPL2",
+            Format::Sami => "SAMI",
+            Format::Sbv => "YouTube SBV",
+            Format::Scc => "Scenarist SCC",
+            Format::Stl => "EBU STL",
+            Format::SubViewer => "SubViewer 2.0",
+            Format::TmPlayer => "TMPlayer",
+            Format::Tsv => "TSV",
+            #[cfg(feature = "ttml")]
+            Format::Ttml => "TTML",
+        }
+    }
+
+    /// Returns the file extensions used by the format, without the leading dot, starting with the
+    /// most common one.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Format::Srt => &["srt"],
+            Format::Csv => &["csv"],
+            Format::Lrc => &["lrc"],
+            Format::MicroDvd => &["sub", "txt"],
+            Format::Mpl2 => &["txt", "mpl"],
+            Format::Sami => &["smi", "sami"],
+            Format::Sbv => &["sbv"],
+            Format::Scc => &["scc"],
+            Format::Stl => &["stl"],
+            Format::SubViewer => &["sub"],
+            Format::TmPlayer => &["txt"],
+            Format::Tsv => &["tsv", "tab"],
+            #[cfg(feature = "ttml")]
+            Format::Ttml => &["ttml", "dfxp", "xml"],
+        }
+    }
+
+    /// Returns what the format can hold.
+    pub fn capabilities(&self) -> Capabilities {
+        let (styling, positioning, millisecond_precision) = match self {
+            Format::Srt | Format::Csv | Format::Tsv => (true, true, true),
+            Format::MicroDvd | Format::Mpl2 => (true, false, false),
+            Format::Sami => (true, false, true),
+            Format::Sbv => (false, false, true),
+            Format::Scc | Format::Stl => (true, true, false),
+            Format::Lrc | Format::SubViewer | Format::TmPlayer => (false, false, false),
+            #[cfg(feature = "ttml")]
+            Format::Ttml => (true, true, true),
+        };
+        Capabilities {
+            styling,
+            positioning,
+            millisecond_precision,
+            binary: *self == Format::Stl,
+        }
+    }
+
+    /// Returns the format using the given file extension, ignoring case and a leading dot.
+    ///
+    /// Returns None if no format uses the extension, or if several do, like `sub` and `txt`.
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        let mut formats = Format::ALL
+            .iter()
+            .filter(|format| format.extensions().contains(&extension.as_str()));
+        match (formats.next(), formats.next()) {
+            (Some(format), None) => Some(*format),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file at the given path, judging by its extension.
+    ///
+    /// See [`from_extension`](#method.from_extension).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| Format::from_extension(&extension.to_string_lossy()))
+    }
+
+    /// Detects the format of the contents of a file, optionally along with its extension.
+    ///
+    /// The contents are looked at first, so a file with a misleading extension is still read
+    /// correctly, and the extension is only used if they don't resemble any format. See
+    /// [`SubtitleReader::detect`] for how confident the detection of every format is.
+    ///
+    /// [`SubtitleReader::detect`]: trait.SubtitleReader.html#tymethod.detect
+    pub fn detect(content: &[u8], extension: Option<&str>) -> Option<Format> {
+        best_reader(Format::ALL, content, extension).copied()
+    }
+
+    /// Constructs a new Subtitles collection by parsing the contents of a file in this format.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8. It is ignored
+    /// by EBU STL files, which declare their own character table.
+    ///
+    /// # Errors
+    ///
+    /// If the encoding label provided is not one of the labels specified by the [Encoding
+    /// Standard], a BadEncodingName error variant will be returned, while contents that aren't
+    /// valid utf-8 result in an IOError variant.
+    ///
+    /// If something unexpected is encountered during the parsing of the contents, a
+    /// corresponding error variant will be returned.
+    ///
+    /// [Encoding Standard]: https://encoding.spec.whatwg.org/#names-and-labels
+    pub fn parse(&self, content: &[u8], encoding: Option<&str>) -> Result<Subtitles, ParsingError> {
+        let input = || decode(content, encoding);
+        let config = EndTimeConfig::default();
+        match self {
+            Format::Srt => Subtitles::parse_from_str(input()?),
+            Format::Csv => csv::parse(&input()?, None),
+            Format::Lrc => lrc::parse(&input()?),
+            Format::MicroDvd => microdvd::parse(&input()?, MICRODVD_FPS, &config),
+            Format::Mpl2 => mpl2::parse(&input()?, &config),
+            Format::Sami => sami::parse(&input()?, None),
+            Format::Sbv => sbv::parse(&input()?),
+            Format::Scc => scc::parse(&input()?),
+            Format::Stl => stl::parse(content),
+            Format::SubViewer => subviewer::parse(&input()?),
+            Format::TmPlayer => tmplayer::parse(&input()?, &config),
+            Format::Tsv => csv::parse(&input()?, Some('\t')),
+            #[cfg(feature = "ttml")]
+            Format::Ttml => ttml::parse(&input()?),
+        }
+    }
+
+    /// Returns the contents of a file in this format holding the subtitles.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8. It is ignored
+    /// by EBU STL files, which declare their own character table.
+    ///
+    /// # Errors
+    ///
+    /// If the encoding label provided is not one of the labels specified by the [Encoding
+    /// Standard], a BadEncodingName error variant will be returned.
+    ///
+    /// If the subtitles can't be held by the format, such as SCC captions with lines too long
+    /// for the screen, an UnsupportedContent error variant will be returned.
+    ///
+    /// [Encoding Standard]: https://encoding.spec.whatwg.org/#names-and-labels
+    pub fn format(
+        &self,
+        subs: &Subtitles,
+        encoding: Option<&str>,
+    ) -> Result<Vec<u8>, ParsingError> {
+        let output = match self {
+            Format::Srt => subs.to_string(),
+            Format::Csv => csv::format(subs, &csv::CsvConfig::default()),
+            Format::Lrc => lrc::format(subs, &lrc::Header::default(), false),
+            Format::MicroDvd => microdvd::format(subs, MICRODVD_FPS),
+            Format::Mpl2 => mpl2::format(subs),
+            Format::Sami => {
+                let language = sami::Language::new("ENCC", "English", "en-US");
+                sami::format(&[(&language, subs)])
+            }
+            Format::Sbv => sbv::format(subs),
+            Format::Scc => scc::format(subs)
+                .map_err(|err| ParsingError::UnsupportedContent(err.to_string()))?,
+            Format::Stl => return Ok(stl::format(subs, &stl::Header::default())),
+            Format::SubViewer => subviewer::format(subs, &subviewer::Header::default()),
+            Format::TmPlayer => tmplayer::format(subs),
+            Format::Tsv => csv::format(subs, &csv::CsvConfig::tsv()),
+            #[cfg(feature = "ttml")]
+            Format::Ttml => ttml::format(subs, ""),
+        };
+        match encoding {
+            Some(enc) => {
+                let (cow, ..) = Encoding::for_label(enc.as_bytes())
+                    .ok_or(ParsingError::BadEncodingName)?
+                    .encode(&output);
+                Ok(cow.into_owned())
+            }
+            None => Ok(output.into_bytes()),
+        }
+    }
+}
+
+/// Decodes the contents of a text file.
+fn decode(content: &[u8], encoding: Option<&str>) -> Result<String, ParsingError> {
+    match encoding {
+        Some(enc) => {
+            let (cow, ..) = Encoding::for_label(enc.as_bytes())
+                .ok_or(ParsingError::BadEncodingName)?
+                .decode(content);
+            Ok(cow.into_owned())
+        }
+        None => String::from_utf8(content.to_vec())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into()),
+    }
+}
+
+/// Checks if a string is a time such as "0:00:01.000", with the given number of fraction digits.
+fn is_time(s: &str, fraction: usize) -> bool {
+    let Some((time, digits)) = s.trim().rsplit_once('.') else {
+        return false;
+    };
+    let parts: Vec<&str> = time.split(':').chain([digits]).collect();
+    parts.len() == 4
+        && digits.len() == fraction
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Checks if a line starts with two numbers in the given brackets, the second of which may be
+/// empty, such as "{25}{50}".
+fn has_frames(line: &str, open: char, close: char) -> bool {
+    line.strip_prefix(open)
+        .and_then(|rest| rest.split_once(close))
+        .is_some_and(|(number, rest)| {
+            !number.is_empty()
+                && number.bytes().all(|b| b.is_ascii_digit())
+                && rest.starts_with(open)
+        })
+}
+
+/// Returns how confident we are that the contents of a file are in a format, judging by their
+/// beginning, from 0 to 100.
+fn score(format: Format, content: &[u8]) -> u8 {
+    if format == Format::Stl {
+        let signature =
+            content.len() >= 1024 && matches!(&content[3..11], b"STL25.01" | b"STL30.01");
+        return if signature { 100 } else { 0 };
+    }
+    let text = String::from_utf8_lossy(&content[..cmp::min(content.len(), SNIFF_LEN)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or_default();
+    let timing = first.split_once(',');
+    let is_timing = |fraction| {
+        timing.is_some_and(|(start, end)| is_time(start, fraction) && is_time(end, fraction))
+    };
+    match format {
+        Format::Srt if first.contains("-->") => 75,
+        Format::Srt
+            if first.bytes().all(|b| b.is_ascii_digit())
+                && lines.next().is_some_and(|line| line.contains("-->")) =>
+        {
+            100
+        }
+        Format::Csv | Format::Tsv => {
+            let delimiter = csv::detect_delimiter(text);
+            let tabs = delimiter == '\t';
+            if (format == Format::Tsv) == tabs && csv::has_header(text, delimiter) {
+                75
+            } else {
+                0
+            }
+        }
+        Format::Lrc if lrc::starts_with_tag(first) => 75,
+        Format::MicroDvd if has_frames(first, '{', '}') => 75,
+        Format::Mpl2 if has_frames(first, '[', ']') => 75,
+        Format::Sami if text.to_ascii_lowercase().contains("<sami") => 100,
+        Format::Sbv if is_timing(3) => 75,
+        Format::Scc if text.starts_with("Scenarist_SCC") => 100,
+        Format::SubViewer if text.starts_with("[INFORMATION]") => 100,
+        Format::SubViewer if is_timing(2) => 75,
+        Format::TmPlayer if tmplayer::split_line(first).is_some() => 50,
+        #[cfg(feature = "ttml")]
+        Format::Ttml => {
+            let lowercase = text.to_ascii_lowercase();
+            if lowercase.starts_with("<tt")
+                || (lowercase.starts_with("<?xml") && lowercase.contains("<tt"))
+            {
+                100
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    }
+}
+
+impl SubtitleFormat for Format {
+    fn name(&self) -> &str {
+        Format::name(self)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        Format::extensions(self)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Format::capabilities(self)
+    }
+}
+
+impl SubtitleReader for Format {
+    fn detect(&self, content: &[u8]) -> u8 {
+        score(*self, content)
+    }
+
+    fn read(&self, content: &[u8], encoding: Option<&str>) -> Result<Subtitles, ParsingError> {
+        self.parse(content, encoding)
+    }
+}
+
+impl SubtitleWriter for Format {
+    fn write(&self, subs: &Subtitles, encoding: Option<&str>) -> Result<Vec<u8>, ParsingError> {
+        self.format(subs, encoding)
+    }
+}
+
+/// A set of subtitle formats that files can be read or written in, starting with the built-in
+/// [`Format`]s, to which formats defined outside of the crate may be added.
+///
+/// When detecting the format of a file, a reader added later wins over earlier ones that are
+/// as confident, so added formats may take over from the built-in ones. See [`SubtitleReader`]
+/// for an example.
+///
+/// [`Format`]: enum.Format.html
+/// [`SubtitleReader`]: trait.SubtitleReader.html
+pub struct Registry {
+    readers: Vec<Box<dyn SubtitleReader>>,
+    writers: Vec<Box<dyn SubtitleWriter>>,
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    /// Constructs a new Registry holding the built-in formats.
+    pub fn new() -> Registry {
+        let mut registry = Registry::empty();
+        for format in Format::ALL {
+            registry = registry.with_reader(*format).with_writer(*format);
+        }
+        registry
+    }
+
+    /// Constructs a new Registry without any formats.
+    pub fn empty() -> Registry {
+        Registry {
+            readers: Vec::new(),
+            writers: Vec::new(),
+        }
+    }
+
+    /// Returns the registry with a reader added to it.
+    pub fn with_reader(mut self, reader: impl SubtitleReader + 'static) -> Registry {
+        self.readers.push(Box::new(reader));
+        self
+    }
+
+    /// Returns the registry with a writer added to it.
+    pub fn with_writer(mut self, writer: impl SubtitleWriter + 'static) -> Registry {
+        self.writers.push(Box::new(writer));
+        self
+    }
+
+    /// Returns an iterator over the readers of the registry, in the order they were added.
+    pub fn readers(&self) -> impl DoubleEndedIterator<Item = &dyn SubtitleReader> {
+        self.readers.iter().map(|reader| reader.as_ref())
+    }
+
+    /// Returns an iterator over the writers of the registry, in the order they were added.
+    pub fn writers(&self) -> impl DoubleEndedIterator<Item = &dyn SubtitleWriter> {
+        self.writers.iter().map(|writer| writer.as_ref())
+    }
+
+    /// Returns the reader most confident that the contents of a file are in its format, preferring
+    /// readers using the file's extension, if given. If no reader recognizes the contents, the
+    /// only reader using the extension is returned, if any.
+    pub fn detect(&self, content: &[u8], extension: Option<&str>) -> Option<&dyn SubtitleReader> {
+        best_reader(self.readers(), content, extension)
+    }
+
+    /// Returns the writer with the given name or extension, ignoring case, and preferring the
+    /// writers added last.
+    pub fn writer(&self, name: &str) -> Option<&dyn SubtitleWriter> {
+        let extension = name.trim_start_matches('.');
+        self.writers().rev().find(|writer| {
+            writer.name().eq_ignore_ascii_case(name)
+                || writer
+                    .extensions()
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// Constructs a new Subtitles collection by parsing a subtitle file in any format of the
+    /// registry, detected from its contents and extension.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read, an IOError error variant will be returned, while a file in an
+    /// unknown format results in a MalformedFile variant. Otherwise the errors of the reader are
+    /// returned.
+    pub fn load(
+        &self,
+        path: impl AsRef<Path>,
+        encoding: Option<&str>,
+    ) -> Result<Subtitles, ParsingError> {
+        let path = path.as_ref();
+        let content = fs::read(path)?;
+        let extension = path.extension().map(|ext| ext.to_string_lossy());
+        self.detect(&content, extension.as_deref())
+            .ok_or_else(|| ParsingError::MalformedFile("unknown subtitle format".to_string()))?
+            .read(&content, encoding)
+    }
+}
+
+impl Subtitles {
+    /// Constructs a new Subtitles collection by parsing a subtitle file in any supported
+    /// [`Format`], detected from its contents and extension. Files that don't resemble any format
+    /// are read as .srt files.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use srtlib::Subtitles;
+    /// use srtlib::formats::Format;
+    /// # fn main() -> Result<(), srtlib::ParsingError> {
+    /// let subs = Subtitles::load("subtitles.smi", Some("euc-kr"))?;
+    /// subs.save("subtitles.txt", Format::Mpl2, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read, an IOError error variant will be returned. Otherwise see
+    /// [`Format::parse`].
+    ///
+    /// [`Format`]: formats/enum.Format.html
+    /// [`Format::parse`]: formats/enum.Format.html#method.parse
+    pub fn load(path: impl AsRef<Path>, encoding: Option<&str>) -> Result<Subtitles, ParsingError> {
+        let path = path.as_ref();
+        let content = fs::read(path)?;
+        let extension = path.extension().map(|ext| ext.to_string_lossy());
+        Format::detect(&content, extension.as_deref())
+            .unwrap_or(Format::Srt)
+            .parse(&content, encoding)
+    }
+
+    /// Writes the contents of this Subtitles collection to a file in the given [`Format`].
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8.
+    ///
+    /// # Errors
+    ///
+    /// If something goes wrong during the creation of the file using the specified path, an
+    /// IOError error variant will be returned. Otherwise see [`Format::format`].
+    ///
+    /// [`Format`]: formats/enum.Format.html
+    /// [`Format::format`]: formats/enum.Format.html#method.format
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: Format,
+        encoding: Option<&str>,
+    ) -> Result<(), ParsingError> {
+        let output = format.format(self, encoding)?;
+        fs::write(path, output)?;
+        Ok(())
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn detection() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i>\nworld\n\n\
+                   2\n00:00:03,000 --> 00:00:04,000\nBye";
+        let subs = Subtitles::parse_from_str(srt.to_string()).unwrap();
+        assert_eq!(
+            Format::detect(srt.as_bytes(), Some("txt")),
+            Some(Format::Srt)
+        );
+
+        for format in Format::ALL {
+            let output = format.format(&subs, None).unwrap();
+            assert_eq!(
+                Format::detect(&output, None),
+                Some(*format),
+                "{}",
+                format.name()
+            );
+            let parsed = format.parse(&output, None).unwrap();
+            assert_eq!(parsed.len(), 2, "{}", format.name());
+        }
+
+        assert_eq!(Format::detect(b"", Some(".SRT")), Some(Format::Srt));
+        assert_eq!(Format::detect(b"", Some("sub")), None);
+        assert_eq!(Format::from_path("movie.en.smi"), Some(Format::Sami));
+        assert!(matches!(
+            Format::Sbv.parse(b"\xff", None),
+            Err(ParsingError::IOError(_))
+        ));
+        assert!(matches!(
+            Format::Srt.format(&subs, Some("nonsense")),
+            Err(ParsingError::BadEncodingName)
+        ));
+    }
+
+    #[test]
+    fn registry() {
+        /// A reader of .srt files recognizing them with more confidence than the built-in one.
+        struct Eager;
+
+        impl SubtitleFormat for Eager {
+            fn name(&self) -> &str {
+                "Eager"
+            }
+
+            fn extensions(&self) -> &[&str] {
+                &["eager", "srt"]
+            }
+
+            fn capabilities(&self) -> Capabilities {
+                Capabilities::default()
+            }
+        }
+
+        impl SubtitleReader for Eager {
+            fn detect(&self, content: &[u8]) -> u8 {
+                if content.is_empty() {
+                    0
+                } else {
+                    100
+                }
+            }
+
+            fn read(&self, _: &[u8], _: Option<&str>) -> Result<Subtitles, ParsingError> {
+                Ok(Subtitles::new())
+            }
+        }
+
+        let srt = b"1\n00:00:01,000 --> 00:00:02,000\nHello";
+        let registry = Registry::new();
+        assert_eq!(registry.readers().count(), Format::ALL.len());
+        assert_eq!(registry.detect(srt, None).unwrap().name(), "SubRip");
+        assert_eq!(registry.writer("SAMI").unwrap().extensions()[0], "smi");
+        assert_eq!(registry.writer(".sub").unwrap().name(), "SubViewer 2.0");
+        assert!(registry.writer("eager").is_none());
+        assert!(Format::Stl.capabilities().binary);
+        assert!(!Format::Sbv.capabilities().styling);
+
+        let registry = registry.with_reader(Eager);
+        assert_eq!(registry.detect(srt, None).unwrap().name(), "Eager");
+        assert_eq!(registry.detect(b"", Some("eager")).unwrap().name(), "Eager");
+        // used by both readers
+        assert!(registry.detect(b"", Some("srt")).is_none());
+        assert!(Registry::empty().detect(srt, Some("srt")).is_none());
+    }
+
+    #[test]
+    fn malformed_files() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n{\\an8}<i>Hello</i>\nworld\n\n\
+                   2\n00:00:03,000 --> 00:00:04,000\n<u>Bye</u> é";
+        let subs = Subtitles::parse_from_str(srt.to_string()).unwrap();
+        let garbage: [&[u8]; 5] = [
+            b"999999999999999999999",
+            b"4000000000",
+            "€".as_bytes(),
+            b"-",
+            b"<",
+        ];
+        let dir = std::env::temp_dir().join(format!("srtlib-malformed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for format in Format::ALL {
+            let output = format.format(&subs, None).unwrap();
+            let path = dir.join(format!("garbage.{}", format.extensions()[0]));
+            for i in 0..=output.len() {
+                for inserted in garbage {
+                    let mut content = output[..i].to_vec();
+                    content.extend_from_slice(inserted);
+                    content.extend_from_slice(&output[i..]);
+                    fs::write(&path, &content).unwrap();
+                    // any result will do, as long as nothing panics
+                    let _ = Subtitles::load(&path, None);
+                }
+            }
+        }
+
+        let path = dir.join("garbage.sub");
+        fs::write(
+            &path,
+            "[INFORMATION]\n[END INFORMATION]\n00:00:00.ab€,00:00:01.00\nx",
+        )
+        .unwrap();
+        assert!(Subtitles::load(&path, None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,527 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SAMI (.smi) files.
+//!
+//! A SAMI file is an HTML-like document whose body is a list of `<SYNC Start=ms>` blocks, each
+//! holding a `<P Class=...>` paragraph for every language that changes at that time. A language
+//! class is declared in the style sheet of the header, and described by a [`Language`]. The text
+//! of a language stays on screen until its next SYNC, which is usually an empty `&nbsp;` one.
+//!
+//! `<i>`, `<b>`, `<u>` and `<font>` tags are kept, `<br>` tags become line breaks and HTML
+//! entities are decoded, while other tags are removed.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::sami::{self, Language};
+//!
+//! let input = "<SAMI><BODY>\n\
+//!              <SYNC Start=1000><P Class=KRCC>안녕하세요\n\
+//!              <P Class=ENCC>Hello &amp; welcome\n\
+//!              <SYNC Start=2500><P Class=KRCC>&nbsp;<P Class=ENCC>&nbsp;\n\
+//!              </BODY></SAMI>";
+//! let subs = sami::parse(input, Some("ENCC")).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:02,500\nHello & welcome");
+//!
+//! let english = Language::new("ENCC", "English", "en-US");
+//! let output = sami::format(&[(&english, &subs)]);
+//! assert!(output.contains("<SYNC Start=1000>\n<P Class=ENCC>Hello &amp; welcome\n"));
+//! ```
+//!
+//! [`Language`]: struct.Language.html
+
+use crate::{unformatted_spans, ParsingError, Subtitle, Subtitles, Timestamp};
+
+/// Tags that are kept in the text of subtitles.
+const KEPT_TAGS: [&str; 4] = ["i", "b", "u", "font"];
+
+/// A language class of a SAMI file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Language {
+    /// The class used by the paragraphs of the language, e.g. "ENCC".
+    pub class: String,
+    /// The name of the language, e.g. "English".
+    pub name: String,
+    /// The language tag, e.g. "en-US".
+    pub lang: String,
+}
+
+impl Language {
+    /// Constructs a new Language.
+    pub fn new(class: &str, name: &str, lang: &str) -> Language {
+        Language {
+            class: class.to_string(),
+            name: name.to_string(),
+            lang: lang.to_string(),
+        }
+    }
+}
+
+/// Returns the value of an attribute of a tag, given its contents without the angle brackets.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    // skip the tag name
+    let mut rest = tag.trim_start().split_once(char::is_whitespace)?.1;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, remaining) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        value[1..].split_once(quote).unwrap_or((&value[1..], ""))
+                    }
+                    _ => value.split_at(value.find(char::is_whitespace).unwrap_or(value.len())),
+                };
+                rest = remaining;
+                value
+            }
+            None => "",
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+/// Decodes an HTML entity, given its name without the ampersand and semicolon.
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "nbsp" => Some('\u{a0}'),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Decodes the HTML entities of a text, leaving unknown ones as they are.
+fn decode_entities(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        res.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match decoded {
+            Some((c, end)) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Converts the HTML of a paragraph into the text of a subtitle.
+fn paragraph_text(html: &str) -> String {
+    // line breaks in HTML are only given by tags
+    let html = html.replace(['\r', '\n'], " ");
+    let mut res = String::new();
+    let mut rest = html.as_str();
+    while !rest.is_empty() {
+        let Some(tag_start) = rest.find('<') else {
+            res.push_str(&decode_entities(rest));
+            break;
+        };
+        res.push_str(&decode_entities(&rest[..tag_start]));
+        rest = &rest[tag_start..];
+        let Some(tag_end) = rest.find('>') else {
+            res.push_str(&decode_entities(rest));
+            break;
+        };
+        let tag = &rest[1..tag_end];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if name == "br" {
+            res.push('\n');
+        } else if KEPT_TAGS.contains(&name.as_str()) {
+            let closing = if tag.starts_with('/') { "/" } else { "" };
+            let attributes = &tag.trim_start_matches('/')[name.len()..];
+            res.push_str(&format!("<{}{}{}>", closing, name, attributes));
+        }
+        rest = &rest[tag_end + 1..];
+    }
+
+    // whitespace in HTML is collapsed
+    let lines: Vec<String> = res
+        .split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Finds the next opening tag with the given lowercase name, returning its byte range.
+fn find_tag(lower: &str, from: usize, name: &str) -> Option<std::ops::Range<usize>> {
+    let pattern = format!("<{}", name);
+    let mut pos = from;
+    while let Some(found) = lower[pos..].find(&pattern) {
+        let start = pos + found;
+        let after = start + pattern.len();
+        if lower[after..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            let end = lower[after..]
+                .find('>')
+                .map_or(lower.len(), |end| after + end + 1);
+            return Some(start..end);
+        }
+        pos = after;
+    }
+    None
+}
+
+/// Returns the name and attributes of a tag found by [`find_tag`], without its brackets. A tag
+/// without a closing bracket lasts until the end of the input.
+fn tag_contents<'a>(input: &'a str, tag: &std::ops::Range<usize>) -> &'a str {
+    let contents = &input[tag.start + 1..tag.end];
+    contents.strip_suffix('>').unwrap_or(contents)
+}
+
+/// A SYNC block: its start time and its paragraphs, by class.
+type Sync = (u32, Vec<(Option<String>, String)>);
+
+/// Splits the body of a SAMI file into SYNC blocks.
+fn syncs(input: &str) -> Result<Vec<Sync>, ParsingError> {
+    let lower = input.to_ascii_lowercase();
+    let body_end = lower.find("</body").unwrap_or(lower.len());
+    let mut res = Vec::new();
+    let mut next = find_tag(&lower, 0, "sync");
+    while let Some(tag) = next.filter(|tag| tag.start < body_end) {
+        next = find_tag(&lower, tag.end, "sync");
+        let end = next
+            .as_ref()
+            .map_or(body_end, |next| next.start.min(body_end));
+        let start = attribute(tag_contents(input, &tag), "start")
+            .and_then(|start| start.trim().parse::<u32>().ok())
+            .map(|start| start.min(Timestamp::MAX_TIMESTAMP_MILLIS))
+            .ok_or_else(|| ParsingError::MalformedFile("SYNC without a start time".to_string()))?;
+
+        let mut paragraphs = Vec::new();
+        // an unterminated tag may reach past the end of the block
+        let mut pos = tag.end.min(end);
+        let mut class = None;
+        loop {
+            let paragraph = find_tag(&lower, pos, "p").filter(|p| p.start < end);
+            let text_end = paragraph.as_ref().map_or(end, |p| p.start);
+            let html = &input[pos..text_end];
+            // text before the first paragraph is only kept if there's some
+            if class.is_some() || !paragraph_text(html).is_empty() {
+                let html = match lower[pos..text_end].find("</p") {
+                    Some(close) => &html[..close],
+                    None => html,
+                };
+                paragraphs.push((class.flatten(), paragraph_text(html)));
+            }
+            let Some(paragraph) = paragraph else {
+                break;
+            };
+            class = Some(attribute(tag_contents(input, &paragraph), "class").map(str::to_string));
+            pos = paragraph.end.min(end);
+        }
+        res.push((start, paragraphs));
+    }
+    Ok(res)
+}
+
+/// Returns the language classes declared in the style sheet of a SAMI file.
+pub fn languages(input: &str) -> Vec<Language> {
+    let lower = input.to_ascii_lowercase();
+    let Some(style) = find_tag(&lower, 0, "style") else {
+        return Vec::new();
+    };
+    let end = lower[style.end..]
+        .find("</style")
+        .map_or(lower.len(), |end| style.end + end);
+    let mut res = Vec::new();
+    for rule in input[style.end..end].split('}') {
+        let Some((selector, declarations)) = rule.split_once('{') else {
+            continue;
+        };
+        let Some(class) = selector.trim().rsplit(char::is_whitespace).next() else {
+            continue;
+        };
+        let Some(class) = class.strip_prefix('.') else {
+            continue;
+        };
+        let mut language = Language::new(class, "", "");
+        for declaration in declarations.split(';') {
+            if let Some((key, value)) = declaration.split_once(':') {
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "name" => language.name = value.trim().to_string(),
+                    "lang" => language.lang = value.trim().to_string(),
+                    _ => (),
+                }
+            }
+        }
+        res.push(language);
+    }
+    res
+}
+
+/// Constructs a new Subtitles collection from the paragraphs of a language class of a SAMI file.
+/// Subtitles are numbered starting from 1.
+///
+/// If **class** is None, the class of the first paragraph is used. Classes are compared
+/// case-insensitively. A subtitle ends at the next SYNC of its class, or if there's none, at the
+/// next SYNC of the file.
+///
+/// # Errors
+///
+/// If a SYNC tag lacks a valid start time, a MalformedFile error variant will be returned.
+pub fn parse(input: &str, class: Option<&str>) -> Result<Subtitles, ParsingError> {
+    let syncs = syncs(input)?;
+    let class = match class {
+        Some(class) => Some(class.to_string()),
+        None => syncs
+            .iter()
+            .flat_map(|(_, paragraphs)| paragraphs)
+            .next()
+            .and_then(|(class, _)| class.clone()),
+    };
+    let same_class = |other: &Option<String>| match (&class, other) {
+        (Some(class), Some(other)) => class.eq_ignore_ascii_case(other),
+        (None, None) => true,
+        _ => false,
+    };
+
+    let mut events: Vec<(u32, String)> = syncs
+        .iter()
+        .flat_map(|(start, paragraphs)| {
+            paragraphs
+                .iter()
+                .filter(|(class, _)| same_class(class))
+                .map(move |(_, text)| (*start, text.clone()))
+        })
+        .collect();
+    events.sort_by_key(|(start, _)| *start);
+    let mut times: Vec<u32> = syncs.iter().map(|(start, _)| *start).collect();
+    times.sort_unstable();
+
+    let mut res = Subtitles::new();
+    for (i, (start, text)) in events.iter().enumerate() {
+        if text.is_empty() {
+            continue;
+        }
+        let end = match events.get(i + 1) {
+            Some((end, _)) => *end,
+            None => times
+                .iter()
+                .copied()
+                .find(|time| time > start)
+                .unwrap_or(*start),
+        };
+        res.push(Subtitle::new(
+            res.len() + 1,
+            Timestamp::from_milliseconds(*start),
+            Timestamp::from_milliseconds(end),
+            text.clone(),
+        ));
+    }
+    Ok(res)
+}
+
+/// Appends the tags between two runs of text that SAMI supports, dropping the rest.
+fn push_tags(res: &mut String, mut tags: &str) {
+    while let Some(c) = tags.chars().next() {
+        let close = if c == '{' { '}' } else { '>' };
+        let end = tags.find(close).map_or(tags.len(), |end| end + 1);
+        let tag = &tags[..end];
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if c == '<' && KEPT_TAGS.contains(&name.as_str()) {
+            res.push_str(tag);
+        }
+        tags = &tags[end..];
+    }
+}
+
+/// Converts the text of a subtitle into the HTML of a paragraph.
+fn paragraph_html(text: &str) -> String {
+    let mut res = String::new();
+    let mut pos = 0;
+    for span in unformatted_spans(text) {
+        push_tags(&mut res, &text[pos..span.start]);
+        let plain = text[span.clone()]
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('\n', "<br>");
+        res.push_str(&plain);
+        pos = span.end;
+    }
+    push_tags(&mut res, &text[pos..]);
+    if res.trim().is_empty() {
+        res = "&nbsp;".to_string();
+    }
+    res
+}
+
+/// Returns the contents of a SAMI file holding a track of subtitles for every language.
+///
+/// Every subtitle is followed by an empty `&nbsp;` SYNC, unless the next subtitle of its language
+/// starts when it ends. Paragraphs of different languages starting at the same time share a
+/// SYNC.
+pub fn format(tracks: &[(&Language, &Subtitles)]) -> String {
+    let mut res = String::from("<SAMI>\n<HEAD>\n<STYLE TYPE=\"text/css\">\n<!--\n");
+    res.push_str("P { margin-left: 8pt; margin-right: 8pt; margin-bottom: 2pt; margin-top: 2pt; text-align: center; font-size: 20pt; font-family: Arial; font-weight: normal; color: white; }\n");
+    for (language, _) in tracks {
+        res.push_str(&format!(
+            ".{} {{ Name: {}; lang: {}; SAMIType: CC; }}\n",
+            language.class, language.name, language.lang
+        ));
+    }
+    res.push_str("-->\n</STYLE>\n</HEAD>\n<BODY>\n");
+
+    // paragraphs by start time, keeping the order of the tracks
+    let mut paragraphs: Vec<(u32, usize, String)> = Vec::new();
+    for (track, (_, subs)) in tracks.iter().enumerate() {
+        let mut subs: Vec<&Subtitle> = subs.into_iter().collect();
+        subs.sort_by_key(|sub| sub.start_time);
+        for (i, sub) in subs.iter().enumerate() {
+            paragraphs.push((
+                sub.start_time.to_milliseconds(),
+                track,
+                paragraph_html(&sub.text),
+            ));
+            let end = sub.end_time.to_milliseconds();
+            if subs
+                .get(i + 1)
+                .is_none_or(|next| next.start_time.to_milliseconds() != end)
+            {
+                paragraphs.push((end, track, "&nbsp;".to_string()));
+            }
+        }
+    }
+    paragraphs.sort_by_key(|(start, track, _)| (*start, *track));
+
+    let mut last = None;
+    for (start, track, html) in paragraphs {
+        if last != Some(start) {
+            res.push_str(&format!("<SYNC Start={}>\n", start));
+            last = Some(start);
+        }
+        res.push_str(&format!("<P Class={}>{}\n", tracks[track].0.class, html));
+    }
+    res.push_str("</BODY>\n</SAMI>\n");
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn parse_languages() {
+        let input = "<SAMI>\n<HEAD>\n<STYLE TYPE=\"text/css\">\n<!--\n\
+                     P { font-family: Arial; }\n\
+                     .KRCC { Name: Korean; lang: ko-KR; SAMIType: CC; }\n\
+                     .ENCC { Name: English; lang: en-US; SAMIType: CC; }\n\
+                     -->\n</STYLE>\n</HEAD>\n<BODY>\n\
+                     <sync start=\"500\"><p class='krcc'>첫 번째<BR>줄</p>\n\
+                     <SYNC Start=500><P Class=ENCC><FONT color=\"#ffff00\">First</FONT>\n  &lt;line&gt;&#33;\n\
+                     <SYNC Start=1500><P Class=ENCC><i>Second</i>\n\
+                     <SYNC Start=2000><P Class=KRCC>&nbsp;\n\
+                     <SYNC Start=3000><P Class=ENCC>&nbsp;\n\
+                     <SYNC Start=4000><P Class=KRCC>마지막\n\
+                     </BODY>\n</SAMI>";
+        assert_eq!(
+            languages(input),
+            vec![
+                Language::new("KRCC", "Korean", "ko-KR"),
+                Language::new("ENCC", "English", "en-US")
+            ]
+        );
+
+        let korean = parse(input, None).unwrap();
+        assert_eq!(korean.len(), 2);
+        assert_eq!(korean[0].text, "첫 번째\n줄");
+        assert_eq!(korean[0].end_time, Timestamp::from_milliseconds(2000));
+        // the last subtitle has no SYNC after it
+        assert_eq!(korean[1].end_time, korean[1].start_time);
+
+        let english = parse(input, Some("encc")).unwrap();
+        assert_eq!(english.len(), 2);
+        assert_eq!(
+            english[0].text,
+            "<font color=\"#ffff00\">First</font> <line>!"
+        );
+        assert_eq!(english[0].end_time, Timestamp::from_milliseconds(1500));
+        assert_eq!(english[1].text, "<i>Second</i>");
+        assert_eq!(english[1].end_time, Timestamp::from_milliseconds(3000));
+
+        assert!(parse("<sync start=1é", None).is_err());
+        assert!(parse("<SYNC Start=1 </BODY", None).is_ok());
+        assert!(parse("<SYNC Start=1><P Class=A-x\n<SYNC Start=2><P>y", None).is_ok());
+        let late = parse("<SYNC Start=4000000000><P>Late", None).unwrap();
+        assert_eq!(
+            late[0].start_time.to_milliseconds(),
+            Timestamp::MAX_TIMESTAMP_MILLIS
+        );
+    }
+
+    #[test]
+    fn format_languages() {
+        let korean = Language::new("KRCC", "Korean", "ko-KR");
+        let english = Language::new("ENCC", "English", "en-US");
+        let ko = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n안녕\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\n하세요"
+                .to_string(),
+        )
+        .unwrap();
+        let en = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:03,000\n{\\an8}<i>Hi</i> & bye\nthere".to_string(),
+        )
+        .unwrap();
+
+        let output = format(&[(&korean, &ko), (&english, &en)]);
+        assert!(output.contains(".ENCC { Name: English; lang: en-US; SAMIType: CC; }"));
+        assert!(output.contains(
+            "<SYNC Start=1000>\n<P Class=KRCC>안녕\n<P Class=ENCC><i>Hi</i> &amp; bye<br>there\n\
+             <SYNC Start=2000>\n<P Class=KRCC>하세요\n\
+             <SYNC Start=3000>\n<P Class=KRCC>&nbsp;\n<P Class=ENCC>&nbsp;\n</BODY>"
+        ));
+        assert_eq!(parse(&output, Some("KRCC")).unwrap(), ko);
+        assert_eq!(
+            parse(&output, Some("ENCC")).unwrap()[0].text,
+            "<i>Hi</i> & bye\nthere"
+        );
+    }
+}
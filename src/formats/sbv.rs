@@ -0,0 +1,108 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! YouTube SBV files.
+//!
+//! An SBV file is a list of cues separated by blank lines, each starting with a
+//! `0:00:01.000,0:00:03.000` line holding its start and end time. Since SBV has no formatting,
+//! tags are removed when writing.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::sbv;
+//!
+//! let subs = sbv::parse("0:00:01.000,0:00:03.500\nHello\nworld\n\n0:00:04.000,0:00:05.000\nBye").unwrap();
+//! assert_eq!(subs[0].to_string(), "1\n00:00:01,000 --> 00:00:03,500\nHello\nworld");
+//!
+//! assert_eq!(sbv::format(&subs), "0:00:01.000,0:00:03.500\nHello\nworld\n\n0:00:04.000,0:00:05.000\nBye\n");
+//! ```
+
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+
+/// Writes a timestamp in the format "h:mm:ss.mmm".
+fn timestamp(time: &Timestamp) -> String {
+    let (hours, minutes, seconds, milliseconds) = time.get();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        hours, minutes, seconds, milliseconds
+    )
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an SBV file. Subtitles are
+/// numbered starting from 1.
+///
+/// # Errors
+///
+/// If a cue lacks a timing line, a BadSubtitleStructure error variant will be returned, while
+/// malformed timestamps result in a MalformedTimestamp or ParseIntError variant.
+pub fn parse(input: &str) -> Result<Subtitles, ParsingError> {
+    let input = input.trim_start_matches('\u{feff}').replace('\r', "");
+    let mut res = Subtitles::new();
+    let mut lines = input.lines().map(str::trim_end).peekable();
+    while lines.peek().is_some() {
+        let cue: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+        let Some((timing, text)) = cue.split_first() else {
+            continue;
+        };
+        let num = res.len() + 1;
+        let (start, end) = timing
+            .split_once(',')
+            .ok_or(ParsingError::BadSubtitleStructure(num))?;
+        res.push(Subtitle::new(
+            num,
+            Timestamp::parse(start.trim())?,
+            Timestamp::parse(end.trim())?,
+            text.join("\n"),
+        ));
+    }
+    Ok(res)
+}
+
+/// Returns the contents of an SBV file holding the subtitles, without any formatting tags.
+pub fn format(subs: &Subtitles) -> String {
+    let cues: Vec<String> = subs
+        .into_iter()
+        .map(|sub| {
+            format!(
+                "{},{}\n{}\n",
+                timestamp(&sub.start_time),
+                timestamp(&sub.end_time),
+                sub.plain_text()
+            )
+        })
+        .collect();
+    cues.join("\n")
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let subs = parse(
+            "\u{feff}0:00:01.000,0:00:02.000\r\n>> Hi\r\n\r\n\r\n12:34:56.789,12:34:57.000\r\nThere\r\n",
+        )
+        .unwrap();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, ">> Hi");
+        assert_eq!(subs[1].num, 2);
+        assert_eq!(subs[1].start_time, Timestamp::new(12, 34, 56, 789));
+
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n{\\an8}<i>Hello</i>".to_string(),
+        )
+        .unwrap();
+        assert_eq!(format(&subs), "0:00:01.000,0:00:02.000\nHello\n");
+        assert!(matches!(
+            parse("0:00:01.000\nNo end"),
+            Err(ParsingError::BadSubtitleStructure(1))
+        ));
+    }
+}
@@ -0,0 +1,744 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Scenarist SCC files, carrying CEA-608 closed captions.
+//!
+//! Every line of an SCC file holds a SMPTE timecode followed by CEA-608 byte pairs, which are
+//! sent one per frame at 29.97 frames per second. Drop-frame (`HH:MM:SS;FF`) and non-drop-frame
+//! (`HH:MM:SS:FF`) timecodes are read, while drop-frame timecodes are written, since they follow
+//! the actual time.
+//!
+//! Only pop-on captions of the first caption channel are supported: the text of a caption is
+//! loaded in the background and shown all at once. Captions are written centred, on the bottom
+//! rows of the screen or on the top ones for subtitles with an `{\an8}` tag, and rows in the
+//! upper half of the screen are read back as such. Italics and underline are kept; other
+//! formatting is dropped. Characters that CEA-608 can't display are replaced with their unaccented
+//! versions where possible, or with "?".
+//!
+//! Since a caption has to be fully loaded before it is shown, and the loading takes a frame for
+//! every two characters, writing fails if there's no time left to load a subtitle. Lines longer
+//! than the 32 columns of the screen are wrapped, and writing fails if a subtitle then needs more
+//! than 4 rows.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::formats::scc;
+//!
+//! let subs = Subtitles::parse_from_str("1\n00:00:01,001 --> 00:00:02,002\n<i>Hello</i> world".to_string()).unwrap();
+//!
+//! let scc = scc::format(&subs).unwrap();
+//! assert!(scc.starts_with("Scenarist_SCC V1.0\n\n00:00:00;12\t94ae 94ae 9420 9420"));
+//! assert_eq!(scc::parse(&scc).unwrap(), subs);
+//! ```
+
+use super::{is_top_aligned, styled_lines, to_markup, trim_lines, Style, StyledLine};
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use unicode_normalization::UnicodeNormalization;
+
+const HEADER: &str = "Scenarist_SCC V1.0";
+
+/// The number of columns of the screen.
+pub const COLUMNS: usize = 32;
+/// The maximum number of rows of a caption.
+pub const MAX_ROWS: usize = 4;
+
+const RESUME_CAPTION_LOADING: [u8; 2] = [0x14, 0x20];
+const BACKSPACE: [u8; 2] = [0x14, 0x21];
+const DELETE_TO_END_OF_ROW: [u8; 2] = [0x14, 0x24];
+const ERASE_DISPLAYED_MEMORY: [u8; 2] = [0x14, 0x2C];
+const ERASE_NON_DISPLAYED_MEMORY: [u8; 2] = [0x14, 0x2E];
+const END_OF_CAPTION: [u8; 2] = [0x14, 0x2F];
+
+/// The first preamble address code byte and the base of the second one for the rows 1 to 15.
+const ROWS: [(u8, u8); 15] = [
+    (0x11, 0x40),
+    (0x11, 0x60),
+    (0x12, 0x40),
+    (0x12, 0x60),
+    (0x15, 0x40),
+    (0x15, 0x60),
+    (0x16, 0x40),
+    (0x16, 0x60),
+    (0x17, 0x40),
+    (0x17, 0x60),
+    (0x10, 0x40),
+    (0x13, 0x40),
+    (0x13, 0x60),
+    (0x14, 0x40),
+    (0x14, 0x60),
+];
+
+/// The characters of the basic set that differ from ASCII.
+const BASIC_EXCEPTIONS: [(u8, char); 10] = [
+    (0x2A, 'á'),
+    (0x5C, 'é'),
+    (0x5E, 'í'),
+    (0x5F, 'ó'),
+    (0x60, 'ú'),
+    (0x7B, 'ç'),
+    (0x7C, '÷'),
+    (0x7D, 'Ñ'),
+    (0x7E, 'ñ'),
+    (0x7F, '█'),
+];
+
+/// The special characters, sent as 0x11 followed by 0x30 to 0x3F.
+const SPECIAL: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', '\u{a0}', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+/// The extended characters, sent as 0x12 or 0x13 followed by 0x20 to 0x3F. They replace the
+/// character sent before them, which decoders without them display instead.
+const EXTENDED: [[char; 32]; 2] = [
+    [
+        'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '’', '—', '©', '℠', '•', '“', '”', //
+        'À', 'Â', 'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+    ],
+    [
+        'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', //
+        'Ä', 'ä', 'Ö', 'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+    ],
+];
+
+/// The errors that can occur when writing an SCC file. Each variant holds the number of the
+/// subtitle that caused it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SccError {
+    /// A line doesn't fit in the 32 columns of the screen, even after wrapping.
+    LineTooLong(usize),
+    /// The subtitle needs more than 4 rows.
+    TooManyRows(usize),
+    /// The subtitle can't be loaded in time, because it starts too soon after the previous one
+    /// or at the very start.
+    NotEnoughBandwidth(usize),
+}
+
+impl fmt::Display for SccError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SccError::LineTooLong(num) => write!(
+                f,
+                "subtitle {} has a line longer than {} columns",
+                num, COLUMNS
+            ),
+            SccError::TooManyRows(num) => {
+                write!(f, "subtitle {} needs more than {} rows", num, MAX_ROWS)
+            }
+            SccError::NotEnoughBandwidth(num) => {
+                write!(f, "subtitle {} can't be loaded before it starts", num)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SccError {}
+
+/// Converts a number of milliseconds to a number of frames at 29.97 frames per second.
+fn to_frames(millis: u32) -> i64 {
+    (millis as i64 * 30 + 500) / 1001
+}
+
+/// Converts a number of frames at 29.97 frames per second to a timestamp.
+fn to_timestamp(frames: i64) -> Timestamp {
+    let millis = (frames * 1001 + 15) / 30;
+    Timestamp::from_milliseconds(millis.clamp(0, Timestamp::MAX_TIMESTAMP_MILLIS as i64) as u32)
+}
+
+/// Writes a number of frames as a drop-frame timecode, which skips the frame numbers 0 and 1 of
+/// every minute except every tenth one.
+fn drop_frame_timecode(frames: i64) -> String {
+    let (tens, rest) = (frames / 17982, frames % 17982);
+    let mut frames = frames + 18 * tens;
+    if rest >= 2 {
+        frames += 2 * ((rest - 2) / 1798);
+    }
+    format!(
+        "{:02}:{:02}:{:02};{:02}",
+        frames / 108000,
+        frames / 1800 % 60,
+        frames / 30 % 60,
+        frames % 30
+    )
+}
+
+/// Parses a drop-frame or non-drop-frame timecode into a number of frames.
+fn parse_timecode(timecode: &str) -> Option<i64> {
+    let drop_frame = timecode.contains([';', '.', ',']);
+    let parts = timecode
+        .split([':', ';', '.', ','])
+        .map(|part| part.parse::<u8>().ok().map(i64::from))
+        .collect::<Option<Vec<i64>>>()?;
+    let [h, m, s, f] = parts[..] else {
+        return None;
+    };
+    let mut frames = ((h * 60 + m) * 60 + s) * 30 + f;
+    if drop_frame {
+        let minutes = h * 60 + m;
+        frames -= 2 * (minutes - minutes / 10);
+    }
+    Some(frames)
+}
+
+/// Sets the parity bit of a byte, so that it has an odd number of bits set.
+fn with_parity(byte: u8) -> u8 {
+    if byte.count_ones().is_multiple_of(2) {
+        byte | 0x80
+    } else {
+        byte
+    }
+}
+
+/// Returns the byte of the basic character set for a character.
+fn basic_char(c: char) -> Option<u8> {
+    if let Some((byte, _)) = BASIC_EXCEPTIONS.iter().find(|(_, e)| *e == c) {
+        return Some(*byte);
+    }
+    match c {
+        ' '..='~' if BASIC_EXCEPTIONS.iter().all(|(byte, _)| *byte != c as u8) => Some(c as u8),
+        _ => None,
+    }
+}
+
+/// Decodes a byte of the basic character set.
+fn decode_basic(byte: u8) -> char {
+    BASIC_EXCEPTIONS
+        .iter()
+        .find(|(b, _)| *b == byte)
+        .map_or(byte as char, |(_, c)| *c)
+}
+
+/// A unit of caption text, taking up a single column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Basic(u8),
+    Special(u8),
+    /// An extended character, with the basic character displayed by decoders that lack it.
+    Extended([u8; 2], u8),
+    /// A mid-row code changing the style, displayed as a space.
+    MidRow(Style),
+}
+
+impl Cell {
+    fn from_char(c: char) -> Cell {
+        if let Some(byte) = basic_char(c) {
+            return Cell::Basic(byte);
+        }
+        if let Some(pos) = SPECIAL.iter().position(|&s| s == c) {
+            return Cell::Special(0x30 + pos as u8);
+        }
+        let base = c.to_string().nfd().next().and_then(basic_char);
+        for (table, chars) in EXTENDED.iter().enumerate() {
+            if let Some(pos) = chars.iter().position(|&e| e == c) {
+                let code = [0x12 + table as u8, 0x20 + pos as u8];
+                return Cell::Extended(code, base.unwrap_or(b' '));
+            }
+        }
+        Cell::Basic(base.unwrap_or(b'?'))
+    }
+}
+
+/// Splits a line into lines of at most 32 columns, at spaces where possible.
+fn wrap(line: Vec<(Style, char)>) -> Vec<Vec<(Style, char)>> {
+    let mut res: Vec<Vec<(Style, char)>> = Vec::new();
+    for word in line
+        .split(|&(_, c)| c == ' ')
+        .filter(|word| !word.is_empty())
+    {
+        match res.last_mut() {
+            Some(current) if current.len() + 1 + word.len() <= COLUMNS => {
+                current.push((current[current.len() - 1].0, ' '));
+                current.extend_from_slice(word);
+            }
+            _ => res.extend(word.chunks(COLUMNS).map(|chunk| chunk.to_vec())),
+        }
+    }
+    res
+}
+
+/// Builds the byte pairs of a caption, sending every control code twice.
+#[derive(Default)]
+struct Encoder {
+    pairs: Vec<[u8; 2]>,
+    pending: Option<u8>,
+}
+
+impl Encoder {
+    fn flush(&mut self) {
+        if let Some(byte) = self.pending.take() {
+            self.pairs.push([byte, 0]);
+        }
+    }
+
+    fn control(&mut self, code: [u8; 2]) {
+        self.flush();
+        self.pairs.push(code);
+        self.pairs.push(code);
+    }
+
+    fn char(&mut self, byte: u8) {
+        match self.pending.take() {
+            Some(first) => self.pairs.push([first, byte]),
+            None => self.pending = Some(byte),
+        }
+    }
+
+    fn cell(&mut self, cell: Cell) {
+        match cell {
+            Cell::Basic(byte) => self.char(byte),
+            Cell::Special(byte) => self.control([0x11, byte]),
+            Cell::Extended(code, fallback) => {
+                self.char(fallback);
+                self.control(code);
+            }
+            Cell::MidRow(style) => self.control([
+                0x11,
+                (if style.italic { 0x2E } else { 0x20 }) | style.underline as u8,
+            ]),
+        }
+    }
+
+    fn finish(mut self) -> Vec<[u8; 2]> {
+        self.flush();
+        self.pairs
+            .into_iter()
+            .map(|[a, b]| [with_parity(a), with_parity(b)])
+            .collect()
+    }
+}
+
+/// Returns the byte pairs loading a subtitle into the non-displayed memory.
+fn load(sub: &Subtitle) -> Result<Vec<[u8; 2]>, SccError> {
+    let lines: Vec<Vec<(Style, char)>> = styled_lines(&sub.text)
+        .into_iter()
+        .flat_map(|line| {
+            wrap(
+                line.into_iter()
+                    .flat_map(|(style, run)| {
+                        run.chars().map(move |c| (style, c)).collect::<Vec<_>>()
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    if lines.len() > MAX_ROWS {
+        return Err(SccError::TooManyRows(sub.num));
+    }
+    let first_row = if is_top_aligned(&sub.text) {
+        0
+    } else {
+        ROWS.len() - lines.len()
+    };
+
+    let mut encoder = Encoder::default();
+    encoder.control(ERASE_NON_DISPLAYED_MEMORY);
+    encoder.control(RESUME_CAPTION_LOADING);
+    for (row, line) in lines.into_iter().enumerate() {
+        let mut cells: Vec<Cell> = Vec::new();
+        let mut style = Style::default();
+        for (run_style, c) in line {
+            let run_style = Style {
+                bold: false,
+                ..run_style
+            };
+            if run_style != style {
+                style = run_style;
+                // the mid-row code takes the place of a space next to it
+                if cells.last() == Some(&Cell::Basic(b' ')) {
+                    cells.pop();
+                    cells.push(Cell::MidRow(style));
+                    cells.push(Cell::from_char(c));
+                } else {
+                    cells.push(Cell::MidRow(style));
+                    if c != ' ' {
+                        cells.push(Cell::from_char(c));
+                    }
+                }
+                continue;
+            }
+            cells.push(Cell::from_char(c));
+        }
+        if cells.len() > COLUMNS {
+            return Err(SccError::LineTooLong(sub.num));
+        }
+
+        let indent = (COLUMNS - cells.len()) / 2;
+        let (first, second) = ROWS[first_row + row];
+        encoder.control([first, second + 0x10 + (indent / 4) as u8 * 2]);
+        if !indent.is_multiple_of(4) {
+            encoder.control([0x17, 0x20 + (indent % 4) as u8]);
+        }
+        for cell in cells {
+            encoder.cell(cell);
+        }
+    }
+    Ok(encoder.finish())
+}
+
+/// Returns the contents of an SCC file holding the subtitles as pop-on captions.
+///
+/// Every caption is loaded right before it's due, or earlier if that would overlap with the
+/// previous caption being erased.
+///
+/// # Errors
+///
+/// If a subtitle doesn't fit on the screen, or can't be loaded before it starts, an SccError is
+/// returned.
+pub fn format(subs: &Subtitles) -> Result<String, SccError> {
+    let encoder = |code: [u8; 2]| {
+        let mut encoder = Encoder::default();
+        encoder.control(code);
+        encoder.finish()
+    };
+    let erase = encoder(ERASE_DISPLAYED_MEMORY);
+    let end_of_caption = encoder(END_OF_CAPTION);
+
+    let mut lines: Vec<(i64, Vec<[u8; 2]>)> = Vec::new();
+    // the first frame that is free, and the end of the caption on display
+    let mut cursor = 0;
+    let mut displayed_until: Option<i64> = None;
+    for sub in subs {
+        let start = to_frames(sub.start_time.to_milliseconds());
+        let pairs = load(sub)?;
+        let too_late = SccError::NotEnoughBandwidth(sub.num);
+        match displayed_until.filter(|&end| end < start) {
+            Some(end) if end + erase.len() as i64 > start - pairs.len() as i64 => {
+                // load before erasing the previous caption
+                let load_start = end - pairs.len() as i64;
+                if load_start < cursor || start < end + erase.len() as i64 {
+                    return Err(too_late);
+                }
+                lines.push((load_start, [pairs, erase.clone()].concat()));
+                lines.push((start, end_of_caption.clone()));
+            }
+            end => {
+                if let Some(end) = end {
+                    if end < cursor {
+                        return Err(too_late);
+                    }
+                    lines.push((end, erase.clone()));
+                    cursor = end + erase.len() as i64;
+                }
+                let load_start = start - pairs.len() as i64;
+                if load_start < cursor {
+                    return Err(too_late);
+                }
+                lines.push((load_start, [pairs, end_of_caption.clone()].concat()));
+            }
+        }
+        cursor = start + end_of_caption.len() as i64;
+        displayed_until = Some(cmp::max(to_frames(sub.end_time.to_milliseconds()), cursor));
+    }
+    if let Some(end) = displayed_until {
+        lines.push((end, erase));
+    }
+
+    let mut res = format!("{}\n\n", HEADER);
+    for (frame, pairs) in lines {
+        let words: Vec<String> = pairs
+            .iter()
+            .map(|[a, b]| format!("{:02x}{:02x}", a, b))
+            .collect();
+        res.push_str(&format!(
+            "{}\t{}\n\n",
+            drop_frame_timecode(frame),
+            words.join(" ")
+        ));
+    }
+    Ok(res)
+}
+
+/// The memory holding the characters of a caption, by row and column.
+type Memory = BTreeMap<usize, [Option<(Style, char)>; COLUMNS]>;
+
+/// Decodes the pop-on captions of the first channel.
+#[derive(Default)]
+struct Decoder {
+    displayed: Memory,
+    non_displayed: Memory,
+    row: usize,
+    column: usize,
+    style: Style,
+    pop_on: bool,
+    first_channel: bool,
+    last_control: Option<[u8; 2]>,
+    /// The start frame and text of the caption on display.
+    shown: Option<(i64, String)>,
+    subs: Vec<Subtitle>,
+}
+
+impl Decoder {
+    fn write(&mut self, c: char, style: Style) {
+        if !self.pop_on || !self.first_channel {
+            return;
+        }
+        let row = self
+            .non_displayed
+            .entry(self.row)
+            .or_insert([None; COLUMNS]);
+        row[self.column] = Some((style, c));
+        self.column = cmp::min(self.column + 1, COLUMNS - 1);
+    }
+
+    fn backspace(&mut self) {
+        if !self.pop_on || !self.first_channel {
+            return;
+        }
+        self.column = self.column.saturating_sub(1);
+        if let Some(row) = self.non_displayed.get_mut(&self.row) {
+            row[self.column] = None;
+        }
+    }
+
+    fn end_caption(&mut self, frame: i64) {
+        if let Some((start, text)) = self.shown.take() {
+            let num = self.subs.len() + 1;
+            self.subs.push(Subtitle::new(
+                num,
+                to_timestamp(start),
+                to_timestamp(frame),
+                text,
+            ));
+        }
+    }
+
+    fn text(memory: &Memory) -> String {
+        let mut lines: Vec<StyledLine> = Vec::new();
+        let mut top = None;
+        for (row, cells) in memory {
+            let mut line = StyledLine::new();
+            for (style, c) in cells
+                .iter()
+                .map(|cell| cell.unwrap_or((Style::default(), ' ')))
+            {
+                match line.last_mut() {
+                    Some((run_style, run)) if *run_style == style => run.push(c),
+                    _ => line.push((style, c.to_string())),
+                }
+            }
+            if line.iter().any(|(_, run)| !run.trim().is_empty()) {
+                top.get_or_insert(*row < ROWS.len() / 2);
+                lines.push(line);
+            }
+        }
+        trim_lines(&mut lines);
+        let text = to_markup(&lines);
+        match top {
+            Some(true) => format!("{{\\an8}}{}", text),
+            _ => text,
+        }
+    }
+
+    fn control(&mut self, [first, second]: [u8; 2], frame: i64) {
+        self.first_channel = first & 0x08 == 0;
+        if !self.first_channel {
+            return;
+        }
+        match (first, second) {
+            (_, 0x40..=0x7F) => {
+                let Some(row) = ROWS
+                    .iter()
+                    .position(|&(f, s)| f == first && s == second & 0x60)
+                else {
+                    return;
+                };
+                self.row = row;
+                self.style = Style {
+                    italic: second & 0x1E == 0x0E,
+                    underline: second & 0x01 == 1,
+                    ..Default::default()
+                };
+                self.column = if second & 0x10 != 0 {
+                    ((second & 0x0E) as usize) * 2
+                } else {
+                    0
+                };
+            }
+            (0x11, 0x20..=0x2F) => {
+                let style = Style {
+                    italic: second & 0x0E == 0x0E,
+                    underline: second & 0x01 == 1,
+                    ..Default::default()
+                };
+                // the space goes with the plain text, so that it stays out of the tags
+                let space_style = if style == Style::default() {
+                    style
+                } else {
+                    self.style
+                };
+                self.write(' ', space_style);
+                self.style = style;
+            }
+            (0x11, 0x30..=0x3F) => self.write(SPECIAL[(second - 0x30) as usize], self.style),
+            (0x12 | 0x13, 0x20..=0x3F) => {
+                self.backspace();
+                let c = EXTENDED[(first - 0x12) as usize][(second - 0x20) as usize];
+                self.write(c, self.style);
+            }
+            (0x17, 0x21..=0x23) => {
+                self.column = cmp::min(self.column + (second - 0x20) as usize, COLUMNS - 1);
+            }
+            (0x14, 0x20..=0x2F) => match [first, second] {
+                RESUME_CAPTION_LOADING => self.pop_on = true,
+                BACKSPACE => self.backspace(),
+                DELETE_TO_END_OF_ROW => {
+                    if let Some(row) = self.non_displayed.get_mut(&self.row) {
+                        row[self.column..].fill(None);
+                    }
+                }
+                ERASE_DISPLAYED_MEMORY => {
+                    self.end_caption(frame);
+                    self.displayed.clear();
+                }
+                ERASE_NON_DISPLAYED_MEMORY => self.non_displayed.clear(),
+                END_OF_CAPTION => {
+                    self.pop_on = true;
+                    mem::swap(&mut self.displayed, &mut self.non_displayed);
+                    self.end_caption(frame);
+                    let text = Decoder::text(&self.displayed);
+                    if !text.is_empty() {
+                        self.shown = Some((frame, text));
+                    }
+                }
+                // roll-up and paint-on captions
+                [_, 0x25..=0x27 | 0x29] => self.pop_on = false,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn pair(&mut self, pair: [u8; 2], frame: i64) {
+        let [first, second] = pair.map(|byte| byte & 0x7F);
+        match first {
+            0x00 if second == 0 => (),
+            0x10..=0x1F => {
+                // control codes are usually sent twice
+                if self.last_control.take() == Some([first, second]) {
+                    return;
+                }
+                self.last_control = Some([first, second]);
+                self.control([first, second], frame);
+            }
+            // extended data services
+            0x00..=0x0F => self.last_control = None,
+            _ => {
+                self.last_control = None;
+                self.write(decode_basic(first), self.style);
+                if second >= 0x20 {
+                    self.write(decode_basic(second), self.style);
+                }
+            }
+        }
+    }
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an SCC file.
+///
+/// A caption that is still on display at the end of the file ends with the last byte pair.
+///
+/// # Errors
+///
+/// If the file lacks the SCC header, or has a malformed timecode or byte pair, a MalformedFile
+/// error variant will be returned.
+pub fn parse(input: &str) -> Result<Subtitles, ParsingError> {
+    let mut lines = input
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    if !lines
+        .next()
+        .is_some_and(|line| line.starts_with("Scenarist_SCC"))
+    {
+        return Err(ParsingError::MalformedFile(
+            "missing SCC header".to_string(),
+        ));
+    }
+
+    let mut decoder = Decoder {
+        first_channel: true,
+        ..Default::default()
+    };
+    let mut last_frame = 0;
+    for line in lines {
+        let mut words = line.split_whitespace();
+        let timecode = words.next().unwrap_or_default();
+        let start = parse_timecode(timecode)
+            .ok_or_else(|| ParsingError::MalformedFile(format!("bad timecode {}", timecode)))?;
+        for (i, word) in words.enumerate() {
+            let value = u16::from_str_radix(word, 16)
+                .ok()
+                .filter(|_| word.len() == 4)
+                .ok_or_else(|| ParsingError::MalformedFile(format!("bad byte pair {}", word)))?;
+            decoder.pair(value.to_be_bytes(), start + i as i64);
+            last_frame = start + i as i64 + 1;
+        }
+    }
+    decoder.end_caption(last_frame);
+    Ok(Subtitles::new_from_vec(decoder.subs))
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn timecodes() {
+        for frames in [0, 1799, 1800, 17982, 107892] {
+            let timecode = drop_frame_timecode(frames);
+            assert_eq!(parse_timecode(&timecode), Some(frames));
+        }
+        assert_eq!(drop_frame_timecode(1800), "00:01:00;02");
+        assert_eq!(drop_frame_timecode(17982), "00:10:00;00");
+        assert_eq!(parse_timecode("00:01:00:00"), Some(1800));
+        assert_eq!(parse_timecode("400000000000:00:00;09"), None);
+        assert_eq!(parse_timecode("00:-1:00:00"), None);
+        assert_eq!(drop_frame_timecode(107892), "01:00:00;00");
+        assert_eq!(to_timestamp(107892), Timestamp::new(0, 59, 59, 996));
+    }
+
+    #[test]
+    fn round_trip() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,001 --> 00:00:03,003\n{\\an8}Ça va, <u>señor</u>\n\n\
+             2\n00:00:03,003 --> 00:00:04,004\n<i>A rather long line that has to be wrapped</i>\n\n\
+             3\n00:00:06,006 --> 00:00:07,007\n♪ ½ ¿Qué? ♪"
+                .to_string(),
+        )
+        .unwrap();
+        let scc = format(&subs).unwrap();
+        let parsed = parse(&scc).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0], subs[0]);
+        assert_eq!(
+            parsed[1].text,
+            "<i>A rather long line that has to</i>\n<i>be wrapped</i>"
+        );
+        assert_eq!(parsed[1].end_time, subs[1].end_time);
+        assert_eq!(parsed[2].text, subs[2].text);
+
+        let crowded = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:01,100\nFirst\n\n\
+             2\n00:00:01,200 --> 00:00:02,000\nThe second one is too soon"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(format(&crowded), Err(SccError::NotEnoughBandwidth(2)));
+        let tall = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n1\n2\n3\n4\n5".to_string(),
+        )
+        .unwrap();
+        assert_eq!(format(&tall), Err(SccError::TooManyRows(1)));
+    }
+}
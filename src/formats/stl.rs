@@ -0,0 +1,755 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The EBU subtitling data exchange format (EBU Tech 3264), commonly known as EBU STL.
+//!
+//! An STL file starts with a General Subtitle Information (GSI) block, described by [`Header`],
+//! followed by a Text and Timing Information (TTI) block for every subtitle. Subtitles with more
+//! text than fits in a single block are continued in extension blocks.
+//!
+//! Text is decoded with the character code table declared in the header: the Latin table is
+//! based on ISO 6937, while the Cyrillic, Arabic, Greek and Hebrew tables are handled through
+//! `encoding_rs`. The text fields of the header itself are only read as ASCII, since the code
+//! pages they use are not supported by `encoding_rs`. Italic and underline control codes are
+//! mapped to .srt markup, and subtitles in the upper half of the screen get an `{\an8}` tag.
+//!
+//! Timecodes are converted at the frame rate of the header and made relative to its start of
+//! programme, so that a subtitle shown at the start of programme starts at zero.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::formats::stl::{self, Header};
+//!
+//! let subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,400\n<i>Déjà vu</i>".to_string()).unwrap();
+//! let header = Header {
+//!     programme_title: "Example".to_string(),
+//!     ..Default::default()
+//! };
+//!
+//! let bytes = stl::format(&subs, &header);
+//! assert_eq!(bytes.len(), 1024 + 128);
+//!
+//! let (parsed_header, parsed_subs) = stl::parse_with_header(&bytes).unwrap();
+//! assert_eq!(parsed_header.programme_title, "Example");
+//! assert_eq!(parsed_subs, subs);
+//! ```
+//!
+//! [`Header`]: struct.Header.html
+
+use super::{is_top_aligned, styled_lines, to_markup, trim_lines, Style, StyledLine};
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+use encoding_rs::{Encoding, ISO_8859_5, ISO_8859_6, ISO_8859_7, ISO_8859_8};
+use unicode_normalization::UnicodeNormalization;
+
+/// The size of the GSI block.
+const GSI_SIZE: usize = 1024;
+/// The size of a TTI block.
+const TTI_SIZE: usize = 128;
+/// The size of the text field of a TTI block.
+const TEXT_FIELD_SIZE: usize = 112;
+
+const ITALICS_ON: u8 = 0x80;
+const ITALICS_OFF: u8 = 0x81;
+const UNDERLINE_ON: u8 = 0x82;
+const UNDERLINE_OFF: u8 = 0x83;
+const NEWLINE: u8 = 0x8A;
+const UNUSED_SPACE: u8 = 0x8F;
+/// The extension block number of the last block of a subtitle.
+const LAST_EXTENSION: u8 = 0xFF;
+
+/// The frame rate of the timecodes, declared by the disk format code of the header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FrameRate {
+    /// 25 frames per second ("STL25.01").
+    #[default]
+    Fps25,
+    /// 30 frames per second ("STL30.01").
+    Fps30,
+}
+
+impl FrameRate {
+    /// Returns the number of frames per second.
+    pub fn fps(&self) -> u32 {
+        match self {
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps30 => 30,
+        }
+    }
+}
+
+/// The way the subtitles are meant to be displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DisplayStandard {
+    Undefined,
+    OpenSubtitling,
+    #[default]
+    Level1Teletext,
+    Level2Teletext,
+}
+
+impl DisplayStandard {
+    fn code(&self) -> u8 {
+        match self {
+            DisplayStandard::Undefined => b' ',
+            DisplayStandard::OpenSubtitling => b'0',
+            DisplayStandard::Level1Teletext => b'1',
+            DisplayStandard::Level2Teletext => b'2',
+        }
+    }
+
+    fn from_code(code: u8) -> DisplayStandard {
+        match code {
+            b'0' => DisplayStandard::OpenSubtitling,
+            b'1' => DisplayStandard::Level1Teletext,
+            b'2' => DisplayStandard::Level2Teletext,
+            _ => DisplayStandard::Undefined,
+        }
+    }
+}
+
+/// The character code table used by the text of the subtitles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CharacterTable {
+    /// Latin alphabet, based on ISO 6937.
+    #[default]
+    Latin,
+    /// Latin/Cyrillic alphabet (ISO 8859-5).
+    Cyrillic,
+    /// Latin/Arabic alphabet (ISO 8859-6).
+    Arabic,
+    /// Latin/Greek alphabet (ISO 8859-7).
+    Greek,
+    /// Latin/Hebrew alphabet (ISO 8859-8).
+    Hebrew,
+}
+
+impl CharacterTable {
+    fn code(&self) -> &'static str {
+        match self {
+            CharacterTable::Latin => "00",
+            CharacterTable::Cyrillic => "01",
+            CharacterTable::Arabic => "02",
+            CharacterTable::Greek => "03",
+            CharacterTable::Hebrew => "04",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<CharacterTable> {
+        match code {
+            "00" => Some(CharacterTable::Latin),
+            "01" => Some(CharacterTable::Cyrillic),
+            "02" => Some(CharacterTable::Arabic),
+            "03" => Some(CharacterTable::Greek),
+            "04" => Some(CharacterTable::Hebrew),
+            _ => None,
+        }
+    }
+
+    /// Returns the encoding of the table, or None for the Latin table.
+    fn encoding(&self) -> Option<&'static Encoding> {
+        match self {
+            CharacterTable::Latin => None,
+            CharacterTable::Cyrillic => Some(ISO_8859_5),
+            CharacterTable::Arabic => Some(ISO_8859_6),
+            CharacterTable::Greek => Some(ISO_8859_7),
+            CharacterTable::Hebrew => Some(ISO_8859_8),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        match self.encoding() {
+            Some(encoding) => encoding.decode_without_bom_handling(bytes).0.into_owned(),
+            None => decode_latin(bytes),
+        }
+    }
+
+    fn encode(&self, text: &str) -> Vec<u8> {
+        match self.encoding() {
+            Some(encoding) => text
+                .chars()
+                .flat_map(|c| {
+                    let mut buf = [0; 4];
+                    let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut buf));
+                    if unmappable {
+                        vec![b'?']
+                    } else {
+                        bytes.into_owned()
+                    }
+                })
+                .collect(),
+            None => encode_latin(text),
+        }
+    }
+}
+
+/// The characters of the upper half of the Latin table, from 0xA0 to 0xFF. Positions 0xC1 to
+/// 0xCF hold the diacritical marks applied to the following letter, as combining characters.
+const LATIN_UPPER: [char; 96] = [
+    '\u{a0}', '¡', '¢', '£', '$', '¥', '#', '§', '¤', '‘', '“', '«', '←', '↑', '→', '↓', //
+    '°', '±', '²', '³', '×', 'µ', '¶', '·', '÷', '’', '”', '»', '¼', '½', '¾', '¿', //
+    '\0', '\u{300}', '\u{301}', '\u{302}', '\u{303}', '\u{304}', '\u{306}', '\u{307}', //
+    '\u{308}', '\0', '\u{30a}', '\u{327}', '\0', '\u{30b}', '\u{328}', '\u{30c}', //
+    '―', '¹', '®', '©', '™', '♪', '¬', '¦', '\0', '\0', '\0', '\0', '⅛', '⅜', '⅝', '⅞', //
+    'Ω', 'Æ', 'Đ', 'ª', 'Ħ', '\0', 'Ĳ', 'Ŀ', 'Ł', 'Ø', 'Œ', 'º', 'Þ', 'Ŧ', 'Ŋ', 'ŉ', //
+    'ĸ', 'æ', 'đ', 'ð', 'ħ', 'ı', 'ĳ', 'ŀ', 'ł', 'ø', 'œ', 'ß', 'þ', 'ŧ', 'ŋ', '\u{ad}',
+];
+
+/// Checks if a byte of the Latin table is a diacritical mark.
+fn is_diacritic(byte: u8) -> bool {
+    (0xC1..=0xCF).contains(&byte)
+}
+
+fn decode_latin(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len());
+    let mut pending_mark = None;
+    for &byte in bytes {
+        let c = match byte {
+            0x20..=0x7E => byte as char,
+            0xA0..=0xFF => LATIN_UPPER[(byte - 0xA0) as usize],
+            _ => '\0',
+        };
+        if c == '\0' {
+            continue;
+        }
+        if is_diacritic(byte) {
+            pending_mark = Some(c);
+            continue;
+        }
+        res.push(c);
+        if let Some(mark) = pending_mark.take() {
+            res.push(mark);
+        }
+    }
+    res.nfc().collect()
+}
+
+fn encode_latin(text: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if (' '..='~').contains(&c) {
+            res.push(c as u8);
+        } else if let Some(pos) = LATIN_UPPER.iter().position(|&u| u == c) {
+            res.push(0xA0 + pos as u8);
+        } else {
+            let decomposed: Vec<char> = c.to_string().nfd().collect();
+            let mark = decomposed
+                .get(1)
+                .and_then(|m| LATIN_UPPER.iter().position(|u| u == m))
+                .map(|pos| 0xA0 + pos as u8);
+            match (decomposed.len(), mark) {
+                (2, Some(mark)) if decomposed[0].is_ascii_alphabetic() => {
+                    res.push(mark);
+                    res.push(decomposed[0] as u8);
+                }
+                _ => res.push(b'?'),
+            }
+        }
+    }
+    res
+}
+
+/// The General Subtitle Information block of an STL file.
+///
+/// Fields describing the subtitles themselves, such as their number, are computed when the file
+/// is written.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Header {
+    /// The code page of the text fields of the header, e.g. "850".
+    pub code_page: String,
+    pub frame_rate: FrameRate,
+    pub display_standard: DisplayStandard,
+    pub character_table: CharacterTable,
+    /// The language of the subtitles, as a two digit hexadecimal EBU language code (e.g. "09"
+    /// for English).
+    pub language_code: String,
+    pub programme_title: String,
+    pub episode_title: String,
+    pub translated_programme_title: String,
+    pub translated_episode_title: String,
+    pub translator_name: String,
+    pub translator_contact: String,
+    pub subtitle_list_reference: String,
+    /// The creation date in the format YYMMDD.
+    pub creation_date: String,
+    /// The revision date in the format YYMMDD.
+    pub revision_date: String,
+    pub revision_number: u8,
+    /// The maximum number of characters in a row.
+    pub max_chars_per_row: u8,
+    /// The maximum number of rows. Rows in the upper half are considered the top of the screen.
+    pub max_rows: u8,
+    /// The timecode of the start of the programme.
+    pub start_of_programme: Timestamp,
+    /// The country of origin as an ISO 3166 alpha-3 code, e.g. "GBR".
+    pub country_of_origin: String,
+    pub publisher: String,
+    pub editor_name: String,
+    pub editor_contact: String,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            code_page: "850".to_string(),
+            frame_rate: FrameRate::default(),
+            display_standard: DisplayStandard::default(),
+            character_table: CharacterTable::default(),
+            language_code: "00".to_string(),
+            programme_title: String::new(),
+            episode_title: String::new(),
+            translated_programme_title: String::new(),
+            translated_episode_title: String::new(),
+            translator_name: String::new(),
+            translator_contact: String::new(),
+            subtitle_list_reference: String::new(),
+            creation_date: String::new(),
+            revision_date: String::new(),
+            revision_number: 0,
+            max_chars_per_row: 40,
+            max_rows: 23,
+            start_of_programme: Timestamp::from_milliseconds(0),
+            country_of_origin: String::new(),
+            publisher: String::new(),
+            editor_name: String::new(),
+            editor_contact: String::new(),
+        }
+    }
+}
+
+fn malformed(reason: &str) -> ParsingError {
+    ParsingError::MalformedFile(reason.to_string())
+}
+
+/// Reads a text field of the header as ASCII.
+fn read_field(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| if b.is_ascii() { b as char } else { '\u{fffd}' })
+        .collect::<String>()
+        .trim_matches(|c: char| c == ' ' || c == '\0')
+        .to_string()
+}
+
+/// Writes a text field of the header as ASCII, padded with spaces.
+fn write_field(res: &mut Vec<u8>, text: &str, size: usize) {
+    let mut field: Vec<u8> = text
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .take(size)
+        .collect();
+    field.resize(size, b' ');
+    res.extend(field);
+}
+
+/// Converts hours, minutes, seconds and frames to a number of milliseconds.
+fn timecode_millis(h: u8, m: u8, s: u8, f: u8, frame_rate: FrameRate) -> u32 {
+    Timestamp::convert_to_milliseconds(h, m, s, 0)
+        + (f as u32 * 1000 + frame_rate.fps() / 2) / frame_rate.fps()
+}
+
+/// Converts a number of milliseconds to hours, minutes, seconds and frames.
+fn millis_timecode(millis: u32, frame_rate: FrameRate) -> [u8; 4] {
+    let fps = frame_rate.fps();
+    // rounding never goes past the last frame of the largest timecode
+    let last_frame = 256 * 3600 * fps as u64 - 1;
+    let mut frames = std::cmp::min((millis as u64 * fps as u64 + 500) / 1000, last_frame);
+    let mut res = [0; 4];
+    res[3] = (frames % fps as u64) as u8;
+    frames /= fps as u64;
+    res[2] = (frames % 60) as u8;
+    frames /= 60;
+    res[1] = (frames % 60) as u8;
+    res[0] = cmp_hours(frames / 60);
+    res
+}
+
+/// Moves a time forward by the start of the programme, stopping at the maximum timestamp.
+fn with_offset(time: Timestamp, offset: Timestamp) -> Timestamp {
+    let millis = time
+        .to_milliseconds()
+        .saturating_add(offset.to_milliseconds());
+    Timestamp::from_milliseconds(std::cmp::min(millis, Timestamp::MAX_TIMESTAMP_MILLIS))
+}
+
+/// Limits the hours of a timecode to the ones it can hold.
+fn cmp_hours(hours: u64) -> u8 {
+    std::cmp::min(hours, u8::MAX as u64) as u8
+}
+
+/// Parses a timecode of the header in the format HHMMSSFF.
+fn parse_header_timecode(text: &str, frame_rate: FrameRate) -> Option<Timestamp> {
+    if text.len() != 8 || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let part = |i: usize| text[i..i + 2].parse::<u8>().ok();
+    Some(Timestamp::from_milliseconds(timecode_millis(
+        part(0)?,
+        part(2)?,
+        part(4)?,
+        part(6)?,
+        frame_rate,
+    )))
+}
+
+impl Header {
+    fn parse(gsi: &[u8]) -> Result<Header, ParsingError> {
+        let field = |start: usize, end: usize| read_field(&gsi[start..end]);
+        let number = |start: usize, end: usize| field(start, end).parse::<u8>().ok();
+        let frame_rate = match &gsi[3..11] {
+            b"STL25.01" => FrameRate::Fps25,
+            b"STL30.01" => FrameRate::Fps30,
+            _ => return Err(malformed("unknown disk format code")),
+        };
+        let character_table = CharacterTable::from_code(&field(12, 14))
+            .ok_or_else(|| malformed("unknown character code table"))?;
+        let defaults = Header::default();
+        Ok(Header {
+            code_page: field(0, 3),
+            frame_rate,
+            display_standard: DisplayStandard::from_code(gsi[11]),
+            character_table,
+            language_code: field(14, 16),
+            programme_title: field(16, 48),
+            episode_title: field(48, 80),
+            translated_programme_title: field(80, 112),
+            translated_episode_title: field(112, 144),
+            translator_name: field(144, 176),
+            translator_contact: field(176, 208),
+            subtitle_list_reference: field(208, 224),
+            creation_date: field(224, 230),
+            revision_date: field(230, 236),
+            revision_number: number(236, 238).unwrap_or(0),
+            max_chars_per_row: number(251, 253).unwrap_or(defaults.max_chars_per_row),
+            max_rows: number(253, 255).unwrap_or(defaults.max_rows),
+            start_of_programme: parse_header_timecode(&field(256, 264), frame_rate)
+                .unwrap_or(defaults.start_of_programme),
+            country_of_origin: field(274, 277),
+            publisher: field(277, 309),
+            editor_name: field(309, 341),
+            editor_contact: field(341, 373),
+        })
+    }
+
+    fn write(&self, res: &mut Vec<u8>, blocks: usize, subtitles: usize, first_cue: Timestamp) {
+        let timecode = |time: Timestamp| {
+            millis_timecode(time.to_milliseconds(), self.frame_rate)
+                .iter()
+                .map(|part| format!("{:02}", part))
+                .collect::<String>()
+        };
+        write_field(res, &self.code_page, 3);
+        res.extend_from_slice(match self.frame_rate {
+            FrameRate::Fps25 => b"STL25.01",
+            FrameRate::Fps30 => b"STL30.01",
+        });
+        res.push(self.display_standard.code());
+        write_field(res, self.character_table.code(), 2);
+        write_field(res, &self.language_code, 2);
+        for (text, size) in [
+            (&self.programme_title, 32),
+            (&self.episode_title, 32),
+            (&self.translated_programme_title, 32),
+            (&self.translated_episode_title, 32),
+            (&self.translator_name, 32),
+            (&self.translator_contact, 32),
+            (&self.subtitle_list_reference, 16),
+            (&self.creation_date, 6),
+            (&self.revision_date, 6),
+        ] {
+            write_field(res, text, size);
+        }
+        write_field(res, &format!("{:02}", self.revision_number), 2);
+        write_field(res, &format!("{:05}", blocks), 5);
+        write_field(res, &format!("{:05}", subtitles), 5);
+        write_field(res, "001", 3);
+        write_field(res, &format!("{:02}", self.max_chars_per_row), 2);
+        write_field(res, &format!("{:02}", self.max_rows), 2);
+        // timecode status: intended for use
+        res.push(b'1');
+        write_field(res, &timecode(self.start_of_programme), 8);
+        write_field(res, &timecode(first_cue), 8);
+        // total number of disks and disk sequence number
+        res.extend_from_slice(b"11");
+        for (text, size) in [
+            (&self.country_of_origin, 3),
+            (&self.publisher, 32),
+            (&self.editor_name, 32),
+            (&self.editor_contact, 32),
+        ] {
+            write_field(res, text, size);
+        }
+        res.resize(res.len() + 75 + 576, b' ');
+    }
+}
+
+/// Decodes the text field of a subtitle into .srt markup.
+fn decode_text(bytes: &[u8], table: CharacterTable) -> String {
+    let mut lines = vec![StyledLine::new()];
+    let mut style = Style::default();
+    let mut chunk: Vec<u8> = Vec::new();
+    let flush = |chunk: &mut Vec<u8>, lines: &mut Vec<StyledLine>, style: Style| {
+        if !chunk.is_empty() {
+            let text = table.decode(chunk);
+            lines
+                .last_mut()
+                .expect("there is always a line")
+                .push((style, text));
+            chunk.clear();
+        }
+    };
+    for &byte in bytes {
+        match byte {
+            // teletext control codes, such as colours and boxing
+            0x00..=0x1F => flush(&mut chunk, &mut lines, style),
+            0x80..=0x8F => {
+                flush(&mut chunk, &mut lines, style);
+                match byte {
+                    ITALICS_ON => style.italic = true,
+                    ITALICS_OFF => style.italic = false,
+                    UNDERLINE_ON => style.underline = true,
+                    UNDERLINE_OFF => style.underline = false,
+                    NEWLINE => lines.push(StyledLine::new()),
+                    _ => (),
+                }
+            }
+            _ => chunk.push(byte),
+        }
+    }
+    flush(&mut chunk, &mut lines, style);
+
+    // empty rows are used for spacing in teletext
+    trim_lines(&mut lines);
+    to_markup(&lines)
+}
+
+/// Encodes the text of a subtitle, without any `{...}` tags, into the contents of text fields.
+fn encode_text(text: &str, table: CharacterTable) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut current = Style::default();
+    for (i, line) in styled_lines(text).iter().enumerate() {
+        if i > 0 {
+            res.push(NEWLINE);
+        }
+        for (style, run) in line {
+            if style.italic != current.italic {
+                res.push(if style.italic {
+                    ITALICS_ON
+                } else {
+                    ITALICS_OFF
+                });
+            }
+            if style.underline != current.underline {
+                res.push(if style.underline {
+                    UNDERLINE_ON
+                } else {
+                    UNDERLINE_OFF
+                });
+            }
+            current = *style;
+            res.extend(table.encode(run));
+        }
+    }
+    res
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an STL file, along with its
+/// header.
+///
+/// Comment blocks are skipped, and subtitles are numbered starting from 1.
+///
+/// # Errors
+///
+/// If the file is too short, declares an unknown frame rate or character code table, or ends
+/// in the middle of a block, a MalformedFile error variant will be returned.
+pub fn parse_with_header(input: &[u8]) -> Result<(Header, Subtitles), ParsingError> {
+    if input.len() < GSI_SIZE {
+        return Err(malformed("missing GSI block"));
+    }
+    let header = Header::parse(&input[..GSI_SIZE])?;
+    let blocks = &input[GSI_SIZE..];
+    if !blocks.len().is_multiple_of(TTI_SIZE) {
+        return Err(malformed("incomplete TTI block"));
+    }
+    let offset = header.start_of_programme.to_milliseconds();
+    let timestamp = |tc: &[u8]| {
+        let millis = timecode_millis(tc[0], tc[1], tc[2], tc[3], header.frame_rate);
+        Timestamp::from_milliseconds(millis.saturating_sub(offset))
+    };
+
+    let mut subs = Subtitles::new();
+    let mut text_field: Vec<u8> = Vec::new();
+    for block in blocks.chunks(TTI_SIZE) {
+        // comment flag
+        if block[15] != 0 {
+            continue;
+        }
+        text_field.extend_from_slice(&block[16..]);
+        if block[3] != LAST_EXTENSION {
+            continue;
+        }
+        let end = text_field
+            .iter()
+            .rposition(|&b| b != UNUSED_SPACE)
+            .map_or(0, |pos| pos + 1);
+        let mut text = decode_text(&text_field[..end], header.character_table);
+        text_field.clear();
+        let vertical_position = block[13];
+        if vertical_position < header.max_rows / 2 {
+            text.insert_str(0, "{\\an8}");
+        }
+        subs.push(Subtitle::new(
+            subs.len() + 1,
+            timestamp(&block[5..9]),
+            timestamp(&block[9..13]),
+            text,
+        ));
+    }
+    Ok((header, subs))
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of an STL file.
+///
+/// # Errors
+///
+/// See [`parse_with_header`](fn.parse_with_header.html).
+pub fn parse(input: &[u8]) -> Result<Subtitles, ParsingError> {
+    parse_with_header(input).map(|(_, subs)| subs)
+}
+
+/// Returns the contents of an STL file holding the subtitles, using the given header.
+///
+/// Text that doesn't fit in a single TTI block is continued in extension blocks, and characters
+/// missing from the character code table of the header are replaced with "?". Bold text is
+/// written as regular text, since STL cannot represent it. Times moved past the maximum timestamp
+/// by the start of the programme are written as the maximum.
+pub fn format(subs: &Subtitles, header: &Header) -> Vec<u8> {
+    let offset = header.start_of_programme;
+    let mut tti = Vec::new();
+    let mut blocks = 0;
+    for (i, sub) in subs.into_iter().enumerate() {
+        let text = encode_text(&sub.text, header.character_table);
+        let max_rows = header.max_rows as usize;
+        let lines = (1 + text.iter().filter(|&&b| b == NEWLINE).count()).min(max_rows.max(1));
+        let vertical_position = if is_top_aligned(&sub.text) {
+            1
+        } else {
+            max_rows.saturating_sub(1 + 2 * (lines - 1)).max(1) as u8
+        };
+        let chunks: Vec<&[u8]> = if text.is_empty() {
+            vec![&[]]
+        } else {
+            text.chunks(TEXT_FIELD_SIZE).collect()
+        };
+        for (extension, chunk) in chunks.iter().enumerate() {
+            let start = with_offset(sub.start_time, offset);
+            let end = with_offset(sub.end_time, offset);
+            // subtitle group number and subtitle number
+            tti.push(0);
+            tti.extend_from_slice(&((i % 0x10000) as u16).to_le_bytes());
+            tti.push(if extension + 1 == chunks.len() {
+                LAST_EXTENSION
+            } else {
+                extension as u8
+            });
+            // cumulative status: not part of a cumulative set
+            tti.push(0);
+            tti.extend(millis_timecode(start.to_milliseconds(), header.frame_rate));
+            tti.extend(millis_timecode(end.to_milliseconds(), header.frame_rate));
+            tti.push(vertical_position);
+            // centered justification, subtitle data
+            tti.extend_from_slice(&[2, 0]);
+            tti.extend_from_slice(chunk);
+            tti.resize(tti.len() + TEXT_FIELD_SIZE - chunk.len(), UNUSED_SPACE);
+            blocks += 1;
+        }
+    }
+
+    let first_cue = subs
+        .into_iter()
+        .map(|s| s.start_time)
+        .min()
+        .unwrap_or(Timestamp::from_milliseconds(0));
+    let mut res = Vec::with_capacity(GSI_SIZE + tti.len());
+    header.write(&mut res, blocks, subs.len(), with_offset(first_cue, offset));
+    res.extend(tti);
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn latin_table() {
+        let text = "Ça coûte 5£ — ŁÓDŹ ½";
+        let encoded = encode_latin(text);
+        assert_eq!(&encoded[..3], &[0xCB, b'C', b'a']);
+        assert_eq!(decode_latin(&encoded), "Ça coûte 5£ ? ŁÓDŹ ½");
+    }
+
+    #[test]
+    fn round_trip() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n{\\an8}<i>Top</i> <u>line</u>\n\n\
+             2\n00:00:03,040 --> 00:00:05,000\nΚαλημέρα"
+                .to_string(),
+        )
+        .unwrap();
+        let header = Header {
+            character_table: CharacterTable::Greek,
+            frame_rate: FrameRate::Fps30,
+            start_of_programme: Timestamp::new(10, 0, 0, 0),
+            ..Default::default()
+        };
+        let bytes = format(&subs, &header);
+        assert_eq!(&bytes[256..272], b"1000000010000100");
+        assert_eq!(bytes[GSI_SIZE + 13], 1);
+        let (parsed_header, parsed) = parse_with_header(&bytes).unwrap();
+        assert_eq!(parsed_header, header);
+        // 3,040 is rounded to the nearest frame
+        assert_eq!(parsed[1].start_time, Timestamp::new(0, 0, 3, 33));
+        assert_eq!(parsed[0], subs[0]);
+        assert_eq!(parsed[1].text, "Καλημέρα");
+
+        let long = "x".repeat(150);
+        let subs = Subtitles::new_from_vec(vec![Subtitle::new(
+            1,
+            Timestamp::new(0, 0, 1, 0),
+            Timestamp::new(0, 0, 2, 0),
+            long.clone(),
+        )]);
+        let bytes = format(&subs, &Header::default());
+        assert_eq!(bytes.len(), GSI_SIZE + 2 * TTI_SIZE);
+        assert_eq!(bytes[GSI_SIZE + 3], 0);
+        assert_eq!(parse(&bytes).unwrap()[0].text, long);
+
+        let many_lines = Subtitles::new_from_vec(vec![Subtitle::new(
+            1,
+            Timestamp::new(0, 0, 1, 0),
+            Timestamp::new(0, 0, 2, 0),
+            "x\n".repeat(300),
+        )]);
+        let bytes = format(&many_lines, &Header::default());
+        assert_eq!(bytes[GSI_SIZE + 13], 1);
+
+        let late = Subtitles::new_from_vec(vec![Subtitle::new(
+            1,
+            Timestamp::new(200, 0, 0, 0),
+            Timestamp::new(200, 0, 1, 0),
+            "Late".to_string(),
+        )]);
+        let header = Header {
+            start_of_programme: Timestamp::new(60, 0, 0, 0),
+            ..Default::default()
+        };
+        let bytes = format(&late, &header);
+        assert_eq!(&bytes[GSI_SIZE + 5..GSI_SIZE + 9], &[255, 59, 59, 24]);
+    }
+}
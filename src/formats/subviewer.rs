@@ -0,0 +1,227 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! SubViewer 2.0 .sub files.
+//!
+//! A SubViewer file starts with an `[INFORMATION]` section, described by [`Header`], followed by
+//! cues separated by blank lines. Each cue starts with a `00:00:01.00,00:00:03.00` line holding
+//! its start and end time in centiseconds, and its lines are joined with `[br]`. Since SubViewer
+//! has no inline formatting, tags are removed when writing.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::subviewer::{self, Header};
+//!
+//! let input = "[INFORMATION]\n[TITLE]Example\n[END INFORMATION]\n[SUBTITLE]\n\n\
+//!              00:00:01.50,00:00:03.00\nHello[br]world";
+//! let (header, subs) = subviewer::parse_with_header(input).unwrap();
+//! assert_eq!(header.title, "Example");
+//! assert_eq!(subs[0].to_string(), "1\n00:00:01,500 --> 00:00:03,000\nHello\nworld");
+//!
+//! let output = subviewer::format(&subs, &header);
+//! assert!(output.ends_with("00:00:01.50,00:00:03.00\nHello[br]world\n"));
+//! ```
+//!
+//! [`Header`]: struct.Header.html
+
+use super::parse_fraction;
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+
+const LINE_BREAK: &str = "[br]";
+
+/// The `[INFORMATION]` section of a SubViewer file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub title: String,
+    pub author: String,
+    pub source: String,
+    /// The program the file was made with.
+    pub program: String,
+    pub file_path: String,
+    pub delay: String,
+    pub cd_track: String,
+    pub comment: String,
+}
+
+impl Header {
+    /// Returns the tags of the header along with their values.
+    fn fields(&self) -> [(&'static str, &String); 8] {
+        [
+            ("TITLE", &self.title),
+            ("AUTHOR", &self.author),
+            ("SOURCE", &self.source),
+            ("PRG", &self.program),
+            ("FILEPATH", &self.file_path),
+            ("DELAY", &self.delay),
+            ("CD TRACK", &self.cd_track),
+            ("COMMENT", &self.comment),
+        ]
+    }
+
+    /// Sets the field of a header tag, ignoring unknown tags.
+    fn set(&mut self, tag: &str, value: &str) {
+        let field = match tag.to_ascii_uppercase().as_str() {
+            "TITLE" => &mut self.title,
+            "AUTHOR" => &mut self.author,
+            "SOURCE" => &mut self.source,
+            "PRG" => &mut self.program,
+            "FILEPATH" => &mut self.file_path,
+            "DELAY" => &mut self.delay,
+            "CD TRACK" => &mut self.cd_track,
+            "COMMENT" => &mut self.comment,
+            _ => return,
+        };
+        *field = value.trim().to_string();
+    }
+}
+
+/// Parses a timestamp in the format "hh:mm:ss.cc".
+fn parse_timestamp(s: &str) -> Result<Timestamp, ParsingError> {
+    let (time, centiseconds) = s
+        .trim()
+        .rsplit_once('.')
+        .ok_or(ParsingError::MalformedTimestamp)?;
+    let time = Timestamp::parse(&format!("{},0", time))?;
+    // a fraction of any length, read as centiseconds when it has two digits
+    let fraction = parse_fraction(centiseconds).ok_or(ParsingError::MalformedTimestamp)?;
+    match time.to_milliseconds().checked_add(fraction) {
+        Some(millis) if millis <= Timestamp::MAX_TIMESTAMP_MILLIS => {
+            Ok(Timestamp::from_milliseconds(millis))
+        }
+        _ => Err(ParsingError::MalformedTimestamp),
+    }
+}
+
+/// Writes a timestamp in the format "hh:mm:ss.cc", rounded to the nearest centisecond.
+fn timestamp(time: &Timestamp) -> String {
+    let millis = std::cmp::min(time.to_milliseconds() + 5, Timestamp::MAX_TIMESTAMP_MILLIS);
+    let (hours, minutes, seconds, milliseconds) = Timestamp::from_milliseconds(millis).get();
+    format!(
+        "{:02}:{:02}:{:02}.{:02}",
+        hours,
+        minutes,
+        seconds,
+        milliseconds / 10
+    )
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of a SubViewer 2.0 file, along
+/// with its header. Subtitles are numbered starting from 1.
+///
+/// Lines in square brackets before the first cue are read as the header, and unknown tags such as
+/// the `[COLF]` style line are ignored.
+///
+/// # Errors
+///
+/// If a cue lacks a timing line, a BadSubtitleStructure error variant will be returned, while
+/// malformed timestamps result in a MalformedTimestamp or ParseIntError variant.
+pub fn parse_with_header(input: &str) -> Result<(Header, Subtitles), ParsingError> {
+    let input = input.trim_start_matches('\u{feff}').replace('\r', "");
+    let mut header = Header::default();
+    let mut subs = Subtitles::new();
+    let mut lines = input.lines().map(str::trim_end).peekable();
+    while lines.peek().is_some() {
+        let cue: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+        let mut cue = cue.as_slice();
+        if subs.is_empty() {
+            while let Some(line) = cue.first().filter(|line| line.starts_with('[')) {
+                if let Some((tag, value)) = line[1..].split_once(']') {
+                    header.set(tag, value);
+                }
+                cue = &cue[1..];
+            }
+        }
+        let Some((timing, text)) = cue.split_first() else {
+            continue;
+        };
+        let num = subs.len() + 1;
+        let (start, end) = timing
+            .split_once(',')
+            .ok_or(ParsingError::BadSubtitleStructure(num))?;
+        subs.push(Subtitle::new(
+            num,
+            parse_timestamp(start)?,
+            parse_timestamp(end)?,
+            text.join("\n").replace(LINE_BREAK, "\n"),
+        ));
+    }
+    Ok((header, subs))
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of a SubViewer 2.0 file.
+///
+/// # Errors
+///
+/// See [`parse_with_header`](fn.parse_with_header.html).
+pub fn parse(input: &str) -> Result<Subtitles, ParsingError> {
+    parse_with_header(input).map(|(_, subs)| subs)
+}
+
+/// Returns the contents of a SubViewer 2.0 file holding the subtitles, without any formatting
+/// tags, using the given header.
+pub fn format(subs: &Subtitles, header: &Header) -> String {
+    let mut res = String::from("[INFORMATION]\n");
+    for (tag, value) in header.fields() {
+        res.push_str(&format!("[{}]{}\n", tag, value));
+    }
+    res.push_str("[END INFORMATION]\n[SUBTITLE]\n[COLF]&HFFFFFF,[STYLE]no,[SIZE]18,[FONT]Arial\n");
+    for sub in subs {
+        res.push_str(&format!(
+            "\n{},{}\n{}\n",
+            timestamp(&sub.start_time),
+            timestamp(&sub.end_time),
+            sub.plain_text().replace('\n', LINE_BREAK)
+        ));
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn header_and_timestamps() {
+        assert_eq!(
+            parse_timestamp("01:02:03.4").unwrap(),
+            Timestamp::new(1, 2, 3, 400)
+        );
+        assert_eq!(
+            parse_timestamp("01:02:03.456").unwrap(),
+            Timestamp::new(1, 2, 3, 456)
+        );
+        for malformed in ["00:00:00.ab€", "00:00:00.-5", "255:59:60.000"] {
+            assert!(matches!(
+                parse_timestamp(malformed),
+                Err(ParsingError::MalformedTimestamp)
+            ));
+        }
+        assert_eq!(timestamp(&Timestamp::new(0, 0, 59, 996)), "00:01:00.00");
+
+        let header = Header {
+            title: "Title".to_string(),
+            delay: "0".to_string(),
+            ..Default::default()
+        };
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n<i>One</i>\ntwo\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nThree"
+                .to_string(),
+        )
+        .unwrap();
+        let output = format(&subs, &header);
+        assert!(output.contains("[TITLE]Title\n[AUTHOR]\n"));
+        assert!(output.contains("\n\n00:00:01.00,00:00:02.00\nOne[br]two\n\n"));
+        let (parsed_header, parsed) = parse_with_header(&output).unwrap();
+        assert_eq!(parsed_header, header);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].text, "One\ntwo");
+        assert_eq!(parsed[1], subs[1]);
+    }
+}
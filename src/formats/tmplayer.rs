@@ -0,0 +1,167 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! TMPlayer files.
+//!
+//! Every line of a TMPlayer file is a subtitle, starting with its start time in whole seconds,
+//! such as `00:00:01:`, with `|` separating lines of text. A line without any text clears the
+//! screen and ends the previous subtitle, and other end times are inferred as described by an
+//! [`EndTimeConfig`]. The `00:00:01=` variant and the multiline `00:00:01,1=` one, giving every
+//! line of text its own line, are read as well. Formatting is not supported.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::{tmplayer, EndTimeConfig};
+//!
+//! let subs = tmplayer::parse("00:00:01:Hello|world\n00:00:04:\n00:00:05:Bye", &EndTimeConfig::default()).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:04,000\nHello\nworld\n\n\
+//!                               2\n00:00:05,000 --> 00:00:08,000\nBye");
+//!
+//! assert_eq!(tmplayer::format(&subs), "00:00:01:Hello|world\n00:00:04:\n00:00:05:Bye\n00:00:08:\n");
+//! ```
+//!
+//! [`EndTimeConfig`]: ../struct.EndTimeConfig.html
+
+use super::EndTimeConfig;
+use crate::{ParsingError, Subtitles, Timestamp};
+
+/// Splits a line into its start time, its line number in the multiline variant, and its text.
+pub(crate) fn split_line(line: &str) -> Option<(Timestamp, Option<&str>, &str)> {
+    let mut rest = line;
+    let mut parts = [0; 3];
+    for (i, part) in parts.iter_mut().enumerate() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        *part = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        if i < 2 {
+            rest = rest.strip_prefix(':')?;
+        }
+    }
+    let time = Timestamp::new(parts[0], parts[1], parts[2], 0);
+    match rest.chars().next()? {
+        ':' | '=' => Some((time, None, &rest[1..])),
+        ',' => {
+            let (number, text) = rest[1..].split_once('=')?;
+            Some((time, Some(number), text))
+        }
+        _ => None,
+    }
+}
+
+/// Constructs a new Subtitles collection by parsing the contents of a TMPlayer file. Subtitles
+/// are sorted by start time and numbered starting from 1.
+///
+/// # Errors
+///
+/// If a line doesn't start with a valid time, a BadSubtitleStructure error variant will be
+/// returned.
+pub fn parse(input: &str, config: &EndTimeConfig) -> Result<Subtitles, ParsingError> {
+    // the start time and lines of every line of the file, empty ones clearing the screen
+    let mut lines: Vec<(Timestamp, Vec<String>)> = Vec::new();
+    for line in input
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let (time, number, text) =
+            split_line(line).ok_or(ParsingError::BadSubtitleStructure(lines.len() + 1))?;
+        let text = text.trim();
+        match lines.last_mut() {
+            // a following line of the multiline variant
+            Some((last, text_lines)) if *last == time && number.is_some_and(|n| n != "1") => {
+                if !text.is_empty() {
+                    text_lines.push(text.to_string());
+                }
+            }
+            _ if text.is_empty() => lines.push((time, Vec::new())),
+            _ => lines.push((time, text.split('|').map(str::to_string).collect())),
+        }
+    }
+    lines.sort_by_key(|(time, _)| *time);
+
+    let mut cues = Vec::new();
+    for (i, (start, text)) in lines.iter().enumerate() {
+        if text.is_empty() {
+            continue;
+        }
+        let end = lines
+            .get(i + 1)
+            .filter(|(_, next)| next.is_empty())
+            .map(|(end, _)| *end);
+        cues.push((*start, end, text.join("\n")));
+    }
+    Ok(config.subtitles(cues))
+}
+
+/// Writes a timestamp in the format "hh:mm:ss", rounded to the nearest second.
+fn timestamp(time: &Timestamp) -> String {
+    let millis = std::cmp::min(
+        time.to_milliseconds() + 500,
+        Timestamp::MAX_TIMESTAMP_MILLIS,
+    );
+    let (hours, minutes, seconds, _) = Timestamp::from_milliseconds(millis).get();
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+/// Returns the contents of a TMPlayer file holding the subtitles, without any formatting tags.
+/// Times are rounded to the nearest second, and a subtitle is followed by a line clearing the
+/// screen unless the next one starts at the same second it ends.
+pub fn format(subs: &Subtitles) -> String {
+    let mut res = String::new();
+    let subs: Vec<_> = subs.into_iter().collect();
+    for (i, sub) in subs.iter().enumerate() {
+        res.push_str(&format!(
+            "{}:{}\n",
+            timestamp(&sub.start_time),
+            sub.plain_text().replace('\n', "|")
+        ));
+        let end = timestamp(&sub.end_time);
+        if subs
+            .get(i + 1)
+            .is_none_or(|next| timestamp(&next.start_time) != end)
+        {
+            res.push_str(&format!("{}:\n", end));
+        }
+    }
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn variants() {
+        let input = "0:00:01=One\n00:00:03,1=Two\n00:00:03,2=lines\n00:00:05:Three\n00:01:00:\n";
+        let config = EndTimeConfig {
+            until_next: true,
+            ..Default::default()
+        };
+        let subs = parse(input, &config).unwrap();
+        assert_eq!(subs.len(), 3);
+        assert_eq!(subs[0].end_time, Timestamp::new(0, 0, 3, 0));
+        assert_eq!(subs[1].text, "Two\nlines");
+        assert_eq!(subs[2].end_time, Timestamp::new(0, 1, 0, 0));
+        assert!(matches!(
+            parse("00:00:01:One\n00:00:xx:Two", &config),
+            Err(ParsingError::BadSubtitleStructure(2))
+        ));
+
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,400 --> 00:00:02,600\n<i>Hi</i>\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nThere"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(format(&subs), "00:00:01:Hi\n00:00:03:There\n00:00:04:\n");
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::{ParsingError, Subtitles};
+use std::fs;
+use std::path::Path;
+
+/// What a subtitle format can hold, i.e. which properties of subtitles survive being written in
+/// it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Italic, bold or underlined text is kept.
+    pub styling: bool,
+    /// Subtitles placed at the top of the screen stay there.
+    pub positioning: bool,
+    /// Times are kept to the millisecond, instead of being rounded to frames or coarser units.
+    pub millisecond_precision: bool,
+    /// Files are binary rather than text, so encodings don't apply to them.
+    pub binary: bool,
+}
+
+/// The description of a subtitle format, shared by its [`SubtitleReader`] and
+/// [`SubtitleWriter`].
+///
+/// [`SubtitleReader`]: trait.SubtitleReader.html
+/// [`SubtitleWriter`]: trait.SubtitleWriter.html
+pub trait SubtitleFormat {
+    /// Returns the name of the format, e.g. "SubRip".
+    fn name(&self) -> &str;
+
+    /// Returns the file extensions used by the format, without the leading dot, starting with the
+    /// most common one.
+    fn extensions(&self) -> &[&str];
+
+    /// Returns what the format can hold.
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// A way to read subtitle files of a format, which may be added to a [`Registry`] next to the
+/// built-in formats.
+///
+/// # Examples
+///
+/// A format where every line holds the start and end time in milliseconds and the text of a
+/// subtitle:
+/// ```
+/// use srtlib::formats::{Capabilities, Registry, SubtitleFormat, SubtitleReader};
+/// use srtlib::{ParsingError, Subtitle, Subtitles, Timestamp};
+///
+/// struct Cap;
+///
+/// impl SubtitleFormat for Cap {
+///     fn name(&self) -> &str {
+///         "Cap"
+///     }
+///
+///     fn extensions(&self) -> &[&str] {
+///         &["cap"]
+///     }
+///
+///     fn capabilities(&self) -> Capabilities {
+///         Capabilities { millisecond_precision: true, ..Default::default() }
+///     }
+/// }
+///
+/// impl SubtitleReader for Cap {
+///     fn detect(&self, content: &[u8]) -> u8 {
+///         if content.starts_with(b"#CAP") { 100 } else { 0 }
+///     }
+///
+///     fn read(&self, content: &[u8], _encoding: Option<&str>) -> Result<Subtitles, ParsingError> {
+///         let mut subs = Subtitles::new();
+///         for line in String::from_utf8_lossy(content).lines().skip(1) {
+///             let mut parts = line.splitn(3, ' ');
+///             let mut time = || -> Result<Timestamp, ParsingError> {
+///                 let millis = parts.next().ok_or(ParsingError::BadSubtitleStructure(subs.len() + 1))?;
+///                 Ok(Timestamp::from_milliseconds(millis.parse()?))
+///             };
+///             let (start, end) = (time()?, time()?);
+///             let text = parts.next().unwrap_or_default().to_string();
+///             subs.push(Subtitle::new(subs.len() + 1, start, end, text));
+///         }
+///         Ok(subs)
+///     }
+/// }
+///
+/// let registry = Registry::new().with_reader(Cap);
+/// let content = b"#CAP\n1000 2500 Hello";
+/// let reader = registry.detect(content, None).unwrap();
+/// assert_eq!(reader.name(), "Cap");
+/// let subs = reader.read(content, None).unwrap();
+/// assert_eq!(subs.to_string(), "1\n00:00:01,000 --> 00:00:02,500\nHello");
+/// ```
+///
+/// [`Registry`]: struct.Registry.html
+pub trait SubtitleReader: SubtitleFormat {
+    /// Returns how confident the reader is that the contents of a file are in its format, from 0
+    /// for not at all to 100 for certain.
+    ///
+    /// The built-in formats return 100 for a signature such as a header, 75 for a recognizable
+    /// first subtitle and 50 for looser matches.
+    fn detect(&self, content: &[u8]) -> u8;
+
+    /// Constructs a new Subtitles collection by parsing the contents of a file in the format.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8.
+    fn read(&self, content: &[u8], encoding: Option<&str>) -> Result<Subtitles, ParsingError>;
+
+    /// Constructs a new Subtitles collection by parsing a file in the format.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read, an IOError error variant will be returned. Otherwise see
+    /// [`read`](#tymethod.read).
+    fn load(&self, path: &Path, encoding: Option<&str>) -> Result<Subtitles, ParsingError> {
+        self.read(&fs::read(path)?, encoding)
+    }
+}
+
+/// A way to write subtitle files of a format, which may be added to a [`Registry`] next to the
+/// built-in formats.
+///
+/// [`Registry`]: struct.Registry.html
+pub trait SubtitleWriter: SubtitleFormat {
+    /// Returns the contents of a file in the format holding the subtitles.
+    ///
+    /// **encoding** should either be Some("encoding-name") or None if using utf-8.
+    fn write(&self, subs: &Subtitles, encoding: Option<&str>) -> Result<Vec<u8>, ParsingError>;
+
+    /// Writes the subtitles to a file in the format.
+    ///
+    /// # Errors
+    ///
+    /// If something goes wrong during the creation of the file using the specified path, an
+    /// IOError error variant will be returned. Otherwise see [`write`](#tymethod.write).
+    fn save(
+        &self,
+        subs: &Subtitles,
+        path: &Path,
+        encoding: Option<&str>,
+    ) -> Result<(), ParsingError> {
+        fs::write(path, self.write(subs, encoding)?)?;
+        Ok(())
+    }
+}
+
+/// Returns the reader most confident that the contents of a file are in its format, preferring
+/// readers using its extension and then the ones that come last. If no reader recognizes the
+/// contents, the only reader using the extension is returned, if any.
+pub(crate) fn best_reader<'a, R: SubtitleReader + ?Sized>(
+    readers: impl IntoIterator<Item = &'a R>,
+    content: &[u8],
+    extension: Option<&str>,
+) -> Option<&'a R> {
+    let extension = extension.map(|ext| ext.trim_start_matches('.'));
+    let uses_extension = |reader: &R| {
+        extension.is_some_and(|ext| {
+            reader
+                .extensions()
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(ext))
+        })
+    };
+    let readers: Vec<&R> = readers.into_iter().collect();
+    let detected = readers
+        .iter()
+        .map(|reader| (*reader, reader.detect(content)))
+        .filter(|(_, score)| *score > 0)
+        .max_by_key(|(reader, score)| (*score, uses_extension(reader)));
+    if let Some((reader, _)) = detected {
+        return Some(reader);
+    }
+    let mut matching = readers.into_iter().filter(|reader| uses_extension(reader));
+    match (matching.next(), matching.next()) {
+        (Some(reader), None) => Some(reader),
+        _ => None,
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Timed Text Markup Language (TTML), including DFXP and the IMSC1 text profile.
+//!
+//! This module is only available with the `ttml` feature enabled.
+//!
+//! Every `<p>` element becomes a subtitle. Clock times ("00:00:01.500" or "00:00:01:12" with
+//! frames), offset times ("1.5s", "36f", "900ms", ...) and timing relative to the enclosing
+//! `<body>` and `<div>` elements are supported, using the `ttp:frameRate`,
+//! `ttp:frameRateMultiplier` and `ttp:tickRate` parameters of the document. Italic, bold and
+//! underlined text, set directly or through referenced styles, is mapped to .srt markup, and
+//! subtitles in a region in the upper half of the screen get an `{\an8}` tag.
+//!
+//! Written documents follow the IMSC1 text profile, with a region for the top and one for the
+//! bottom of the screen.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::formats::ttml;
+//!
+//! let subs = ttml::parse(r#"<tt xmlns="http://www.w3.org/ns/ttml"
+//!                               xmlns:tts="http://www.w3.org/ns/ttml#styling"
+//!                               xmlns:ttp="http://www.w3.org/ns/ttml#parameter" ttp:frameRate="25">
+//!   <body><div>
+//!     <p begin="00:00:01:05" end="3s">Hello<br/><span tts:fontStyle="italic">world</span></p>
+//!   </div></body>
+//! </tt>"#).unwrap();
+//! assert_eq!(subs.to_string(), "1\n00:00:01,200 --> 00:00:03,000\nHello\n<i>world</i>");
+//!
+//! let document = ttml::format(&subs, "en");
+//! assert_eq!(ttml::parse(&document).unwrap(), subs);
+//! ```
+
+use super::{is_top_aligned, styled_lines, to_markup, Style, StyledLine};
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+
+/// Returns the value of an attribute by its local name, ignoring its namespace. Older DFXP
+/// documents use different namespaces for the same attributes.
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name)
+        .map(|a| a.value())
+}
+
+/// Returns the ID of an element.
+fn id<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    attr(node, "id")
+}
+
+fn malformed(reason: String) -> ParsingError {
+    ParsingError::MalformedFile(reason)
+}
+
+/// The parameters needed to interpret time expressions.
+struct Timing {
+    frame_rate: f64,
+    tick_rate: f64,
+}
+
+impl Timing {
+    fn from_root(root: Node) -> Timing {
+        let frame_rate = attr(root, "frameRate").and_then(|r| r.trim().parse::<f64>().ok());
+        let multiplier = attr(root, "frameRateMultiplier")
+            .and_then(|m| {
+                let (num, den) = m.split_once(' ')?;
+                Some(num.trim().parse::<f64>().ok()? / den.trim().parse::<f64>().ok()?)
+            })
+            .unwrap_or(1.0);
+        let sub_frame_rate = attr(root, "subFrameRate")
+            .and_then(|r| r.trim().parse::<f64>().ok())
+            .unwrap_or(1.0);
+        let tick_rate = attr(root, "tickRate")
+            .and_then(|r| r.trim().parse::<f64>().ok())
+            .or(frame_rate.map(|r| r * sub_frame_rate))
+            .unwrap_or(1.0);
+        Timing {
+            frame_rate: frame_rate.unwrap_or(30.0) * multiplier,
+            tick_rate,
+        }
+    }
+
+    /// Parses a clock time or an offset time into milliseconds.
+    fn parse(&self, expr: &str) -> Result<f64, ParsingError> {
+        let expr = expr.trim();
+        let invalid = || malformed(format!("invalid time expression {}", expr));
+        let number = |s: &str| s.parse::<f64>().map_err(|_| invalid());
+        if expr.contains(':') {
+            let parts: Vec<&str> = expr.split(':').collect();
+            let (hours, minutes) = match parts[..] {
+                [h, m, _] | [h, m, _, _] => (number(h)?, number(m)?),
+                _ => return Err(invalid()),
+            };
+            let mut seconds = hours * 3600.0 + minutes * 60.0 + number(parts[2])?;
+            if let Some(frames) = parts.get(3) {
+                // frames may be followed by sub-frames, which are ignored
+                let frames = frames.split('.').next().unwrap_or_default();
+                seconds += number(frames)? / self.frame_rate;
+            }
+            return Ok(seconds * 1000.0);
+        }
+        let metric_start = expr
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let value = number(&expr[..metric_start])?;
+        let millis = match &expr[metric_start..] {
+            "h" => value * 3_600_000.0,
+            "m" => value * 60_000.0,
+            "s" => value * 1000.0,
+            "ms" => value,
+            "f" => value * 1000.0 / self.frame_rate,
+            "t" => value * 1000.0 / self.tick_rate,
+            _ => return Err(invalid()),
+        };
+        Ok(millis)
+    }
+}
+
+/// Converts milliseconds to a Timestamp.
+fn to_timestamp(millis: f64) -> Result<Timestamp, ParsingError> {
+    if !(0.0..=Timestamp::MAX_TIMESTAMP_MILLIS as f64).contains(&millis) {
+        return Err(malformed(format!("time out of range ({} ms)", millis)));
+    }
+    Ok(Timestamp::from_milliseconds(millis.round() as u32))
+}
+
+/// The elements of the document referenced by their ID.
+struct Context<'a, 'input> {
+    timing: Timing,
+    styles: HashMap<&'a str, Node<'a, 'input>>,
+    regions: HashMap<&'a str, Node<'a, 'input>>,
+}
+
+impl<'a, 'input> Context<'a, 'input> {
+    /// Returns the value of a styling attribute of an element, either set directly or through
+    /// the styles it references.
+    fn style_attr(&self, node: Node<'a, 'input>, name: &str, depth: usize) -> Option<&'a str> {
+        if let Some(value) = attr(node, name) {
+            return Some(value);
+        }
+        if depth > 8 {
+            return None;
+        }
+        attr(node, "style")?
+            .split_whitespace()
+            .filter_map(|id| self.styles.get(id))
+            .find_map(|style| self.style_attr(*style, name, depth + 1))
+    }
+
+    /// Applies the text styles of an element.
+    fn apply_style(&self, mut style: Style, node: Node<'a, 'input>) -> Style {
+        if let Some(value) = self.style_attr(node, "fontStyle", 0) {
+            style.italic = matches!(value, "italic" | "oblique");
+        }
+        if let Some(value) = self.style_attr(node, "fontWeight", 0) {
+            style.bold = value == "bold";
+        }
+        if let Some(value) = self.style_attr(node, "textDecoration", 0) {
+            if value.contains("noUnderline") || value == "none" {
+                style.underline = false;
+            } else if value.contains("underline") {
+                style.underline = true;
+            }
+        }
+        style
+    }
+
+    /// Checks if the region of a paragraph lies in the upper half of the screen.
+    fn is_top_region(&self, p: Node<'a, 'input>) -> bool {
+        let region = match p
+            .ancestors()
+            .find_map(|n| attr(n, "region"))
+            .and_then(|id| self.regions.get(id))
+        {
+            Some(region) => *region,
+            None => return false,
+        };
+        let percentages = |name: &str| -> Option<(f64, f64)> {
+            let value = self.style_attr(region, name, 0)?;
+            let mut values = value
+                .split_whitespace()
+                .map(|v| v.strip_suffix('%').and_then(|v| v.parse::<f64>().ok()));
+            Some((values.next()??, values.next()??))
+        };
+        let origin = percentages("origin");
+        let align = self.style_attr(region, "displayAlign", 0);
+        if origin.is_none() && align.is_none() {
+            return false;
+        }
+        let top = origin.map_or(0.0, |(_, y)| y);
+        let height = percentages("extent").map_or(100.0 - top, |(_, h)| h);
+        let position = match align {
+            Some("after") => top + height,
+            Some("center") => top + height / 2.0,
+            _ => top,
+        };
+        position < 50.0
+    }
+
+    /// Collects the text of an element into styled lines.
+    fn collect_text(&self, node: Node<'a, 'input>, style: Style, lines: &mut Vec<StyledLine>) {
+        for child in node.children() {
+            if child.is_text() {
+                let text = child.text().unwrap_or_default();
+                let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if text.starts_with(char::is_whitespace) {
+                    collapsed.insert(0, ' ');
+                }
+                if text.len() > 1 && text.ends_with(char::is_whitespace) {
+                    collapsed.push(' ');
+                }
+                let line = lines.last_mut().expect("there is always a line");
+                let at_space = line
+                    .last()
+                    .is_none_or(|(_, run)| run.is_empty() || run.ends_with(' '));
+                if at_space {
+                    collapsed = collapsed.trim_start().to_string();
+                }
+                line.push((style, collapsed));
+            } else if child.is_element() {
+                match child.tag_name().name() {
+                    "br" => lines.push(StyledLine::new()),
+                    _ => self.collect_text(child, self.apply_style(style, child), lines),
+                }
+            }
+        }
+    }
+
+    /// Constructs a subtitle from a paragraph.
+    fn subtitle(&self, num: usize, p: Node<'a, 'input>) -> Result<Subtitle, ParsingError> {
+        let mut offset = 0.0;
+        for ancestor in p.ancestors().skip(1) {
+            if let Some(begin) = attr(ancestor, "begin") {
+                offset += self.timing.parse(begin)?;
+            }
+        }
+        let begin = match attr(p, "begin") {
+            Some(begin) => offset + self.timing.parse(begin)?,
+            None => offset,
+        };
+        let end = match (attr(p, "end"), attr(p, "dur")) {
+            (Some(end), _) => offset + self.timing.parse(end)?,
+            (None, Some(dur)) => begin + self.timing.parse(dur)?,
+            (None, None) => return Err(malformed(format!("paragraph {} without an end", num))),
+        };
+
+        let mut style = Style::default();
+        let mut ancestors: Vec<Node> = p.ancestors().collect();
+        ancestors.reverse();
+        for node in ancestors {
+            style = self.apply_style(style, node);
+        }
+        let mut lines = vec![StyledLine::new()];
+        self.collect_text(p, style, &mut lines);
+        for line in &mut lines {
+            while let Some((_, run)) = line.last_mut() {
+                let trimmed = run.trim_end().len();
+                run.truncate(trimmed);
+                if !run.is_empty() {
+                    break;
+                }
+                line.pop();
+            }
+        }
+
+        let mut text = to_markup(&lines);
+        if self.is_top_region(p) {
+            text.insert_str(0, "{\\an8}");
+        }
+        Ok(Subtitle::new(
+            num,
+            to_timestamp(begin)?,
+            to_timestamp(end)?,
+            text,
+        ))
+    }
+}
+
+/// Constructs a new Subtitles collection by parsing a TTML document.
+///
+/// # Errors
+///
+/// If the document is not well-formed XML, has no `<tt>` root element, or contains a paragraph
+/// with invalid or missing timing, a MalformedFile error variant will be returned.
+pub fn parse(input: &str) -> Result<Subtitles, ParsingError> {
+    let doc = Document::parse(input).map_err(|e| malformed(e.to_string()))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "tt" {
+        return Err(malformed("missing tt root element".to_string()));
+    }
+    let elements = |name: &'static str| {
+        doc.descendants()
+            .filter(move |n| n.is_element() && n.tag_name().name() == name)
+            .filter_map(|n| Some((id(n)?, n)))
+            .collect()
+    };
+    let context = Context {
+        timing: Timing::from_root(root),
+        styles: elements("style"),
+        regions: elements("region"),
+    };
+
+    let mut subs = Subtitles::new();
+    for p in root
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == "p")
+    {
+        subs.push(context.subtitle(subs.len() + 1, p)?);
+    }
+    Ok(subs)
+}
+
+/// Escapes the characters with a special meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats a timestamp as a TTML clock time, e.g. "00:01:02.500".
+fn clock_time(time: &Timestamp) -> String {
+    time.to_string().replace(',', ".")
+}
+
+/// Returns the subtitles as a TTML document following the IMSC1 text profile, in the language
+/// with the given BCP 47 code (e.g. "en"). The code may be empty if the language is unknown.
+pub fn format(subs: &Subtitles, language: &str) -> String {
+    let mut res = String::new();
+    res.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    res.push_str(&format!(
+        "<tt xmlns=\"http://www.w3.org/ns/ttml\" \
+         xmlns:ttp=\"http://www.w3.org/ns/ttml#parameter\" \
+         xmlns:tts=\"http://www.w3.org/ns/ttml#styling\" \
+         ttp:timeBase=\"media\" \
+         ttp:contentProfiles=\"http://www.w3.org/ns/ttml/profile/imsc1/text\" \
+         xml:lang=\"{}\">\n",
+        escape(language)
+    ));
+    res.push_str("  <head>\n    <layout>\n");
+    for (region, align) in [("top", "before"), ("bottom", "after")] {
+        res.push_str(&format!(
+            "      <region xml:id=\"{}\" tts:origin=\"10% 10%\" tts:extent=\"80% 80%\" \
+             tts:displayAlign=\"{}\" tts:textAlign=\"center\"/>\n",
+            region, align
+        ));
+    }
+    res.push_str("    </layout>\n  </head>\n  <body>\n    <div>\n");
+    for sub in subs {
+        let lines: Vec<String> = styled_lines(&sub.text)
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|(style, run)| {
+                        let mut attrs = String::new();
+                        if style.italic {
+                            attrs.push_str(" tts:fontStyle=\"italic\"");
+                        }
+                        if style.bold {
+                            attrs.push_str(" tts:fontWeight=\"bold\"");
+                        }
+                        if style.underline {
+                            attrs.push_str(" tts:textDecoration=\"underline\"");
+                        }
+                        if attrs.is_empty() {
+                            escape(run)
+                        } else {
+                            format!("<span{}>{}</span>", attrs, escape(run))
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        res.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\" region=\"{}\">{}</p>\n",
+            clock_time(&sub.start_time),
+            clock_time(&sub.end_time),
+            if is_top_aligned(&sub.text) {
+                "top"
+            } else {
+                "bottom"
+            },
+            lines.join("<br/>")
+        ));
+    }
+    res.push_str("    </div>\n  </body>\n</tt>\n");
+    res
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn timing_and_styles() {
+        let subs = parse(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<tt xmlns="http://www.w3.org/2006/10/ttaf1" xmlns:tts="http://www.w3.org/2006/10/ttaf1#styling"
+    xmlns:ttp="http://www.w3.org/2006/10/ttaf1#parameter"
+    ttp:frameRate="24" ttp:frameRateMultiplier="1000 1001" ttp:tickRate="10000000">
+  <head>
+    <styling>
+      <style xml:id="base" tts:fontWeight="bold"/>
+      <style xml:id="emphasis" style="base" tts:fontStyle="italic"/>
+    </styling>
+    <layout>
+      <region xml:id="r1" tts:origin="0% 5%" tts:extent="100% 15%"/>
+    </layout>
+  </head>
+  <body begin="10s">
+    <div begin="1m">
+      <p begin="24f" dur="5000000t" region="r1">
+        Top   <span style="emphasis">and</span>
+        <br/> styled
+      </p>
+      <p begin="00:00:02:12" end="3.5s"><span tts:textDecoration="underline">Under</span>line</p>
+    </div>
+  </body>
+</tt>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            subs.to_string(),
+            "1\n00:01:11,001 --> 00:01:11,501\n{\\an8}Top <i><b>and</b></i>\nstyled\n\n\
+             2\n00:01:12,501 --> 00:01:13,500\n<u>Under</u>line"
+        );
+        assert!(parse("<tt><body><p begin=\"1s\">No end</p></body></tt>").is_err());
+        assert!(parse("<html/>").is_err());
+    }
+
+    #[test]
+    fn format_round_trip() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n{\\an8}<i>Fish & chips</i>\nare <b>\"good\"</b>\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\n<u>Bottom</u>"
+                .to_string(),
+        )
+        .unwrap();
+        let document = format(&subs, "en-GB");
+        assert!(document.contains(
+            "<p begin=\"00:00:01.000\" end=\"00:00:02.000\" region=\"top\">\
+             <span tts:fontStyle=\"italic\">Fish &amp; chips</span><br/>are \
+             <span tts:fontWeight=\"bold\">&quot;good&quot;</span></p>"
+        ));
+        assert_eq!(parse(&document).unwrap(), subs);
+    }
+}
@@ -0,0 +1,517 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Editing sessions with undo and redo support.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::history::EditSession;
+//!
+//! let subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nHello".to_string()).unwrap();
+//! let mut session = EditSession::new(subs);
+//!
+//! session.begin_transaction();
+//! session.set_text(0, "Hello world".to_string());
+//! session.shift(0..1, 500);
+//! session.end_transaction();
+//! assert_eq!(session.subtitles().to_string(), "1\n00:00:01,500 --> 00:00:02,500\nHello world");
+//!
+//! // Both edits of the transaction are undone at once.
+//! session.undo();
+//! assert_eq!(session.subtitles().to_string(), "1\n00:00:01,000 --> 00:00:02,000\nHello");
+//!
+//! session.redo();
+//! assert_eq!(session.subtitles()[0].text, "Hello world");
+//! ```
+
+use crate::words::Word;
+use crate::{Subtitle, Subtitles, Timestamp};
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::Range;
+
+/// A reversible change to a subtitle collection.
+#[derive(Debug, Clone)]
+enum Operation {
+    /// Moves the subtitles in the range by the given amount of milliseconds.
+    Shift { range: Range<usize>, millis: i64 },
+    /// Replaces the subtitles removed from the given index with the inserted ones.
+    Splice {
+        index: usize,
+        removed: Vec<Subtitle>,
+        inserted: Vec<Subtitle>,
+    },
+}
+
+impl Operation {
+    fn apply(&self, subs: &mut Subtitles) {
+        match self {
+            Operation::Shift { range, millis } => {
+                for sub in &mut subs.0[range.clone()] {
+                    sub.add_milliseconds(*millis);
+                }
+            }
+            Operation::Splice {
+                index,
+                removed,
+                inserted,
+            } => {
+                subs.0
+                    .splice(*index..*index + removed.len(), inserted.iter().cloned());
+            }
+        }
+    }
+
+    /// Returns an estimate of the memory held by the operation, in bytes.
+    fn size(&self) -> usize {
+        let subtitles = |subs: &[Subtitle]| -> usize {
+            subs.iter()
+                .map(|sub| {
+                    let words: usize = sub
+                        .words
+                        .iter()
+                        .map(|w| mem::size_of::<Word>() + w.text.len())
+                        .sum();
+                    mem::size_of::<Subtitle>() + sub.text.len() + words
+                })
+                .sum()
+        };
+        mem::size_of::<Operation>()
+            + match self {
+                Operation::Shift { .. } => 0,
+                Operation::Splice {
+                    removed, inserted, ..
+                } => subtitles(removed) + subtitles(inserted),
+            }
+    }
+
+    fn inverse(&self) -> Operation {
+        match self {
+            Operation::Shift { range, millis } => Operation::Shift {
+                range: range.clone(),
+                millis: -millis,
+            },
+            Operation::Splice {
+                index,
+                removed,
+                inserted,
+            } => Operation::Splice {
+                index: *index,
+                removed: inserted.clone(),
+                inserted: removed.clone(),
+            },
+        }
+    }
+}
+
+/// A [`Subtitles`] collection together with a history of the edits performed on it.
+///
+/// Every edit method records a reversible operation, so that it can be undone and redone. Edits
+/// may be grouped into transactions, which are undone and redone as a single step. Only the
+/// most recent steps are kept, up to the history limit and the memory limit of the session,
+/// although the most recent step is always kept. The memory used by a step is estimated from the
+/// subtitles it holds, since edits such as deleting a range of subtitles store a copy of them.
+///
+/// Subtitle numbers are never changed by the session, except for the second half of a split
+/// subtitle which is numbered right after the first one.
+///
+/// [`Subtitles`]: ../struct.Subtitles.html
+#[derive(Debug, Clone)]
+pub struct EditSession {
+    subs: Subtitles,
+    undo_stack: VecDeque<Vec<Operation>>,
+    redo_stack: Vec<Vec<Operation>>,
+    transaction: Option<Vec<Operation>>,
+    transaction_depth: usize,
+    history_limit: usize,
+    memory_limit: usize,
+    history_size: usize,
+}
+
+impl EditSession {
+    /// The number of undo steps kept by sessions constructed with [`new`](#method.new).
+    pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+    /// The estimated number of bytes the undo steps of a session may hold, unless constructed
+    /// with [`with_limits`](#method.with_limits).
+    pub const DEFAULT_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+    /// Constructs a new EditSession around a Subtitles collection.
+    pub fn new(subs: Subtitles) -> EditSession {
+        EditSession::with_history_limit(subs, Self::DEFAULT_HISTORY_LIMIT)
+    }
+
+    /// Constructs a new EditSession that keeps at most **limit** undo steps.
+    pub fn with_history_limit(subs: Subtitles, limit: usize) -> EditSession {
+        EditSession::with_limits(subs, limit, Self::DEFAULT_MEMORY_LIMIT)
+    }
+
+    /// Constructs a new EditSession that keeps at most **steps** undo steps, holding an estimated
+    /// total of at most **bytes** bytes.
+    pub fn with_limits(subs: Subtitles, steps: usize, bytes: usize) -> EditSession {
+        EditSession {
+            subs,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            transaction: None,
+            transaction_depth: 0,
+            history_limit: steps,
+            memory_limit: bytes,
+            history_size: 0,
+        }
+    }
+
+    /// Returns an estimate of the memory held by the undo steps, in bytes.
+    pub fn history_size(&self) -> usize {
+        self.history_size
+    }
+
+    /// Returns the current state of the subtitles.
+    pub fn subtitles(&self) -> &Subtitles {
+        &self.subs
+    }
+
+    /// Ends the session, returning the edited subtitles.
+    pub fn into_subtitles(self) -> Subtitles {
+        self.subs
+    }
+
+    /// Applies an operation and records it in the history.
+    fn perform(&mut self, op: Operation) {
+        op.apply(&mut self.subs);
+        self.redo_stack.clear();
+        match self.transaction.as_mut() {
+            Some(ops) => ops.push(op),
+            None => self.record(vec![op]),
+        }
+    }
+
+    /// Pushes a step to the undo stack, dropping the oldest steps beyond the history limit and
+    /// the memory limit.
+    fn record(&mut self, step: Vec<Operation>) {
+        self.history_size += step.iter().map(Operation::size).sum::<usize>();
+        self.undo_stack.push_back(step);
+        while self.undo_stack.len() > self.history_limit
+            || (self.history_size > self.memory_limit && self.undo_stack.len() > 1)
+        {
+            self.pop_undo_front();
+        }
+    }
+
+    /// Drops the oldest undo step.
+    fn pop_undo_front(&mut self) {
+        if let Some(step) = self.undo_stack.pop_front() {
+            self.history_size -= step.iter().map(Operation::size).sum::<usize>();
+        }
+    }
+
+    /// Ends any open transaction, however deeply nested.
+    fn close_transaction(&mut self) {
+        if self.transaction_depth > 0 {
+            self.transaction_depth = 1;
+            self.end_transaction();
+        }
+    }
+
+    /// Starts a transaction. All edits until the matching call to
+    /// [`end_transaction`](#method.end_transaction) are undone and redone as a single step.
+    ///
+    /// Transactions may be nested, in which case only the outermost one is recorded.
+    pub fn begin_transaction(&mut self) {
+        if self.transaction_depth == 0 {
+            self.transaction = Some(Vec::new());
+        }
+        self.transaction_depth += 1;
+    }
+
+    /// Ends the current transaction. Does nothing if no transaction was started.
+    pub fn end_transaction(&mut self) {
+        if self.transaction_depth == 0 {
+            return;
+        }
+        self.transaction_depth -= 1;
+        if self.transaction_depth == 0 {
+            if let Some(ops) = self.transaction.take() {
+                if !ops.is_empty() {
+                    self.record(ops);
+                }
+            }
+        }
+    }
+
+    /// Checks if there are any steps that can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Checks if there are any steps that can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Undoes the most recent step, returning false if there was nothing to undo.
+    ///
+    /// Any open transaction is ended first.
+    pub fn undo(&mut self) -> bool {
+        self.close_transaction();
+        match self.undo_stack.pop_back() {
+            Some(step) => {
+                self.history_size -= step.iter().map(Operation::size).sum::<usize>();
+                for op in step.iter().rev() {
+                    op.inverse().apply(&mut self.subs);
+                }
+                self.redo_stack.push(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redoes the most recently undone step, returning false if there was nothing to redo.
+    ///
+    /// Any open transaction is ended first.
+    pub fn redo(&mut self) -> bool {
+        self.close_transaction();
+        match self.redo_stack.pop() {
+            Some(step) => {
+                for op in &step {
+                    op.apply(&mut self.subs);
+                }
+                self.record(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the subtitles in the given index range **millis** milliseconds forward in time.
+    /// Negative values may be provided in order to move the subtitles back in time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds, or if any timestamp, including the ones of timed
+    /// words, would exceed the upper limit or go below zero. Nothing is moved in that case.
+    pub fn shift(&mut self, range: Range<usize>, millis: i64) {
+        if !self.subs[range.clone()]
+            .iter()
+            .all(|sub| sub.can_shift(millis))
+        {
+            panic!("Surpassed limits of Timestamp!");
+        }
+        self.perform(Operation::Shift { range, millis });
+    }
+
+    /// Replaces the text of the subtitle at **index**.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is out of bounds.
+    pub fn set_text(&mut self, index: usize, text: String) {
+        let old = self.subs[index].clone();
+        let mut new = old.clone();
+        new.set_text(text);
+        self.perform(Operation::Splice {
+            index,
+            removed: vec![old],
+            inserted: vec![new],
+        });
+    }
+
+    /// Inserts a subtitle at **index**, shifting all subtitles after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is greater than the number of subtitles.
+    pub fn insert(&mut self, index: usize, sub: Subtitle) {
+        assert!(index <= self.subs.len(), "insertion index out of bounds");
+        self.perform(Operation::Splice {
+            index,
+            removed: Vec::new(),
+            inserted: vec![sub],
+        });
+    }
+
+    /// Removes the subtitle at **index**.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is out of bounds.
+    pub fn delete(&mut self, index: usize) {
+        let old = self.subs[index].clone();
+        self.perform(Operation::Splice {
+            index,
+            removed: vec![old],
+            inserted: Vec::new(),
+        });
+    }
+
+    /// Splits the subtitle at **index** in two at the given time.
+    ///
+    /// The text before byte offset **text_split** goes to the first subtitle and the rest to the
+    /// second one, with surrounding whitespace trimmed. Timed words are split in the same way, by
+    /// their start time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is out of bounds, if **time** is not strictly within the subtitle or if
+    /// **text_split** is not a char boundary of its text.
+    pub fn split(&mut self, index: usize, time: Timestamp, text_split: usize) {
+        let old = self.subs[index].clone();
+        assert!(
+            old.start_time < time && time < old.end_time,
+            "split time outside of subtitle"
+        );
+        let (first_text, second_text) = old.text.split_at(text_split);
+        let (first_words, second_words) = crate::words::split(&old.words, time);
+        let mut first = Subtitle::new(
+            old.num,
+            old.start_time,
+            time,
+            first_text.trim_end().to_string(),
+        );
+        let mut second = Subtitle::new(
+            old.num + 1,
+            time,
+            old.end_time,
+            second_text.trim_start().to_string(),
+        );
+        first.words = first_words;
+        second.words = second_words;
+        self.perform(Operation::Splice {
+            index,
+            removed: vec![old],
+            inserted: vec![first, second],
+        });
+    }
+
+    /// Merges the subtitle at **index** with the one following it. The merged subtitle keeps the
+    /// number and start time of the first subtitle and the end time of the second, while their
+    /// texts and timed words are joined.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no subtitle after **index**.
+    pub fn merge(&mut self, index: usize) {
+        let removed = self.subs[index..index + 2].to_vec();
+        let mut merged = Subtitle::new(
+            removed[0].num,
+            removed[0].start_time,
+            std::cmp::max(removed[0].end_time, removed[1].end_time),
+            format!("{}\n{}", removed[0].text, removed[1].text),
+        );
+        merged.words = [removed[0].words.as_slice(), &removed[1].words].concat();
+        self.perform(Operation::Splice {
+            index,
+            removed,
+            inserted: vec![merged],
+        });
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+    use super::*;
+
+    fn sample() -> Subtitles {
+        Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:03,000\nHello world\n\n\
+             2\n00:00:04,000 --> 00:00:05,000\nBye"
+                .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn undo_redo_every_operation() {
+        let mut session = EditSession::new(sample());
+        session.split(0, Timestamp::new(0, 0, 2, 0), 5);
+        assert_eq!(
+            session.subtitles().to_string(),
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n\
+             2\n00:00:02,000 --> 00:00:03,000\nworld\n\n\
+             2\n00:00:04,000 --> 00:00:05,000\nBye"
+        );
+        session.merge(1);
+        session.delete(0);
+        session.insert(
+            1,
+            Subtitle::new(
+                3,
+                Timestamp::new(0, 0, 6, 0),
+                Timestamp::new(0, 0, 7, 0),
+                "New".to_string(),
+            ),
+        );
+        session.shift(0..2, -1000);
+        session.set_text(1, "Newer".to_string());
+        let edited = session.subtitles().clone();
+
+        while session.undo() {}
+        assert_eq!(session.subtitles(), &sample());
+        while session.redo() {}
+        assert_eq!(session.subtitles(), &edited);
+    }
+
+    #[test]
+    fn history_limit() {
+        let mut session = EditSession::with_history_limit(sample(), 2);
+        for _ in 0..5 {
+            session.shift(0..1, 100);
+        }
+        assert!(session.undo());
+        assert!(session.undo());
+        assert!(!session.undo());
+        assert_eq!(
+            session.subtitles()[0].start_time,
+            Timestamp::new(0, 0, 1, 300)
+        );
+        assert!(session.can_redo());
+    }
+
+    #[test]
+    fn memory_limit() {
+        let mut session = EditSession::with_limits(sample(), 100, 1);
+        session.delete(0);
+        session.delete(0);
+        // only the most recent step is kept
+        assert!(session.undo());
+        assert!(!session.undo());
+        assert_eq!(session.history_size(), 0);
+        assert_eq!(session.subtitles().len(), 1);
+
+        let mut session = EditSession::new(sample());
+        session.begin_transaction();
+        session.set_text(0, "Hi".to_string());
+        assert!(session.undo());
+        session.begin_transaction();
+        assert!(session.redo());
+        assert_eq!(session.transaction_depth, 0);
+        assert!(session.history_size() > 0);
+    }
+
+    #[test]
+    fn shift_checks_words() {
+        let mut subs = sample();
+        // only the second subtitle can't be moved back, because of a word before it
+        for sub in (&mut subs).into_iter().skip(1) {
+            sub.set_words(vec![Word::new(
+                Timestamp::new(0, 0, 0, 500),
+                sub.end_time,
+                "early".to_string(),
+            )]);
+        }
+        let mut session = EditSession::new(subs.clone());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            session.shift(0..2, -600);
+        }));
+        assert!(result.is_err());
+        assert_eq!(session.subtitles(), &subs);
+        assert!(!session.can_undo());
+    }
+}
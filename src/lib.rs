@@ -79,12 +79,25 @@
 //!
 //! ```
 
-use encoding_rs::*;
 use std::fmt;
-use std::fs;
-use std::io::prelude::*;
 use std::ops::Index;
 use std::path::Path;
+
+pub mod dialogue;
+pub mod diff;
+pub mod fix;
+pub mod formats;
+pub mod history;
+pub mod lint;
+pub mod normalize;
+pub mod patch;
+pub mod rtl;
+pub mod sdh;
+#[cfg(feature = "regex")]
+pub mod search;
+pub mod segment;
+pub mod words;
+
 /// The number of milliseconds in a second.
 const ONE_SECOND_MILLIS: u32 = 1000;
 /// The number of milliseconds in a minute.
@@ -100,6 +113,10 @@ pub enum ParsingError {
     MalformedTimestamp,
     BadSubtitleStructure(usize),
     BadEncodingName,
+    MalformedPatch(usize),
+    MalformedConfig(String),
+    MalformedFile(String),
+    UnsupportedContent(String),
 }
 
 impl fmt::Display for ParsingError {
@@ -109,6 +126,10 @@ impl fmt::Display for ParsingError {
             ParsingError::IOError(error) => write!(f, "{}", error),
             ParsingError::MalformedTimestamp => write!(f, "tried parsing a malformed timestamp"),
             ParsingError::BadEncodingName => write!(f, "incorrect encoding name provided; refer to https://encoding.spec.whatwg.org/#names-and-labels for available encodings"),
+            ParsingError::MalformedPatch(line) => write!(f, "tried parsing a malformed patch (line {})", line),
+            ParsingError::MalformedConfig(reason) => write!(f, "tried parsing a malformed configuration: {}", reason),
+            ParsingError::MalformedFile(reason) => write!(f, "tried parsing a malformed subtitle file: {}", reason),
+            ParsingError::UnsupportedContent(reason) => write!(f, "tried writing subtitles the format can't hold: {}", reason),
             ParsingError::BadSubtitleStructure(num) => {
                 let number = if num > &0 { num.to_string() } else { String::from("unknown") }; 
                 write!(f, "tried parsing an incorrectly formatted subtitle (subtitle number {})", number)
@@ -186,6 +207,11 @@ impl Timestamp {
         }
     }
 
+    /// Returns the timestamp as a number of milliseconds.
+    pub fn to_milliseconds(&self) -> u32 {
+        self.milliseconds
+    }
+
     /// Constructs a new Timestamp by parsing a string with the format
     /// "hours:minutes:seconds,milliseconds".
     ///
@@ -310,9 +336,35 @@ impl fmt::Display for Timestamp {
     }
 }
 
+/// Returns the byte ranges of a text that lie outside of formatting tags such as `<i>` or `{\an8}`.
+/// Tags without a closing bracket are treated as regular text.
+pub(crate) fn unformatted_spans(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find(['<', '{']) {
+        let start = pos + offset;
+        let close = if text[start..].starts_with('<') {
+            '>'
+        } else {
+            '}'
+        };
+        match text[start..].find(close) {
+            Some(end) => {
+                spans.push(pos..start);
+                pos = start + end + 1;
+            }
+            None => break,
+        }
+    }
+    spans.push(pos..text.len());
+    spans.retain(|span| !span.is_empty());
+    spans
+}
+
 /// A single subtitle.
 ///
-/// Contains the numeric counter, the beginning and end timestamps and the text of the subtitle.
+/// Contains the numeric counter, the beginning and end timestamps and the text of the subtitle,
+/// along with optional word-level timing (see the [`words`] module).
 ///
 /// # Examples
 ///
@@ -326,12 +378,51 @@ impl fmt::Display for Timestamp {
 /// let sub = Subtitle::parse("2\n00:00:01,500 --> 00:00:02,500\nFooBar".to_string()).unwrap();
 /// assert_eq!(sub.text, "FooBar");
 /// ```
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// Subtitles are compared, ordered and hashed by their number, timestamps and text only, so two
+/// subtitles differing only in their word-level timing are equal.
+///
+/// [`words`]: words/index.html
+#[derive(Debug, Clone)]
 pub struct Subtitle {
     pub num: usize,
     pub start_time: Timestamp,
     pub end_time: Timestamp,
     pub text: String,
+    words: Vec<words::Word>,
+}
+
+impl Subtitle {
+    /// The fields subtitles are compared by.
+    fn key(&self) -> (usize, Timestamp, Timestamp, &str) {
+        (self.num, self.start_time, self.end_time, &self.text)
+    }
+}
+
+impl PartialEq for Subtitle {
+    fn eq(&self, other: &Subtitle) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Subtitle {}
+
+impl PartialOrd for Subtitle {
+    fn partial_cmp(&self, other: &Subtitle) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Subtitle {
+    fn cmp(&self, other: &Subtitle) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl std::hash::Hash for Subtitle {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
 }
 
 impl Subtitle {
@@ -342,6 +433,7 @@ impl Subtitle {
             start_time,
             end_time,
             text,
+            words: Vec::new(),
         }
     }
 
@@ -386,8 +478,7 @@ impl Subtitle {
     ///
     /// Panics if we exceed the upper limit or go below zero.
     pub fn add_hours(&mut self, n: i64) {
-        self.start_time.add_hours(n);
-        self.end_time.add_hours(n);
+        self.shift(n * ONE_HOUR_MILLIS as i64);
     }
 
     /// Moves the start and end timestamps n minutes forward in time.
@@ -397,8 +488,7 @@ impl Subtitle {
     ///
     /// Panics if we exceed the upper limit or go below zero.
     pub fn add_minutes(&mut self, n: i64) {
-        self.start_time.add_minutes(n);
-        self.end_time.add_minutes(n);
+        self.shift(n * ONE_MINUTE_MILLIS as i64);
     }
 
     /// Moves the start and end timestamps n seconds forward in time.
@@ -408,8 +498,7 @@ impl Subtitle {
     ///
     /// Panics if we exceed the upper limit or go below zero.
     pub fn add_seconds(&mut self, n: i64) {
-        self.start_time.add_seconds(n);
-        self.end_time.add_seconds(n);
+        self.shift(n * ONE_SECOND_MILLIS as i64);
     }
 
     /// Moves the start and end timestamps n milliseconds forward in time.
@@ -419,8 +508,7 @@ impl Subtitle {
     ///
     /// Panics if we exceed the upper limit or go below zero.
     pub fn add_milliseconds(&mut self, n: i64) {
-        self.start_time.add_milliseconds(n);
-        self.end_time.add_milliseconds(n);
+        self.shift(n);
     }
 
     /// Moves the start and end timestamps forward in time by an amount specified as timestamp.
@@ -429,8 +517,7 @@ impl Subtitle {
     ///     
     /// Panics if we exceed the upper limit
     pub fn add(&mut self, timestamp: &Timestamp) {
-        self.start_time.add(timestamp);
-        self.end_time.add(timestamp);
+        self.shift(timestamp.to_milliseconds() as i64);
     }
 
     /// Moves the start and end timestamps backward in time by an amount specified as timestamp.
@@ -439,8 +526,71 @@ impl Subtitle {
     ///
     /// Panics if we go below zero
     pub fn sub(&mut self, timestamp: &Timestamp) {
-        self.start_time.sub(timestamp);
-        self.end_time.sub(timestamp);
+        self.shift(-(timestamp.to_milliseconds() as i64));
+    }
+
+    /// Moves the start and end timestamps and the words n milliseconds forward in time, making
+    /// sure that all of them stay within the limits before moving any.
+    fn shift(&mut self, n: i64) {
+        if !self.can_shift(n) {
+            panic!("Surpassed limits of Timestamp!");
+        }
+        self.start_time.add_milliseconds(n);
+        self.end_time.add_milliseconds(n);
+        for word in &mut self.words {
+            word.start_time.add_milliseconds(n);
+            word.end_time.add_milliseconds(n);
+        }
+    }
+
+    /// Checks if the start and end timestamps and the words can be moved n milliseconds forward
+    /// in time without exceeding the upper limit or going below zero.
+    pub(crate) fn can_shift(&self, n: i64) -> bool {
+        [self.start_time, self.end_time]
+            .into_iter()
+            .chain(self.words.iter().flat_map(|w| [w.start_time, w.end_time]))
+            .all(|time| {
+                let millis = time.to_milliseconds() as i64 + n;
+                (0..=Timestamp::MAX_TIMESTAMP_MILLIS as i64).contains(&millis)
+            })
+    }
+
+    /// Returns the timing of the individual words of the text, if known. Empty for subtitles
+    /// parsed from .srt files, which cannot store it.
+    pub fn words(&self) -> &[words::Word] {
+        &self.words
+    }
+
+    /// Sets the timing of the individual words of the text.
+    pub fn set_words(&mut self, words: Vec<words::Word>) {
+        self.words = words;
+    }
+
+    /// Replaces the text of the subtitle. If the text changes, the timing of its words is
+    /// dropped, since it no longer matches the text.
+    pub fn set_text(&mut self, text: String) {
+        if text != self.text {
+            self.text = text;
+            self.words.clear();
+        }
+    }
+
+    /// Returns the text of the subtitle with any formatting tags (such as `<i>` or `{\an8}`)
+    /// removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::Subtitle;
+    ///
+    /// let sub = Subtitle::parse("1\n00:00:00,000 --> 00:00:01,000\n{\\an8}<i>Hello</i> world".to_string()).unwrap();
+    /// assert_eq!(sub.plain_text(), "Hello world");
+    /// ```
+    pub fn plain_text(&self) -> String {
+        unformatted_spans(&self.text)
+            .into_iter()
+            .map(|span| &self.text[span])
+            .collect()
     }
 }
 
@@ -517,7 +667,9 @@ impl Subtitles {
         Ok(res)
     }
 
-    /// Constructs a new Subtitles collection by parsing a .srt file.
+    /// Constructs a new Subtitles collection by parsing a subtitle file, detecting its format as
+    /// described in [`load`](#method.load). Files that don't resemble any other format are read
+    /// as .srt files.
     ///
     /// **encoding** should either be Some("encoding-name") or None if using utf-8.
     /// For example if the file is using the ISO-8859-7 encoding (informally referred to as
@@ -553,22 +705,13 @@ impl Subtitles {
         path: impl AsRef<Path>,
         encoding: Option<&str>,
     ) -> Result<Subtitles, ParsingError> {
-        let mut f = fs::File::open(path)?;
-        if let Some(enc) = encoding {
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer)?;
-            let (cow, ..) = Encoding::for_label(enc.as_bytes())
-                .ok_or(ParsingError::BadEncodingName)?
-                .decode(buffer.as_slice());
-            Subtitles::parse_from_str(cow[..].to_string())
-        } else {
-            let mut buffer = String::new();
-            f.read_to_string(&mut buffer)?;
-            Subtitles::parse_from_str(buffer)
-        }
+        Subtitles::load(path, encoding)
     }
 
-    /// Writes the contents of this Subtitles collection to a .srt file with the correct formatting.
+    /// Writes the contents of this Subtitles collection to a subtitle file with the correct
+    /// formatting, in the format its extension stands for. Extensions that are unknown or used by
+    /// several formats, like .txt, result in a .srt file; [`save`](#method.save) takes the format
+    /// explicitly.
     ///
     /// **encoding** should either be Some("encoding-name") or None if using utf-8.
     /// For example if the file is using the ISO-8859-7 encoding (informally referred to as
@@ -603,18 +746,8 @@ impl Subtitles {
         path: impl AsRef<Path>,
         encoding: Option<&str>,
     ) -> Result<(), ParsingError> {
-        let mut f = fs::File::create(path)?;
-        if let Some(enc) = encoding {
-            let string = &self.to_string();
-            let (cow, ..) = Encoding::for_label(enc.as_bytes())
-                .ok_or(ParsingError::BadEncodingName)?
-                .encode(string);
-            f.write_all(&cow)?;
-        } else {
-            f.write_all(self.to_string().as_bytes())?;
-        }
-
-        Ok(())
+        let format = formats::Format::from_path(&path).unwrap_or(formats::Format::Srt);
+        self.save(path, format, encoding)
     }
 
     /// Returns the Subtitles collection as a simple vector of [`Subtitle`] structs.
@@ -643,6 +776,192 @@ impl Subtitles {
     pub fn sort(&mut self) {
         self.0.sort();
     }
+
+    /// Renumbers the subtitles sequentially starting from 1, keeping their current order.
+    pub fn renumber(&mut self) {
+        for (i, sub) in self.0.iter_mut().enumerate() {
+            sub.num = i + 1;
+        }
+    }
+
+    /// Splits the collection in two at the given time.
+    ///
+    /// Subtitles starting before **time** end up in the first part, while the rest form the second
+    /// part, which is moved back in time by **time** so that it is relative to the start of the
+    /// second video file. Subtitles that start before **time** but end after it are kept whole in
+    /// the first part. Both parts are renumbered starting from 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::{Subtitles, Timestamp};
+    ///
+    /// let subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+    ///                                       2\n00:45:01,000 --> 00:45:03,000\nTwo".to_string()).unwrap();
+    /// let (cd1, cd2) = subs.split_at_time(Timestamp::new(0, 45, 0, 0));
+    /// assert_eq!(cd1.to_string(), "1\n00:00:01,000 --> 00:00:02,000\nOne");
+    /// assert_eq!(cd2.to_string(), "1\n00:00:01,000 --> 00:00:03,000\nTwo");
+    /// ```
+    pub fn split_at_time(self, time: Timestamp) -> (Subtitles, Subtitles) {
+        let (first, second): (Vec<Subtitle>, Vec<Subtitle>) =
+            self.0.into_iter().partition(|s| s.start_time < time);
+        let mut first = Subtitles(first);
+        let mut second = Subtitles(second);
+        for sub in &mut second {
+            sub.sub(&time);
+        }
+        first.renumber();
+        second.renumber();
+        (first, second)
+    }
+
+    /// Splits the collection in two at the given index.
+    ///
+    /// The subtitle at **index** becomes the first subtitle of the second part. The second part
+    /// is moved back in time so that its first subtitle starts at zero. Both parts are renumbered
+    /// starting from 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is greater than the number of subtitles in the collection.
+    pub fn split_at_index(mut self, index: usize) -> (Subtitles, Subtitles) {
+        let mut second = Subtitles(self.0.split_off(index));
+        if let Some(start) = second.0.iter().map(|s| s.start_time).min() {
+            for sub in &mut second {
+                sub.sub(&start);
+            }
+        }
+        self.renumber();
+        second.renumber();
+        (self, second)
+    }
+
+    /// Concatenates several Subtitles collections into one, moving each part forward in time by
+    /// its accompanying offset. The result is renumbered starting from 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any subtitle is moved beyond the upper limit of [`Timestamp`].
+    ///
+    /// [`Timestamp`]: struct.Timestamp.html
+    pub fn join(parts: Vec<(Subtitles, Timestamp)>) -> Subtitles {
+        let mut res = Subtitles::new();
+        for (part, offset) in parts {
+            for mut sub in part {
+                sub.add(&offset);
+                res.push(sub);
+            }
+        }
+        res.renumber();
+        res
+    }
+
+    /// Concatenates several Subtitles collections into one, where each part is accompanied by the
+    /// duration of the video file it belongs to. Every part is moved forward in time by the sum of
+    /// the durations of all the parts preceding it. The result is renumbered starting from 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::{Subtitles, Timestamp};
+    ///
+    /// let cd1 = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nOne".to_string()).unwrap();
+    /// let cd2 = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:03,000\nTwo".to_string()).unwrap();
+    /// let subs = Subtitles::join_by_durations(vec![
+    ///     (cd1, Timestamp::new(0, 45, 0, 0)),
+    ///     (cd2, Timestamp::new(0, 50, 0, 0)),
+    /// ]);
+    /// assert_eq!(subs[1].to_string(), "2\n00:45:01,000 --> 00:45:03,000\nTwo");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if any subtitle is moved beyond the upper limit of [`Timestamp`].
+    ///
+    /// [`Timestamp`]: struct.Timestamp.html
+    pub fn join_by_durations(parts: Vec<(Subtitles, Timestamp)>) -> Subtitles {
+        let mut offset = Timestamp::from_milliseconds(0);
+        let mut with_offsets = Vec::with_capacity(parts.len());
+        for (part, duration) in parts {
+            with_offsets.push((part, offset));
+            offset.add(&duration);
+        }
+        Subtitles::join(with_offsets)
+    }
+
+    /// Returns the subtitles that appear within the time window from **start** to **end**,
+    /// moved back in time so that the window starts at zero.
+    ///
+    /// Subtitles that are only partially inside the window are clipped to its edges, while
+    /// subtitles entirely outside of it are dropped. The result is renumbered starting from 1.
+    /// If **end** is not after **start** an empty collection is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use srtlib::{Subtitles, Timestamp};
+    ///
+    /// let subs = Subtitles::parse_from_str("1\n00:09:59,000 --> 00:10:02,000\nOne\n\n\
+    ///                                       2\n00:12:00,000 --> 00:12:01,000\nTwo\n\n\
+    ///                                       3\n00:13:00,000 --> 00:13:01,000\nThree".to_string()).unwrap();
+    /// let clip = subs.extract_window(Timestamp::new(0, 10, 0, 0), Timestamp::new(0, 12, 30, 0));
+    /// assert_eq!(clip.to_string(), "1\n00:00:00,000 --> 00:00:02,000\nOne\n\n\
+    ///                               2\n00:02:00,000 --> 00:02:01,000\nTwo");
+    /// ```
+    pub fn extract_window(&self, start: Timestamp, end: Timestamp) -> Subtitles {
+        if end <= start {
+            return Subtitles::new();
+        }
+        let mut res = Subtitles::new();
+        for sub in &self.0 {
+            if sub.end_time <= start || sub.start_time >= end {
+                continue;
+            }
+            let mut clipped = sub.clone();
+            clipped.start_time = std::cmp::max(sub.start_time, start);
+            clipped.end_time = std::cmp::min(sub.end_time, end);
+            clipped.words = words::clip(&sub.words, start, end);
+            clipped.sub(&start);
+            res.push(clipped);
+        }
+        res.renumber();
+        res
+    }
+
+    /// Removes the section of time from **start** to **end** and closes the resulting gap.
+    ///
+    /// Subtitles entirely inside the section are removed, subtitles partially inside it are
+    /// shortened and subtitles after it are moved back in time by the length of the section. The
+    /// collection is renumbered starting from 1. If **end** is not after **start** nothing is
+    /// changed.
+    pub fn remove_section(&mut self, start: Timestamp, end: Timestamp) {
+        if end <= start {
+            return;
+        }
+        let length = end.milliseconds - start.milliseconds;
+        let mut res = Vec::with_capacity(self.len());
+        for mut sub in self.0.drain(..) {
+            if sub.end_time <= start {
+                res.push(sub);
+            } else if sub.start_time >= end {
+                sub.add_milliseconds(-(length as i64));
+                res.push(sub);
+            } else {
+                sub.start_time = std::cmp::min(sub.start_time, start);
+                sub.end_time = if sub.end_time > end {
+                    Timestamp::from_milliseconds(sub.end_time.milliseconds - length)
+                } else {
+                    start
+                };
+                sub.words = words::remove_section(&sub.words, start, end);
+                if sub.start_time < sub.end_time {
+                    res.push(sub);
+                }
+            }
+        }
+        self.0 = res;
+        self.renumber();
+    }
 }
 
 impl IntoIterator for Subtitles {
@@ -945,4 +1264,85 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn split_subtitles() {
+        let subs = "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+                    2\n00:00:59,000 --> 00:01:01,000\nTwo\n\n\
+                    3\n00:01:05,000 --> 00:01:06,000\nThree";
+        let parsed_subs = Subtitles::parse_from_str(subs.to_string()).unwrap();
+
+        let (first, second) = parsed_subs
+            .clone()
+            .split_at_time(Timestamp::new(0, 1, 0, 0));
+        assert_eq!(first.len(), 2);
+        assert_eq!(
+            second.to_string(),
+            "1\n00:00:05,000 --> 00:00:06,000\nThree"
+        );
+
+        let (first, second) = parsed_subs.split_at_index(1);
+        assert_eq!(first.to_string(), "1\n00:00:01,000 --> 00:00:02,000\nOne");
+        assert_eq!(
+            second.to_string(),
+            "1\n00:00:00,000 --> 00:00:02,000\nTwo\n\n\
+             2\n00:00:06,000 --> 00:00:07,000\nThree"
+        );
+    }
+
+    #[test]
+    fn join_subtitles() {
+        let one =
+            Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nOne".to_string()).unwrap();
+        let two =
+            Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nTwo".to_string()).unwrap();
+
+        let joined = Subtitles::join(vec![
+            (one.clone(), Timestamp::new(0, 0, 0, 0)),
+            (two.clone(), Timestamp::new(0, 0, 10, 0)),
+        ]);
+        assert_eq!(
+            joined.to_string(),
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:11,000 --> 00:00:12,000\nTwo"
+        );
+
+        let joined = Subtitles::join_by_durations(vec![
+            (one, Timestamp::new(0, 0, 10, 0)),
+            (two, Timestamp::new(0, 0, 5, 0)),
+        ]);
+        assert_eq!(joined[1].start_time, Timestamp::new(0, 0, 11, 0));
+        assert_eq!(joined[1].num, 2);
+    }
+
+    #[test]
+    fn remove_section_closes_gap() {
+        let subs = "1\n00:00:01,000 --> 00:00:02,000\nBefore\n\n\
+                    2\n00:00:04,000 --> 00:00:06,000\nStraddling\n\n\
+                    3\n00:00:06,500 --> 00:00:07,000\nInside\n\n\
+                    4\n00:00:12,000 --> 00:00:13,000\nAfter";
+        let mut parsed_subs = Subtitles::parse_from_str(subs.to_string()).unwrap();
+        parsed_subs.remove_section(Timestamp::new(0, 0, 5, 0), Timestamp::new(0, 0, 10, 0));
+
+        assert_eq!(
+            parsed_subs.to_string(),
+            "1\n00:00:01,000 --> 00:00:02,000\nBefore\n\n\
+             2\n00:00:04,000 --> 00:00:05,000\nStraddling\n\n\
+             3\n00:00:07,000 --> 00:00:08,000\nAfter"
+        );
+    }
+
+    #[test]
+    fn extract_reversed_window() {
+        let subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:10,000\nLong".to_string())
+            .unwrap();
+        assert!(subs
+            .extract_window(Timestamp::new(0, 0, 5, 0), Timestamp::new(0, 0, 3, 0))
+            .is_empty());
+        assert_eq!(
+            subs.extract_window(Timestamp::new(0, 0, 3, 0), Timestamp::new(0, 0, 5, 0))
+                .to_string(),
+            "1\n00:00:00,000 --> 00:00:02,000\nLong"
+        );
+    }
 }
@@ -0,0 +1,490 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Quality control checks for subtitle collections.
+//!
+//! A [`LintConfig`] holds a set of rules, each with the severity its findings should be reported
+//! with. Running it over a [`Subtitles`] collection produces a list of [`Finding`]s.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::lint::{LintConfig, Rule, Severity};
+//!
+//! let subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:01,500\nThis line is definitely too long to read".to_string()).unwrap();
+//! let config = LintConfig::new()
+//!     .with_rule(Rule::MaxCharsPerLine(32), Severity::Error)
+//!     .with_rule(Rule::MinDuration(1000), Severity::Warning);
+//!
+//! let findings = subs.lint(&config);
+//! assert_eq!(findings.len(), 2);
+//! assert_eq!(findings[0].to_string(),
+//!            "#1 error [max-chars-per-line]: line 1 has 40 characters (maximum 32); \
+//!             suggestion: break the line or shorten the text");
+//! ```
+//!
+//! Instead of configuring every rule by hand, a configuration may also start from one of the
+//! built-in [`Preset`]s.
+//!
+//! [`LintConfig`]: struct.LintConfig.html
+//! [`Subtitles`]: ../struct.Subtitles.html
+//! [`Finding`]: struct.Finding.html
+//! [`Preset`]: enum.Preset.html
+
+use crate::{Subtitle, Subtitles};
+use std::fmt;
+
+mod presets;
+
+pub use presets::Preset;
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single quality control rule. Durations and gaps are in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Rule {
+    /// Maximum number of characters in any line, not counting formatting tags.
+    MaxCharsPerLine(usize),
+    /// Maximum number of lines in a subtitle.
+    MaxLines(usize),
+    /// Maximum reading speed, in characters per second.
+    MaxCharsPerSecond(f64),
+    /// Minimum time a subtitle stays on screen.
+    MinDuration(u32),
+    /// Maximum time a subtitle stays on screen.
+    MaxDuration(u32),
+    /// Minimum time between the end of a subtitle and the start of the next one.
+    MinGap(u32),
+    /// Subtitles must not overlap with the previous one.
+    Overlap,
+    /// Subtitles must contain some text.
+    EmptyText,
+    /// Formatting tags such as `<i>` must be closed and properly nested.
+    UnbalancedMarkup,
+    /// Subtitles must be numbered sequentially starting from 1.
+    NonSequentialNumbering,
+    /// Subtitles must end after they start.
+    ZeroDuration,
+}
+
+impl Rule {
+    /// Returns the name of the rule in kebab-case, e.g. "max-chars-per-line".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::MaxCharsPerLine(_) => "max-chars-per-line",
+            Rule::MaxLines(_) => "max-lines",
+            Rule::MaxCharsPerSecond(_) => "max-chars-per-second",
+            Rule::MinDuration(_) => "min-duration",
+            Rule::MaxDuration(_) => "max-duration",
+            Rule::MinGap(_) => "min-gap",
+            Rule::Overlap => "overlap",
+            Rule::EmptyText => "empty-text",
+            Rule::UnbalancedMarkup => "unbalanced-markup",
+            Rule::NonSequentialNumbering => "non-sequential-numbering",
+            Rule::ZeroDuration => "zero-duration",
+        }
+    }
+
+    /// Returns the limit of the rule, or None for rules without one.
+    pub fn limit(&self) -> Option<f64> {
+        match *self {
+            Rule::MaxCharsPerLine(max) | Rule::MaxLines(max) => Some(max as f64),
+            Rule::MaxCharsPerSecond(max) => Some(max),
+            Rule::MinDuration(millis) | Rule::MaxDuration(millis) | Rule::MinGap(millis) => {
+                Some(millis as f64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Constructs a rule from its [name](#method.name) and limit.
+    ///
+    /// Returns None if the name is unknown, or if the rule requires a limit and none was given.
+    pub fn from_name(name: &str, limit: Option<f64>) -> Option<Rule> {
+        let rule = match name {
+            "max-chars-per-line" => Rule::MaxCharsPerLine(limit? as usize),
+            "max-lines" => Rule::MaxLines(limit? as usize),
+            "max-chars-per-second" => Rule::MaxCharsPerSecond(limit?),
+            "min-duration" => Rule::MinDuration(limit? as u32),
+            "max-duration" => Rule::MaxDuration(limit? as u32),
+            "min-gap" => Rule::MinGap(limit? as u32),
+            "overlap" => Rule::Overlap,
+            "empty-text" => Rule::EmptyText,
+            "unbalanced-markup" => Rule::UnbalancedMarkup,
+            "non-sequential-numbering" => Rule::NonSequentialNumbering,
+            "zero-duration" => Rule::ZeroDuration,
+            _ => return None,
+        };
+        Some(rule)
+    }
+}
+
+/// A problem found in a subtitle collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// The rule that produced this finding.
+    pub rule: Rule,
+    pub severity: Severity,
+    /// The position of the offending subtitle in the collection (not its number).
+    pub index: usize,
+    /// The number of the offending subtitle.
+    pub num: usize,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} [{}]: {}",
+            self.num,
+            self.severity,
+            self.rule.name(),
+            self.message
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; suggestion: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of rules along with the severity of their findings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintConfig {
+    pub rules: Vec<(Rule, Severity)>,
+}
+
+impl LintConfig {
+    /// Constructs a new LintConfig without any rules.
+    pub fn new() -> LintConfig {
+        Default::default()
+    }
+
+    /// Constructs a LintConfig with every rule enabled, using common general-purpose limits.
+    pub fn recommended() -> LintConfig {
+        LintConfig::new()
+            .with_rule(Rule::MaxCharsPerLine(42), Severity::Warning)
+            .with_rule(Rule::MaxLines(2), Severity::Warning)
+            .with_rule(Rule::MaxCharsPerSecond(21.0), Severity::Warning)
+            .with_rule(Rule::MinDuration(833), Severity::Warning)
+            .with_rule(Rule::MaxDuration(7000), Severity::Warning)
+            .with_rule(Rule::MinGap(83), Severity::Info)
+            .with_rule(Rule::Overlap, Severity::Error)
+            .with_rule(Rule::EmptyText, Severity::Error)
+            .with_rule(Rule::UnbalancedMarkup, Severity::Error)
+            .with_rule(Rule::NonSequentialNumbering, Severity::Warning)
+            .with_rule(Rule::ZeroDuration, Severity::Error)
+    }
+
+    /// Constructs a LintConfig with the rules of a built-in [`Preset`].
+    ///
+    /// [`Preset`]: enum.Preset.html
+    pub fn preset(preset: Preset) -> LintConfig {
+        preset.config()
+    }
+
+    /// Adds a rule to the configuration, returning the updated configuration.
+    pub fn with_rule(mut self, rule: Rule, severity: Severity) -> LintConfig {
+        self.rules.push((rule, severity));
+        self
+    }
+
+    /// Adds a rule to the configuration, replacing any existing rule with the same name.
+    pub fn set_rule(&mut self, rule: Rule, severity: Severity) {
+        match self.rules.iter_mut().find(|(r, _)| r.name() == rule.name()) {
+            Some(existing) => *existing = (rule, severity),
+            None => self.rules.push((rule, severity)),
+        }
+    }
+
+    /// Removes any rule with the given name from the configuration.
+    pub fn remove_rule(&mut self, name: &str) {
+        self.rules.retain(|(r, _)| r.name() != name);
+    }
+
+    /// Runs every rule over the subtitles, returning the findings ordered by subtitle position.
+    pub fn check(&self, subs: &Subtitles) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for &(rule, severity) in &self.rules {
+            for (index, sub) in subs.into_iter().enumerate() {
+                let prev = index.checked_sub(1).map(|i| &subs[i]);
+                if let Some((message, suggestion)) = check_rule(rule, sub, prev, index) {
+                    findings.push(Finding {
+                        rule,
+                        severity,
+                        index,
+                        num: sub.num,
+                        message,
+                        suggestion,
+                    });
+                }
+            }
+        }
+        findings.sort_by_key(|f| f.index);
+        findings
+    }
+}
+
+/// Returns the duration of a subtitle in milliseconds, which is negative if it ends before it
+/// starts.
+fn duration(sub: &Subtitle) -> i64 {
+    sub.end_time.to_milliseconds() as i64 - sub.start_time.to_milliseconds() as i64
+}
+
+/// Describes the first formatting tag problem in a text, if any.
+fn markup_problem(text: &str) -> Option<String> {
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => return Some("unterminated tag".to_string()),
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if !closing {
+            open.push(name);
+        } else if open.last() == Some(&name) {
+            open.pop();
+        } else {
+            return Some(format!("unexpected closing tag </{}>", name));
+        }
+    }
+    open.last()
+        .map(|name| format!("tag <{}> is never closed", name))
+}
+
+/// Checks a single rule against a subtitle, returning a message and suggested fix on failure.
+fn check_rule(
+    rule: Rule,
+    sub: &Subtitle,
+    prev: Option<&Subtitle>,
+    index: usize,
+) -> Option<(String, Option<String>)> {
+    match rule {
+        Rule::MaxCharsPerLine(max) => {
+            let plain = sub.plain_text();
+            plain.lines().enumerate().find_map(|(i, line)| {
+                let count = line.chars().count();
+                (count > max).then(|| {
+                    (
+                        format!("line {} has {} characters (maximum {})", i + 1, count, max),
+                        Some("break the line or shorten the text".to_string()),
+                    )
+                })
+            })
+        }
+        Rule::MaxLines(max) => {
+            let count = sub.text.lines().count();
+            (count > max).then(|| {
+                (
+                    format!("subtitle has {} lines (maximum {})", count, max),
+                    Some("split the subtitle in two".to_string()),
+                )
+            })
+        }
+        Rule::MaxCharsPerSecond(max) => {
+            let millis = duration(sub);
+            let chars = sub.plain_text().chars().filter(|&c| c != '\n').count();
+            if millis <= 0 || chars == 0 {
+                return None;
+            }
+            let cps = chars as f64 * 1000.0 / millis as f64;
+            (cps > max).then(|| {
+                let needed = (chars as f64 * 1000.0 / max).ceil() as i64;
+                (
+                    format!(
+                        "reading speed is {:.1} characters per second (maximum {})",
+                        cps, max
+                    ),
+                    Some(format!(
+                        "extend the subtitle to at least {} ms or shorten the text",
+                        needed
+                    )),
+                )
+            })
+        }
+        Rule::MinDuration(min) => {
+            let millis = duration(sub);
+            (millis > 0 && millis < min as i64).then(|| {
+                (
+                    format!("subtitle lasts {} ms (minimum {} ms)", millis, min),
+                    Some(format!("extend the subtitle to at least {} ms", min)),
+                )
+            })
+        }
+        Rule::MaxDuration(max) => {
+            let millis = duration(sub);
+            (millis > max as i64).then(|| {
+                (
+                    format!("subtitle lasts {} ms (maximum {} ms)", millis, max),
+                    Some(format!("shorten the subtitle to at most {} ms", max)),
+                )
+            })
+        }
+        Rule::MinGap(min) => {
+            let prev = prev?;
+            let gap =
+                sub.start_time.to_milliseconds() as i64 - prev.end_time.to_milliseconds() as i64;
+            (gap >= 0 && gap < min as i64).then(|| {
+                (
+                    format!(
+                        "gap to the previous subtitle is {} ms (minimum {} ms)",
+                        gap, min
+                    ),
+                    Some(format!(
+                        "end the previous subtitle at least {} ms earlier",
+                        min as i64 - gap
+                    )),
+                )
+            })
+        }
+        Rule::Overlap => {
+            let prev = prev?;
+            (sub.start_time < prev.end_time).then(|| {
+                (
+                    format!(
+                        "subtitle starts at {} before the previous one ends at {}",
+                        sub.start_time, prev.end_time
+                    ),
+                    Some(format!(
+                        "end the previous subtitle at {} or earlier",
+                        sub.start_time
+                    )),
+                )
+            })
+        }
+        Rule::EmptyText => sub.plain_text().trim().is_empty().then(|| {
+            (
+                "subtitle has no text".to_string(),
+                Some("remove the subtitle".to_string()),
+            )
+        }),
+        Rule::UnbalancedMarkup => markup_problem(&sub.text).map(|problem| {
+            (
+                problem,
+                Some("close every formatting tag in the order it was opened".to_string()),
+            )
+        }),
+        Rule::NonSequentialNumbering => (sub.num != index + 1).then(|| {
+            (
+                format!("subtitle is numbered {} instead of {}", sub.num, index + 1),
+                Some("renumber the subtitles".to_string()),
+            )
+        }),
+        Rule::ZeroDuration => (sub.end_time <= sub.start_time).then(|| {
+            (
+                format!(
+                    "subtitle ends at {} but starts at {}",
+                    sub.end_time, sub.start_time
+                ),
+                Some("set an end time after the start time".to_string()),
+            )
+        }),
+    }
+}
+
+impl Subtitles {
+    /// Checks the subtitles against the rules of a [`LintConfig`], returning the findings ordered
+    /// by subtitle position.
+    ///
+    /// [`LintConfig`]: lint/struct.LintConfig.html
+    pub fn lint(&self, config: &LintConfig) -> Vec<Finding> {
+        config.check(self)
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn recommended_rules() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:03,000\n<i>Hello</i> world\n\n\
+             3\n00:00:02,500 --> 00:00:04,000\n<i>Unclosed\n\n\
+             3\n00:00:05,000 --> 00:00:05,000\n "
+                .to_string(),
+        )
+        .unwrap();
+
+        let findings = subs.lint(&LintConfig::recommended());
+        let names: Vec<(usize, &str)> = findings.iter().map(|f| (f.index, f.rule.name())).collect();
+        assert_eq!(
+            names,
+            vec![
+                (1, "overlap"),
+                (1, "unbalanced-markup"),
+                (1, "non-sequential-numbering"),
+                (2, "empty-text"),
+                (2, "zero-duration"),
+            ]
+        );
+        assert_eq!(findings[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn markup_problems() {
+        assert_eq!(markup_problem("<i>Hello</i> <b>world</b>"), None);
+        assert_eq!(
+            markup_problem("<i><b>Hello</i></b>"),
+            Some("unexpected closing tag </i>".to_string())
+        );
+        assert_eq!(
+            markup_problem("<font color=\"red\">Hello"),
+            Some("tag <font> is never closed".to_string())
+        );
+    }
+
+    #[test]
+    fn reading_speed() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:00,000 --> 00:00:01,000\n<i>Twenty-five characters</i>!!!".to_string(),
+        )
+        .unwrap();
+        let config = LintConfig::new().with_rule(Rule::MaxCharsPerSecond(20.0), Severity::Warning);
+        let findings = subs.lint(&config);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].suggestion.as_deref(),
+            Some("extend the subtitle to at least 1250 ms or shorten the text")
+        );
+    }
+}
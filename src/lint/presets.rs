@@ -0,0 +1,317 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use super::{LintConfig, Rule, Severity};
+#[cfg(any(feature = "toml", feature = "json"))]
+use crate::ParsingError;
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::path::Path;
+
+/// A built-in set of rules modelled after a common style guide.
+///
+/// The limits are approximations of the publicly available guidelines and are meant as a starting
+/// point; individual rules can be changed afterwards with [`LintConfig::set_rule`].
+///
+/// # Examples
+///
+/// ```
+/// use srtlib::lint::{LintConfig, Preset, Rule, Severity};
+///
+/// let mut config = LintConfig::preset(Preset::Streaming);
+/// config.set_rule(Rule::MaxCharsPerLine(37), Severity::Error);
+/// assert!(config.rules.contains(&(Rule::MaxCharsPerLine(37), Severity::Error)));
+/// ```
+///
+/// [`LintConfig::set_rule`]: struct.LintConfig.html#method.set_rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Streaming platform style: 42 characters per line, 20 characters per second, at least 5/6
+    /// of a second on screen and a 2 frame gap at 24 fps.
+    Streaming,
+    /// BBC-like broadcast style: 37 characters per line, 17 characters per second, at least a
+    /// second on screen and a 2 frame gap at 25 fps.
+    Bbc,
+    /// Generic accessibility style with a slower reading speed: 32 characters per line, 15
+    /// characters per second and at least one and a half seconds on screen.
+    Accessibility,
+}
+
+impl Preset {
+    /// Every built-in preset.
+    pub const ALL: [Preset; 3] = [Preset::Streaming, Preset::Bbc, Preset::Accessibility];
+
+    /// Returns the name of the preset in kebab-case, e.g. "streaming".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Streaming => "streaming",
+            Preset::Bbc => "bbc",
+            Preset::Accessibility => "accessibility",
+        }
+    }
+
+    /// Returns the preset with the given [name](#method.name), if any.
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.iter().copied().find(|p| p.name() == name)
+    }
+
+    /// Returns the rules of the preset.
+    pub fn config(&self) -> LintConfig {
+        let (cpl, cps, min_duration, max_duration, min_gap) = match self {
+            Preset::Streaming => (42, 20.0, 833, 7000, 83),
+            Preset::Bbc => (37, 17.0, 1000, 8000, 80),
+            Preset::Accessibility => (32, 15.0, 1500, 6000, 83),
+        };
+        LintConfig::new()
+            .with_rule(Rule::MaxCharsPerLine(cpl), Severity::Error)
+            .with_rule(Rule::MaxLines(2), Severity::Error)
+            .with_rule(Rule::MaxCharsPerSecond(cps), Severity::Warning)
+            .with_rule(Rule::MinDuration(min_duration), Severity::Warning)
+            .with_rule(Rule::MaxDuration(max_duration), Severity::Warning)
+            .with_rule(Rule::MinGap(min_gap), Severity::Warning)
+            .with_rule(Rule::Overlap, Severity::Error)
+            .with_rule(Rule::EmptyText, Severity::Error)
+            .with_rule(Rule::UnbalancedMarkup, Severity::Error)
+            .with_rule(Rule::NonSequentialNumbering, Severity::Warning)
+            .with_rule(Rule::ZeroDuration, Severity::Error)
+    }
+}
+
+/// A single rule as stored in a configuration file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct RuleEntry {
+    rule: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<f64>,
+    #[serde(default = "default_severity")]
+    severity: Severity,
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    enabled: bool,
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+fn default_enabled() -> bool {
+    true
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+/// The contents of a configuration file.
+#[cfg(any(feature = "toml", feature = "json"))]
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extends: Option<String>,
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+impl ConfigFile {
+    fn into_config(self) -> Result<LintConfig, ParsingError> {
+        let mut config = match self.extends {
+            Some(name) => Preset::from_name(&name)
+                .ok_or_else(|| ParsingError::MalformedConfig(format!("unknown preset {}", name)))?
+                .config(),
+            None => LintConfig::new(),
+        };
+        for entry in self.rules {
+            if !entry.enabled {
+                config.remove_rule(&entry.rule);
+                continue;
+            }
+            let rule = Rule::from_name(&entry.rule, entry.limit).ok_or_else(|| {
+                ParsingError::MalformedConfig(format!(
+                    "unknown rule {} or missing limit",
+                    entry.rule
+                ))
+            })?;
+            config.set_rule(rule, entry.severity);
+        }
+        Ok(config)
+    }
+
+    fn from_config(config: &LintConfig) -> ConfigFile {
+        ConfigFile {
+            extends: None,
+            rules: config
+                .rules
+                .iter()
+                .map(|(rule, severity)| RuleEntry {
+                    rule: rule.name().to_string(),
+                    limit: rule.limit(),
+                    severity: *severity,
+                    enabled: true,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Loading and storing configurations as TOML or JSON.
+///
+/// A configuration file may extend one of the built-in presets, and then add, replace or disable
+/// rules by name:
+///
+/// ```toml
+/// extends = "streaming"
+///
+/// [[rules]]
+/// rule = "max-chars-per-line"
+/// limit = 37
+/// severity = "error"
+///
+/// [[rules]]
+/// rule = "min-gap"
+/// enabled = false
+/// ```
+///
+/// The severity defaults to "warning" if omitted. The same structure is used for JSON files.
+impl LintConfig {
+    /// Constructs a new LintConfig by parsing the contents of a TOML configuration file.
+    ///
+    /// # Errors
+    ///
+    /// If the contents are not valid TOML, or name an unknown preset or rule, a MalformedConfig
+    /// error variant will be returned.
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(input: &str) -> Result<LintConfig, ParsingError> {
+        toml::from_str::<ConfigFile>(input)
+            .map_err(|e| ParsingError::MalformedConfig(e.to_string()))?
+            .into_config()
+    }
+
+    /// Returns the configuration in the TOML configuration file format.
+    ///
+    /// # Errors
+    ///
+    /// If the configuration cannot be serialized, a MalformedConfig error variant will be
+    /// returned.
+    #[cfg(feature = "toml")]
+    pub fn to_toml_string(&self) -> Result<String, ParsingError> {
+        toml::to_string(&ConfigFile::from_config(self))
+            .map_err(|e| ParsingError::MalformedConfig(e.to_string()))
+    }
+
+    /// Constructs a new LintConfig by parsing the contents of a JSON configuration file.
+    ///
+    /// # Errors
+    ///
+    /// If the contents are not valid JSON, or name an unknown preset or rule, a MalformedConfig
+    /// error variant will be returned.
+    #[cfg(feature = "json")]
+    pub fn from_json_str(input: &str) -> Result<LintConfig, ParsingError> {
+        serde_json::from_str::<ConfigFile>(input)
+            .map_err(|e| ParsingError::MalformedConfig(e.to_string()))?
+            .into_config()
+    }
+
+    /// Returns the configuration in the JSON configuration file format.
+    ///
+    /// # Errors
+    ///
+    /// If the configuration cannot be serialized, a MalformedConfig error variant will be
+    /// returned.
+    #[cfg(feature = "json")]
+    pub fn to_json_string(&self) -> Result<String, ParsingError> {
+        serde_json::to_string_pretty(&ConfigFile::from_config(self))
+            .map_err(|e| ParsingError::MalformedConfig(e.to_string()))
+    }
+
+    /// Constructs a new LintConfig by parsing a .toml or .json configuration file, depending on
+    /// its extension.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read, an IOError error variant will be returned. If the extension is
+    /// not supported by the enabled features, or the contents are malformed, a MalformedConfig
+    /// error variant will be returned.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<LintConfig, ParsingError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => LintConfig::from_toml_str(&contents),
+            #[cfg(feature = "json")]
+            Some("json") => LintConfig::from_json_str(&contents),
+            _ => Err(ParsingError::MalformedConfig(format!(
+                "unsupported configuration file {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn preset_names() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+            assert_eq!(preset.config().rules.len(), 11);
+        }
+        assert_eq!(Preset::from_name("unknown"), None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_config() {
+        let config = LintConfig::from_toml_str(
+            "extends = \"bbc\"\n\
+             [[rules]]\n\
+             rule = \"max-chars-per-line\"\n\
+             limit = 40\n\
+             [[rules]]\n\
+             rule = \"min-gap\"\n\
+             enabled = false\n",
+        )
+        .unwrap();
+        assert!(config
+            .rules
+            .contains(&(Rule::MaxCharsPerLine(40), Severity::Warning)));
+        assert_eq!(config.rules.len(), 10);
+        assert_eq!(
+            LintConfig::from_toml_str(&config.to_toml_string().unwrap()).unwrap(),
+            config
+        );
+        assert!(LintConfig::from_toml_str("extends = \"nope\"").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_config() {
+        let config = LintConfig::from_json_str(
+            r#"{"rules": [{"rule": "overlap", "severity": "error"},
+                          {"rule": "max-chars-per-second", "limit": 17.5}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.rules,
+            vec![
+                (Rule::Overlap, Severity::Error),
+                (Rule::MaxCharsPerSecond(17.5), Severity::Warning)
+            ]
+        );
+        assert_eq!(
+            LintConfig::from_json_str(&config.to_json_string().unwrap()).unwrap(),
+            config
+        );
+        assert!(LintConfig::from_json_str(r#"{"rules": [{"rule": "min-gap"}]}"#).is_err());
+    }
+}
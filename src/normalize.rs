@@ -0,0 +1,381 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Unicode normalization and typography cleanup.
+//!
+//! Subtitles from different sources often mix composed and decomposed characters, curly and
+//! straight quotes, ellipsis characters and three dots, or non-breaking spaces. A
+//! [`NormalizeConfig`] rewrites the text of subtitles consistently, so that they can be compared
+//! and checked reliably. Formatting tags are never modified.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::normalize::{Ellipsis, NormalizeConfig};
+//!
+//! let mut subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\n\
+//!                                           \"Cafe\u{301}\u{a0}au lait\"... don't".to_string()).unwrap();
+//! let config = NormalizeConfig {
+//!     ellipsis: Some(Ellipsis::Character),
+//!     ..NormalizeConfig::for_language("en-US")
+//! };
+//!
+//! assert_eq!(subs.normalize(&config), 1);
+//! assert_eq!(subs[0].text, "“Café au lait”… don’t");
+//! ```
+//!
+//! [`NormalizeConfig`]: struct.NormalizeConfig.html
+
+use crate::{unformatted_spans, Subtitle, Subtitles};
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnicodeForm {
+    /// Canonical composition, e.g. "e" followed by a combining acute accent becomes "é".
+    Nfc,
+    /// Compatibility composition, which additionally replaces characters such as ligatures,
+    /// full-width letters and "…" with their plain equivalents.
+    Nfkc,
+}
+
+/// The quotation marks used by a language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuoteStyle {
+    /// "Straight" and 'straight' quotes.
+    Straight,
+    /// “English” and ‘English’ quotes.
+    English,
+    /// „German“ and ‚German‘ quotes.
+    German,
+    /// „Polish” and ‚Polish’ quotes.
+    Polish,
+    /// «Guillemets» and ‹single guillemets›, as used in French, Spanish or Russian.
+    Guillemets,
+}
+
+impl QuoteStyle {
+    /// Returns the usual quote style of a language, given its ISO 639-1 code with an optional
+    /// region, e.g. "de" or "en-GB".
+    pub fn for_language(language: &str) -> Option<QuoteStyle> {
+        let code = language.split(['-', '_']).next()?.to_ascii_lowercase();
+        match code.as_str() {
+            "en" | "nl" | "pt" | "tr" | "zh" | "ko" | "id" => Some(QuoteStyle::English),
+            "de" | "cs" | "sk" | "sl" | "is" | "lt" | "bg" => Some(QuoteStyle::German),
+            "pl" | "hu" | "ro" | "hr" => Some(QuoteStyle::Polish),
+            "fr" | "es" | "it" | "ru" | "uk" | "el" | "ca" | "be" | "no" => {
+                Some(QuoteStyle::Guillemets)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the opening and closing double quotes, the opening and closing single quotes and
+    /// the apostrophe of the style.
+    fn marks(&self) -> [char; 5] {
+        match self {
+            QuoteStyle::Straight => ['"', '"', '\'', '\'', '\''],
+            QuoteStyle::English => ['“', '”', '‘', '’', '’'],
+            QuoteStyle::German => ['„', '“', '‚', '‘', '’'],
+            QuoteStyle::Polish => ['„', '”', '‚', '’', '’'],
+            QuoteStyle::Guillemets => ['«', '»', '‹', '›', '’'],
+        }
+    }
+}
+
+/// The way an ellipsis is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ellipsis {
+    /// A single "…" character.
+    Character,
+    /// Three dots, "...".
+    ThreeDots,
+}
+
+/// The characters recognised as double quotes.
+const DOUBLE_QUOTES: [char; 7] = ['"', '“', '”', '„', '‟', '«', '»'];
+/// The characters recognised as single quotes or apostrophes.
+const SINGLE_QUOTES: [char; 7] = ['\'', '‘', '’', '‚', '‛', '‹', '›'];
+
+/// Invisible characters without any effect on the rendering of the text. Zero-width joiners and
+/// non-joiners are not included, as they are needed by emoji sequences and some scripts.
+const ZERO_WIDTH: [char; 4] = ['\u{200B}', '\u{2060}', '\u{FEFF}', '\u{180E}'];
+
+/// The normalizations to apply to the text of subtitles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NormalizeConfig {
+    /// The Unicode normalization form to convert to, if any.
+    pub unicode_form: Option<UnicodeForm>,
+    /// The quote style to convert quotes and apostrophes to, if any.
+    pub quotes: Option<QuoteStyle>,
+    /// The way to write ellipses, if they should be converted.
+    pub ellipsis: Option<Ellipsis>,
+    /// Replace tabs and special spaces (e.g. non-breaking ones) with regular spaces, collapse
+    /// consecutive spaces and remove whitespace at the start and end of lines, even when it is
+    /// inside formatting tags as in "<i> Hello</i>".
+    pub clean_whitespace: bool,
+    /// Remove zero-width spaces, word joiners and byte order marks.
+    pub remove_zero_width: bool,
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> NormalizeConfig {
+        NormalizeConfig {
+            unicode_form: Some(UnicodeForm::Nfc),
+            quotes: None,
+            ellipsis: None,
+            clean_whitespace: true,
+            remove_zero_width: true,
+        }
+    }
+}
+
+/// Checks if a quote preceded by the given character opens a quotation.
+fn is_opening(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => {
+            c.is_whitespace()
+                || matches!(c, '(' | '[' | '{' | '-' | '–' | '—' | '/')
+                || DOUBLE_QUOTES.contains(&c)
+                || SINGLE_QUOTES.contains(&c)
+        }
+    }
+}
+
+/// Converts every quote and apostrophe outside of formatting tags to the given style.
+fn convert_quotes(text: &str, style: QuoteStyle) -> String {
+    let [open_double, close_double, open_single, close_single, apostrophe] = style.marks();
+    let chars: Vec<(usize, char)> = unformatted_spans(text)
+        .into_iter()
+        .flat_map(|span| {
+            text[span.clone()]
+                .char_indices()
+                .map(move |(i, c)| (span.start + i, c))
+        })
+        .collect();
+
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p].1);
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let replacement = if DOUBLE_QUOTES.contains(&c) {
+            if is_opening(prev) {
+                open_double
+            } else {
+                close_double
+            }
+        } else if SINGLE_QUOTES.contains(&c) {
+            let in_word = prev.is_some_and(char::is_alphanumeric);
+            if in_word && next.is_some_and(char::is_alphanumeric) {
+                apostrophe
+            } else if is_opening(prev) {
+                open_single
+            } else {
+                close_single
+            }
+        } else {
+            continue;
+        };
+        res.push_str(&text[last..pos]);
+        res.push(replacement);
+        last = pos + c.len_utf8();
+    }
+    res.push_str(&text[last..]);
+    res
+}
+
+/// Replaces every run of exactly three dots with an ellipsis character.
+fn dots_to_ellipsis(text: &str) -> String {
+    fn flush(res: &mut String, dots: usize) {
+        if dots == 3 {
+            res.push('…');
+        } else {
+            res.extend(std::iter::repeat_n('.', dots));
+        }
+    }
+
+    let mut res = String::with_capacity(text.len());
+    let mut dots = 0;
+    for c in text.chars() {
+        if c == '.' {
+            dots += 1;
+            continue;
+        }
+        flush(&mut res, dots);
+        dots = 0;
+        res.push(c);
+    }
+    flush(&mut res, dots);
+    res
+}
+
+/// Replaces special spaces outside of formatting tags with regular ones, collapses consecutive
+/// spaces and trims every line, including inside the formatting tags at its start and end.
+fn clean_whitespace(text: &str) -> String {
+    let spans = unformatted_spans(text);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    // whether the line has any text outside of tags so far
+    let mut has_text = false;
+    // the position of a space that is only kept if more text follows on the line
+    let mut space = None;
+    for (i, c) in text.char_indices() {
+        let unformatted = spans.iter().any(|s| s.contains(&i));
+        if c == '\n' {
+            lines.push(std::mem::take(&mut line));
+            has_text = false;
+            space = None;
+        } else if c.is_whitespace() && c != '\u{3000}' && unformatted {
+            if has_text && space.is_none() {
+                space = Some(line.len());
+            }
+        } else {
+            if unformatted {
+                if let Some(pos) = space.take() {
+                    line.insert(pos, ' ');
+                }
+                has_text = true;
+            }
+            line.push(c);
+        }
+    }
+    lines.push(line);
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+/// Applies a transformation to every part of the text outside of formatting tags.
+fn map_unformatted(text: &str, f: impl Fn(&str) -> String) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for span in unformatted_spans(text) {
+        res.push_str(&text[last..span.start]);
+        res.push_str(&f(&text[span.clone()]));
+        last = span.end;
+    }
+    res.push_str(&text[last..]);
+    res
+}
+
+impl NormalizeConfig {
+    /// Constructs the default configuration along with the quote style of the given language,
+    /// as returned by [`QuoteStyle::for_language`].
+    ///
+    /// [`QuoteStyle::for_language`]: enum.QuoteStyle.html#method.for_language
+    pub fn for_language(language: &str) -> NormalizeConfig {
+        NormalizeConfig {
+            quotes: QuoteStyle::for_language(language),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the normalized text.
+    ///
+    /// The Unicode normalization is applied first, so an ellipsis expanded by NFKC is only kept
+    /// as a single character if [`ellipsis`](#structfield.ellipsis) asks for it.
+    pub fn normalize_text(&self, text: &str) -> String {
+        let mut text = match self.unicode_form {
+            Some(UnicodeForm::Nfc) => map_unformatted(text, |s| s.nfc().collect()),
+            Some(UnicodeForm::Nfkc) => map_unformatted(text, |s| s.nfkc().collect()),
+            None => text.to_string(),
+        };
+        if self.remove_zero_width {
+            text = map_unformatted(&text, |s| s.replace(ZERO_WIDTH, ""));
+        }
+        if self.clean_whitespace {
+            text = clean_whitespace(&text);
+        }
+        match self.ellipsis {
+            Some(Ellipsis::Character) => text = map_unformatted(&text, dots_to_ellipsis),
+            Some(Ellipsis::ThreeDots) => text = map_unformatted(&text, |s| s.replace('…', "...")),
+            None => (),
+        }
+        if let Some(style) = self.quotes {
+            text = convert_quotes(&text, style);
+        }
+        text
+    }
+}
+
+impl Subtitle {
+    /// Normalizes the text of the subtitle according to the [`NormalizeConfig`].
+    ///
+    /// [`NormalizeConfig`]: normalize/struct.NormalizeConfig.html
+    pub fn normalize(&mut self, config: &NormalizeConfig) {
+        self.set_text(config.normalize_text(&self.text));
+    }
+}
+
+impl Subtitles {
+    /// Normalizes the text of every subtitle according to the [`NormalizeConfig`], returning
+    /// the number of subtitles that were changed.
+    ///
+    /// [`NormalizeConfig`]: normalize/struct.NormalizeConfig.html
+    pub fn normalize(&mut self, config: &NormalizeConfig) -> usize {
+        let mut changed = 0;
+        for sub in self {
+            let text = config.normalize_text(&sub.text);
+            if text != sub.text {
+                sub.set_text(text);
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn quotes() {
+        let text = "<font color=\"red\">\"It's 'fine',\"</font> he said.";
+        assert_eq!(
+            convert_quotes(text, QuoteStyle::German),
+            "<font color=\"red\">„It’s ‚fine‘,“</font> he said."
+        );
+        assert_eq!(
+            convert_quotes("«Oui» — ‘no’", QuoteStyle::Straight),
+            "\"Oui\" — 'no'"
+        );
+        assert_eq!(
+            QuoteStyle::for_language("fr_CA"),
+            Some(QuoteStyle::Guillemets)
+        );
+        assert_eq!(QuoteStyle::for_language("xx"), None);
+    }
+
+    #[test]
+    fn ellipsis_and_whitespace() {
+        assert_eq!(
+            dots_to_ellipsis("Wait... what.... ok."),
+            "Wait… what.... ok."
+        );
+        assert_eq!(dots_to_ellipsis("a\0..."), "a\0…");
+        let config = NormalizeConfig {
+            unicode_form: Some(UnicodeForm::Nfkc),
+            ellipsis: Some(Ellipsis::Character),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.normalize_text(" ﬁne\u{200b}…\t\u{a0}ok \n"),
+            "fine… ok"
+        );
+        assert_eq!(
+            config.normalize_text("<font face=\"ﬁ  sans...\"> a\u{a0}\u{a0}b... </font>"),
+            "<font face=\"ﬁ  sans...\">a b…</font>"
+        );
+        assert_eq!(
+            config.normalize_text("<i> Hello</i>  <b>world </b>\n <i>\t</i>ok"),
+            "<i>Hello</i> <b>world</b>\n<i></i>ok"
+        );
+    }
+}
@@ -0,0 +1,585 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recorded edits that can be stored and re-applied to a subtitle collection.
+//!
+//! A [`Patch`] identifies the subtitles it edits by their timing and text instead of their
+//! number, so it can be applied to a re-delivered version of the same file. Patches are stored
+//! in a simple line-based text format, with one edit per line and its fields separated by tabs:
+//!
+//! ```text
+//! retime <start> <end> <text> <new start> <new end>
+//! text <start> <end> <old text> <new text>
+//! insert <start> <end> <text>
+//! delete <start> <end> <text>
+//! ```
+//!
+//! where line breaks, tabs and backslashes in texts are escaped as `\n`, `\t` and `\\`.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::patch::Patch;
+//!
+//! let old = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nHelo".to_string()).unwrap();
+//! let new = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\nHello".to_string()).unwrap();
+//!
+//! let patch = Patch::from_diff(&old.diff(&new));
+//! let stored = patch.to_string();
+//! assert_eq!(stored, "text\t00:00:01,000\t00:00:02,000\tHelo\tHello");
+//!
+//! let mut delivery = Subtitles::parse_from_str("7\n00:00:01,000 --> 00:00:02,000\nHelo".to_string()).unwrap();
+//! let conflicts = delivery.apply_patch(&Patch::parse(&stored).unwrap());
+//! assert!(conflicts.is_empty());
+//! assert_eq!(delivery[0].text, "Hello");
+//! ```
+//!
+//! [`Patch`]: struct.Patch.html
+
+use crate::diff::{Change, SubtitlesDiff};
+use crate::words::{self, Word};
+use crate::{ParsingError, Subtitle, Subtitles, Timestamp};
+use std::fmt;
+
+/// A single recorded edit.
+///
+/// The `start_time`, `end_time` and `text` fields identify the subtitle the edit applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Moves a subtitle to a new time.
+    Retime {
+        start_time: Timestamp,
+        end_time: Timestamp,
+        text: String,
+        new_start_time: Timestamp,
+        new_end_time: Timestamp,
+    },
+    /// Replaces the text of a subtitle.
+    ChangeText {
+        start_time: Timestamp,
+        end_time: Timestamp,
+        old_text: String,
+        new_text: String,
+    },
+    /// Adds a new subtitle.
+    Insert {
+        start_time: Timestamp,
+        end_time: Timestamp,
+        text: String,
+    },
+    /// Removes a subtitle.
+    Delete {
+        start_time: Timestamp,
+        end_time: Timestamp,
+        text: String,
+    },
+}
+
+impl Edit {
+    /// Returns the timing and text of the subtitle this edit targets, or None for insertions.
+    fn target(&self) -> Option<(Timestamp, Timestamp, &str)> {
+        match self {
+            Edit::Retime {
+                start_time,
+                end_time,
+                text,
+                ..
+            }
+            | Edit::Delete {
+                start_time,
+                end_time,
+                text,
+            } => Some((*start_time, *end_time, text)),
+            Edit::ChangeText {
+                start_time,
+                end_time,
+                old_text,
+                ..
+            } => Some((*start_time, *end_time, old_text)),
+            Edit::Insert { .. } => None,
+        }
+    }
+}
+
+/// Escapes a text so that it fits in a single field of the patch format.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+/// Reverses the escaping performed by `escape`.
+fn unescape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some(other) => res.push(other),
+                None => res.push('\\'),
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Retime {
+                start_time,
+                end_time,
+                text,
+                new_start_time,
+                new_end_time,
+            } => write!(
+                f,
+                "retime\t{}\t{}\t{}\t{}\t{}",
+                start_time,
+                end_time,
+                escape(text),
+                new_start_time,
+                new_end_time
+            ),
+            Edit::ChangeText {
+                start_time,
+                end_time,
+                old_text,
+                new_text,
+            } => write!(
+                f,
+                "text\t{}\t{}\t{}\t{}",
+                start_time,
+                end_time,
+                escape(old_text),
+                escape(new_text)
+            ),
+            Edit::Insert {
+                start_time,
+                end_time,
+                text,
+            } => write!(f, "insert\t{}\t{}\t{}", start_time, end_time, escape(text)),
+            Edit::Delete {
+                start_time,
+                end_time,
+                text,
+            } => write!(f, "delete\t{}\t{}\t{}", start_time, end_time, escape(text)),
+        }
+    }
+}
+
+/// The reason an edit could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// No subtitle resembling the target of the edit was found.
+    Missing,
+    /// A subtitle with the same text was found, but at a different time.
+    Moved(Subtitle),
+    /// A subtitle with the same timing was found, but with a different text.
+    Changed(Subtitle),
+    /// The subtitle to be inserted is already present, e.g. because the patch was applied before.
+    Exists(Subtitle),
+}
+
+/// An edit that could not be applied, along with the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub edit: Edit,
+    pub kind: ConflictKind,
+}
+
+/// An ordered list of edits.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patch {
+    pub edits: Vec<Edit>,
+}
+
+impl Patch {
+    /// Constructs a new(empty) Patch.
+    pub fn new() -> Patch {
+        Default::default()
+    }
+
+    /// Constructs the patch that turns the old collection of a diff into the new one.
+    pub fn from_diff(diff: &SubtitlesDiff) -> Patch {
+        let mut edits = Vec::new();
+        for change in &diff.changes {
+            match change {
+                Change::Added(sub) => edits.push(Edit::Insert {
+                    start_time: sub.start_time,
+                    end_time: sub.end_time,
+                    text: sub.text.clone(),
+                }),
+                Change::Removed(sub) => edits.push(Edit::Delete {
+                    start_time: sub.start_time,
+                    end_time: sub.end_time,
+                    text: sub.text.clone(),
+                }),
+                Change::Retimed { old, new, .. } => edits.push(Edit::Retime {
+                    start_time: old.start_time,
+                    end_time: old.end_time,
+                    text: old.text.clone(),
+                    new_start_time: new.start_time,
+                    new_end_time: new.end_time,
+                }),
+                Change::TextChanged { old, new, .. } => {
+                    edits.push(Edit::ChangeText {
+                        start_time: old.start_time,
+                        end_time: old.end_time,
+                        old_text: old.text.clone(),
+                        new_text: new.text.clone(),
+                    });
+                    if old.start_time != new.start_time || old.end_time != new.end_time {
+                        edits.push(Edit::Retime {
+                            start_time: old.start_time,
+                            end_time: old.end_time,
+                            text: new.text.clone(),
+                            new_start_time: new.start_time,
+                            new_end_time: new.end_time,
+                        });
+                    }
+                }
+                Change::Unchanged { .. } => {}
+            }
+        }
+        Patch { edits }
+    }
+
+    /// Constructs a new Patch by parsing a string in the patch format described in the [module
+    /// documentation].
+    ///
+    /// # Errors
+    ///
+    /// If a line does not describe a valid edit, a MalformedPatch error variant containing the
+    /// line number will be returned.
+    ///
+    /// [module documentation]: index.html
+    pub fn parse(input: &str) -> Result<Patch, ParsingError> {
+        let mut edits = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let err = || ParsingError::MalformedPatch(i + 1);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let time = |n: usize| Timestamp::parse(fields.get(n).ok_or_else(err)?);
+            let text = |n: usize| fields.get(n).map(|t| unescape(t)).ok_or_else(err);
+            let edit = match (fields[0], fields.len()) {
+                ("retime", 6) => Edit::Retime {
+                    start_time: time(1)?,
+                    end_time: time(2)?,
+                    text: text(3)?,
+                    new_start_time: time(4)?,
+                    new_end_time: time(5)?,
+                },
+                ("text", 5) => Edit::ChangeText {
+                    start_time: time(1)?,
+                    end_time: time(2)?,
+                    old_text: text(3)?,
+                    new_text: text(4)?,
+                },
+                ("insert", 4) => Edit::Insert {
+                    start_time: time(1)?,
+                    end_time: time(2)?,
+                    text: text(3)?,
+                },
+                ("delete", 4) => Edit::Delete {
+                    start_time: time(1)?,
+                    end_time: time(2)?,
+                    text: text(3)?,
+                },
+                _ => return Err(err()),
+            };
+            edits.push(edit);
+        }
+        Ok(Patch { edits })
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.edits.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Finds the subtitle targeted by an edit, or describes why it could not be found.
+fn locate(
+    subs: &[Subtitle],
+    start_time: Timestamp,
+    end_time: Timestamp,
+    text: &str,
+) -> Result<usize, ConflictKind> {
+    if let Some(i) = subs
+        .iter()
+        .position(|s| s.start_time == start_time && s.end_time == end_time && s.text == text)
+    {
+        return Ok(i);
+    }
+    if let Some(s) = subs.iter().filter(|s| s.text == text).min_by_key(|s| {
+        (s.start_time.to_milliseconds() as i64 - start_time.to_milliseconds() as i64).abs()
+    }) {
+        return Err(ConflictKind::Moved(s.clone()));
+    }
+    if let Some(s) = subs
+        .iter()
+        .find(|s| s.start_time == start_time && s.end_time == end_time)
+    {
+        return Err(ConflictKind::Changed(s.clone()));
+    }
+    Err(ConflictKind::Missing)
+}
+
+impl Subtitles {
+    /// Applies the edits of a [`Patch`] to this collection, in order.
+    ///
+    /// Every edit except insertions is applied to the subtitle with exactly the timing and text
+    /// recorded in the edit. Edits whose target cannot be found are skipped and returned as
+    /// conflicts, and so are insertions of a subtitle with the same timing and text as one that
+    /// is already present. The collection is kept sorted by time and renumbered starting from 1.
+    ///
+    /// The words of a retimed subtitle move along with its start and are clipped to its new
+    /// duration.
+    ///
+    /// [`Patch`]: patch/struct.Patch.html
+    pub fn apply_patch(&mut self, patch: &Patch) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for edit in &patch.edits {
+            let index = match edit.target() {
+                Some((start_time, end_time, text)) => {
+                    match locate(&self.0, start_time, end_time, text) {
+                        Ok(i) => Some(i),
+                        Err(kind) => {
+                            conflicts.push(Conflict {
+                                edit: edit.clone(),
+                                kind,
+                            });
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+            match (edit, index) {
+                (
+                    Edit::Retime {
+                        new_start_time,
+                        new_end_time,
+                        ..
+                    },
+                    Some(i),
+                ) => {
+                    let sub = &mut self.0[i];
+                    let start = new_start_time.to_milliseconds() as i64;
+                    let end = new_end_time.to_milliseconds() as i64;
+                    let delta = start - sub.start_time.to_milliseconds() as i64;
+                    // kept within the new span, so that they can't go past the limits
+                    let moved = |time: Timestamp| {
+                        let millis = (time.to_milliseconds() as i64 + delta).max(start).min(end);
+                        Timestamp::from_milliseconds(millis as u32)
+                    };
+                    let shifted: Vec<Word> = sub
+                        .words
+                        .iter()
+                        .map(|w| Word::new(moved(w.start_time), moved(w.end_time), w.text.clone()))
+                        .collect();
+                    sub.words = words::clip(&shifted, *new_start_time, *new_end_time);
+                    sub.start_time = *new_start_time;
+                    sub.end_time = *new_end_time;
+                }
+                (Edit::ChangeText { new_text, .. }, Some(i)) => {
+                    self.0[i].set_text(new_text.clone());
+                }
+                (Edit::Delete { .. }, Some(i)) => {
+                    self.0.remove(i);
+                }
+                (
+                    Edit::Insert {
+                        start_time,
+                        end_time,
+                        text,
+                    },
+                    _,
+                ) => {
+                    let existing = self.0.iter().find(|s| {
+                        s.start_time == *start_time && s.end_time == *end_time && s.text == *text
+                    });
+                    match existing {
+                        Some(s) => conflicts.push(Conflict {
+                            edit: edit.clone(),
+                            kind: ConflictKind::Exists(s.clone()),
+                        }),
+                        None => self.push(Subtitle::new(0, *start_time, *end_time, text.clone())),
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.0.sort_by_key(|s| (s.start_time, s.end_time));
+        self.renumber();
+        conflicts
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn patch_round_trip() {
+        let patch = Patch {
+            edits: vec![
+                Edit::Insert {
+                    start_time: Timestamp::new(0, 0, 1, 0),
+                    end_time: Timestamp::new(0, 0, 2, 0),
+                    text: "Two\nlines\twith a \\".to_string(),
+                },
+                Edit::Retime {
+                    start_time: Timestamp::new(0, 0, 3, 0),
+                    end_time: Timestamp::new(0, 0, 4, 0),
+                    text: "Moved".to_string(),
+                    new_start_time: Timestamp::new(0, 0, 3, 500),
+                    new_end_time: Timestamp::new(0, 0, 4, 500),
+                },
+            ],
+        };
+        assert_eq!(Patch::parse(&patch.to_string()).unwrap(), patch);
+        assert!(matches!(
+            Patch::parse("resize\t00:00:01,000"),
+            Err(ParsingError::MalformedPatch(1))
+        ));
+    }
+
+    #[test]
+    fn apply_with_conflicts() {
+        let old = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo\n\n\
+             3\n00:00:05,000 --> 00:00:06,000\nThree"
+                .to_string(),
+        )
+        .unwrap();
+        let new = Subtitles::parse_from_str(
+            "1\n00:00:01,500 --> 00:00:02,500\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo!\n\n\
+             3\n00:00:07,000 --> 00:00:08,000\nFour"
+                .to_string(),
+        )
+        .unwrap();
+        let patch = Patch::from_diff(&old.diff(&new));
+
+        let mut delivery = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo?\n\n\
+             3\n00:00:05,000 --> 00:00:06,000\nThree"
+                .to_string(),
+        )
+        .unwrap();
+        let conflicts = delivery.apply_patch(&patch);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].kind,
+            ConflictKind::Changed(delivery[1].clone())
+        );
+        assert_eq!(
+            delivery.to_string(),
+            "1\n00:00:01,500 --> 00:00:02,500\nOne\n\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo?\n\n\
+             3\n00:00:07,000 --> 00:00:08,000\nFour"
+        );
+
+        // applying the patch again inserts nothing
+        let conflicts = delivery.apply_patch(&patch);
+        assert_eq!(delivery.len(), 3);
+        assert!(conflicts
+            .iter()
+            .any(|c| c.kind == ConflictKind::Exists(delivery[2].clone())));
+    }
+
+    #[test]
+    fn retime_moves_words() {
+        let mut subs = Subtitles::new();
+        let mut sub = Subtitle::new(
+            1,
+            Timestamp::new(0, 0, 1, 0),
+            Timestamp::new(0, 0, 3, 0),
+            "Hello there".to_string(),
+        );
+        sub.words = vec![
+            Word::new(
+                Timestamp::new(0, 0, 1, 0),
+                Timestamp::new(0, 0, 2, 0),
+                "Hello".to_string(),
+            ),
+            Word::new(
+                Timestamp::new(0, 0, 2, 0),
+                Timestamp::new(0, 0, 3, 0),
+                "there".to_string(),
+            ),
+        ];
+        subs.push(sub);
+        let patch = Patch {
+            edits: vec![Edit::Retime {
+                start_time: Timestamp::new(0, 0, 1, 0),
+                end_time: Timestamp::new(0, 0, 3, 0),
+                text: "Hello there".to_string(),
+                new_start_time: Timestamp::new(0, 0, 5, 0),
+                new_end_time: Timestamp::new(0, 0, 6, 500),
+            }],
+        };
+        assert!(subs.apply_patch(&patch).is_empty());
+        assert_eq!(
+            subs[0].words,
+            vec![
+                Word::new(
+                    Timestamp::new(0, 0, 5, 0),
+                    Timestamp::new(0, 0, 6, 0),
+                    "Hello".to_string(),
+                ),
+                Word::new(
+                    Timestamp::new(0, 0, 6, 0),
+                    Timestamp::new(0, 0, 6, 500),
+                    "there".to_string(),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn retime_past_the_limits() {
+        let mut sub = Subtitle::new(
+            1,
+            Timestamp::new(0, 0, 0, 0),
+            Timestamp::new(0, 0, 10, 0),
+            "Late".to_string(),
+        );
+        sub.set_words(vec![Word::new(
+            Timestamp::new(0, 0, 9, 0),
+            Timestamp::new(0, 0, 10, 0),
+            "Late".to_string(),
+        )]);
+        let mut subs = Subtitles::new_from_vec(vec![sub]);
+        let patch = Patch {
+            edits: vec![Edit::Retime {
+                start_time: Timestamp::new(0, 0, 0, 0),
+                end_time: Timestamp::new(0, 0, 10, 0),
+                text: "Late".to_string(),
+                new_start_time: Timestamp::new(255, 59, 59, 0),
+                new_end_time: Timestamp::new(255, 59, 59, 999),
+            }],
+        };
+        assert!(subs.apply_patch(&patch).is_empty());
+        assert!(subs[0].words().is_empty());
+    }
+}
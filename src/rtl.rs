@@ -0,0 +1,230 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Handling of right-to-left text, such as Arabic and Hebrew subtitles.
+//!
+//! Many players lay out every line left-to-right, which displays the punctuation of right-to-left
+//! lines on the wrong side. Surrounding such lines with Unicode directional marks fixes their
+//! display, while files that were "fixed" by moving the punctuation to the start of the line can
+//! be repaired with [`Subtitle::fix_rtl_punctuation`].
+//!
+//! Adding marks always removes existing ones first, so that marks are never doubled. Stripping
+//! the marks from the output therefore gives back the text without any of its marks, which is
+//! the original text only if it had none to begin with.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitle;
+//! use srtlib::rtl::DirectionalMarks;
+//!
+//! let mut sub = Subtitle::parse("1\n00:00:01,000 --> 00:00:02,000\n.שלום\nHello.".to_string()).unwrap();
+//! sub.fix_rtl_punctuation();
+//! assert_eq!(sub.text, "שלום.\nHello.");
+//!
+//! sub.add_rtl_marks(DirectionalMarks::Rlm);
+//! assert_eq!(sub.text, "\u{200f}שלום.\u{200f}\nHello.");
+//!
+//! sub.strip_rtl_marks();
+//! assert_eq!(sub.text, "שלום.\nHello.");
+//! ```
+//!
+//! [`Subtitle::fix_rtl_punctuation`]: ../struct.Subtitle.html#method.fix_rtl_punctuation
+
+use crate::dialogue::split_leading_tags;
+use crate::{Subtitle, Subtitles};
+
+/// Right-to-left mark.
+const RLM: char = '\u{200F}';
+/// Right-to-left embedding.
+const RLE: char = '\u{202B}';
+/// Pop directional formatting, ending an embedding.
+const PDF: char = '\u{202C}';
+
+/// Every Unicode character that only affects the direction of text.
+const DIRECTIONAL_MARKS: [char; 12] = [
+    '\u{200E}', '\u{200F}', '\u{061C}', '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}',
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+/// Punctuation that belongs at the end of a sentence or clause.
+const PUNCTUATION: [char; 10] = ['.', ',', '!', '?', ':', ';', '…', '،', '؟', '؛'];
+
+/// The way right-to-left lines are marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirectionalMarks {
+    /// Surround the line with right-to-left marks (U+200F).
+    Rlm,
+    /// Wrap the line in a right-to-left embedding (U+202B ... U+202C).
+    Embedding,
+}
+
+/// Checks if a character belongs to a right-to-left script.
+fn is_rtl_char(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}')
+}
+
+/// Checks if a line is written right-to-left, meaning that its first letter belongs to a
+/// right-to-left script. Formatting tags are ignored.
+pub fn is_rtl(line: &str) -> bool {
+    crate::unformatted_spans(line)
+        .into_iter()
+        .flat_map(|span| line[span].chars())
+        .find(|c| c.is_alphabetic())
+        .is_some_and(is_rtl_char)
+}
+
+/// Removes every directional mark from a text.
+pub fn strip_marks(text: &str) -> String {
+    text.replace(DIRECTIONAL_MARKS, "")
+}
+
+/// Splits the formatting tags at the end of a line (e.g. "</i>") from the rest of it.
+fn split_trailing_tags(line: &str) -> (&str, &str) {
+    let mut start = line.len();
+    while line[..start].ends_with('>') {
+        match line[..start].rfind('<') {
+            Some(open) => start = open,
+            None => break,
+        }
+    }
+    line.split_at(start)
+}
+
+/// Moves punctuation from the start of a right-to-left line back to its end, and a dialogue
+/// dash from its end back to its start.
+fn fix_line_punctuation(line: &str) -> String {
+    if !is_rtl(line) {
+        return line.to_string();
+    }
+    let (lead, rest) = split_leading_tags(line);
+    let (body, trail) = split_trailing_tags(rest);
+
+    let mut body = body.to_string();
+    let moved = body.trim_start_matches(PUNCTUATION);
+    if moved.len() != body.len() && !moved.trim_end().ends_with(PUNCTUATION) {
+        let punctuation = &body[..body.len() - moved.len()];
+        body = format!("{}{}", moved.trim_start(), punctuation);
+    }
+    if let Some(text) = body.trim_end().strip_suffix('-') {
+        if !text.trim_start().starts_with('-') {
+            body = format!("- {}", text.trim());
+        }
+    }
+    format!("{}{}{}", lead, body, trail)
+}
+
+impl Subtitle {
+    /// Checks if any line of the subtitle is written right-to-left.
+    pub fn is_rtl(&self) -> bool {
+        self.text.lines().any(is_rtl)
+    }
+
+    /// Surrounds every right-to-left line of the subtitle with directional marks, replacing
+    /// any marks already present.
+    pub fn add_rtl_marks(&mut self, marks: DirectionalMarks) {
+        self.text = strip_marks(&self.text)
+            .split('\n')
+            .map(|line| {
+                if !is_rtl(line) {
+                    line.to_string()
+                } else {
+                    match marks {
+                        DirectionalMarks::Rlm => format!("{}{}{}", RLM, line, RLM),
+                        DirectionalMarks::Embedding => format!("{}{}{}", RLE, line, PDF),
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    /// Removes every directional mark from the text of the subtitle.
+    pub fn strip_rtl_marks(&mut self) {
+        self.text = strip_marks(&self.text);
+    }
+
+    /// Repairs right-to-left lines whose trailing punctuation was moved to the start of the line
+    /// (".שלום" becomes "שלום."), along with dialogue dashes that were moved to the end.
+    pub fn fix_rtl_punctuation(&mut self) {
+        self.text = self
+            .text
+            .split('\n')
+            .map(fix_line_punctuation)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+impl Subtitles {
+    /// Surrounds every right-to-left line with directional marks, replacing any marks already
+    /// present.
+    pub fn add_rtl_marks(&mut self, marks: DirectionalMarks) {
+        for sub in self {
+            sub.add_rtl_marks(marks);
+        }
+    }
+
+    /// Removes every directional mark from the text of the subtitles.
+    pub fn strip_rtl_marks(&mut self) {
+        for sub in self {
+            sub.strip_rtl_marks();
+        }
+    }
+
+    /// Repairs right-to-left lines whose punctuation was moved to the wrong end of the line.
+    pub fn fix_rtl_punctuation(&mut self) {
+        for sub in self {
+            sub.fix_rtl_punctuation();
+        }
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn detection() {
+        assert!(is_rtl("<i>مرحبا</i> world"));
+        assert!(is_rtl("123 שלום"));
+        assert!(!is_rtl("Hello שלום"));
+        assert!(!is_rtl("..."));
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(fix_line_punctuation("<i>?מה</i>"), "<i>מה?</i>");
+        assert_eq!(fix_line_punctuation("כן -"), "- כן");
+        assert_eq!(fix_line_punctuation("...כן, בטח."), "...כן, בטח.");
+        assert_eq!(fix_line_punctuation(".Hello"), ".Hello");
+    }
+
+    #[test]
+    fn marks_round_trip() {
+        let original =
+            Subtitle::parse("1\n00:00:01,000 --> 00:00:02,000\nשלום.\nHi".to_string()).unwrap();
+        let mut sub = original.clone();
+        sub.add_rtl_marks(DirectionalMarks::Embedding);
+        sub.add_rtl_marks(DirectionalMarks::Embedding);
+        assert_eq!(sub.text, "\u{202b}שלום.\u{202c}\nHi");
+        sub.strip_rtl_marks();
+        assert_eq!(sub, original);
+
+        // marks of the original text don't survive the round trip
+        sub.text = "\u{200e}Hi\u{200e}".to_string();
+        sub.add_rtl_marks(DirectionalMarks::Rlm);
+        sub.strip_rtl_marks();
+        assert_eq!(sub.text, "Hi");
+    }
+}
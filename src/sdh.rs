@@ -0,0 +1,284 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Removal of hearing-impaired (SDH) annotations.
+//!
+//! Subtitles for the deaf and hard of hearing contain sound descriptions, music symbols and
+//! speaker labels that are not wanted in regular subtitles. This module strips them, cleaning up
+//! the leftover dialogue dashes and empty lines, and drops subtitles that end up without text.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::sdh::SdhConfig;
+//!
+//! let mut subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\n[door slams]\n\n\
+//!                                           2\n00:00:03,000 --> 00:00:04,000\n- JOHN: Who's there?\n- (gasps)".to_string()).unwrap();
+//!
+//! // Preview the changes without modifying the subtitles.
+//! let report = subs.sdh_report(&SdhConfig::default());
+//! assert_eq!(report.len(), 2);
+//! assert_eq!(report[0].after, None);
+//!
+//! subs.remove_sdh(&SdhConfig::default());
+//! assert_eq!(subs.to_string(), "1\n00:00:03,000 --> 00:00:04,000\nWho's there?");
+//! ```
+
+use crate::dialogue::split_speaker_label;
+use crate::{Subtitle, Subtitles};
+
+/// The annotations to remove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SdhConfig {
+    /// Remove text in square brackets, e.g. "[door slams]".
+    pub remove_brackets: bool,
+    /// Remove text in parentheses, e.g. "(gasps)".
+    pub remove_parentheses: bool,
+    /// Remove music symbols such as "♪".
+    pub remove_music_symbols: bool,
+    /// Remove speaker labels at the start of a line, e.g. "JOHN:".
+    pub remove_speaker_labels: bool,
+    /// Only treat labels written in uppercase as speaker labels, so that "Note: ..." is kept.
+    pub uppercase_labels_only: bool,
+}
+
+impl Default for SdhConfig {
+    fn default() -> SdhConfig {
+        SdhConfig {
+            remove_brackets: true,
+            remove_parentheses: true,
+            remove_music_symbols: true,
+            remove_speaker_labels: true,
+            uppercase_labels_only: true,
+        }
+    }
+}
+
+/// A subtitle that is changed by the removal of SDH annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdhChange {
+    /// The position of the subtitle in the collection before any subtitles were dropped.
+    pub index: usize,
+    pub before: Subtitle,
+    /// The subtitle after the removal, or None if it is dropped for being empty.
+    pub after: Option<Subtitle>,
+}
+
+/// The symbols treated as music symbols.
+const MUSIC_SYMBOLS: [char; 3] = ['♪', '♫', '♬'];
+
+/// Marks every span of text enclosed by the given delimiters, including the delimiters, as
+/// removed. Unterminated spans are left alone.
+fn mark_enclosed(text: &str, open: char, close: char, removed: &mut [bool]) {
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find(open).map(|start| pos + start) {
+        match text[start..].find(close) {
+            Some(end) => {
+                pos = start + end + close.len_utf8();
+                removed[start..pos].fill(true);
+            }
+            None => break,
+        }
+    }
+}
+
+/// Splits a line into its leading dialogue dash (if any) and the rest.
+fn split_dash(line: &str) -> (&str, &str) {
+    let trimmed = line.trim_start();
+    match trimmed.strip_prefix('-') {
+        Some(rest) => ("- ", rest.trim_start()),
+        None => ("", trimmed),
+    }
+}
+
+/// Removes the speaker label at the start of a line, if it has one.
+fn remove_label(line: &str, uppercase_only: bool) -> String {
+    let (dash, rest) = split_dash(line);
+    match split_speaker_label(rest, uppercase_only) {
+        Some((_, text)) => format!("{}{}", dash, text),
+        None => line.to_string(),
+    }
+}
+
+/// Removes the formatting tags left without any content.
+fn remove_empty_tags(text: &str) -> String {
+    let mut res = text.to_string();
+    for tag in ["i", "b", "u"] {
+        for inner in ["", " "] {
+            res = res.replace(&format!("<{}>{}</{}>", tag, inner, tag), "");
+        }
+    }
+    res
+}
+
+impl SdhConfig {
+    /// Returns the text with the configured annotations removed.
+    pub fn clean_text(&self, text: &str) -> String {
+        let dashes_before = text
+            .lines()
+            .filter(|l| l.trim_start().starts_with('-'))
+            .count();
+
+        let mut removed = vec![false; text.len()];
+        if self.remove_brackets {
+            mark_enclosed(text, '[', ']', &mut removed);
+        }
+        if self.remove_parentheses {
+            mark_enclosed(text, '(', ')', &mut removed);
+        }
+        if self.remove_music_symbols {
+            for (i, c) in text.char_indices() {
+                if MUSIC_SYMBOLS.contains(&c) {
+                    removed[i..i + c.len_utf8()].fill(true);
+                }
+            }
+        }
+
+        // each line along with whether anything was removed from it
+        let mut cleaned = vec![(String::new(), false)];
+        for (i, c) in text.char_indices() {
+            let (line, touched) = cleaned.last_mut().unwrap();
+            if removed[i] {
+                *touched = true;
+            } else if c == '\n' {
+                cleaned.push((String::new(), false));
+            } else {
+                line.push(c);
+            }
+        }
+
+        let mut lines: Vec<String> = cleaned
+            .into_iter()
+            .filter_map(|(line, mut touched)| {
+                let mut line = line.strip_suffix('\r').map(str::to_string).unwrap_or(line);
+                if touched {
+                    line = remove_empty_tags(&line);
+                }
+                if self.remove_speaker_labels {
+                    let unlabeled = remove_label(&line, self.uppercase_labels_only);
+                    touched |= unlabeled != line;
+                    line = unlabeled;
+                }
+                if !touched {
+                    return Some(line);
+                }
+                let line = line
+                    .split(' ')
+                    .filter(|w| !w.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                // drop lines left with nothing but a dash or punctuation
+                line.chars().any(char::is_alphanumeric).then_some(line)
+            })
+            .collect();
+
+        if dashes_before > 1 && lines.len() == 1 {
+            lines[0] = split_dash(&lines[0]).1.to_string();
+        }
+        lines.join("\n")
+    }
+
+    /// Computes the changes that removing the annotations would make, without modifying the
+    /// subtitles.
+    pub fn report(&self, subs: &Subtitles) -> Vec<SdhChange> {
+        let mut changes = Vec::new();
+        for (index, sub) in subs.into_iter().enumerate() {
+            let text = self.clean_text(&sub.text);
+            if text == sub.text {
+                continue;
+            }
+            let after = if text.trim().is_empty() {
+                None
+            } else {
+                let mut after = sub.clone();
+                after.set_text(text);
+                Some(after)
+            };
+            changes.push(SdhChange {
+                index,
+                before: sub.clone(),
+                after,
+            });
+        }
+        changes
+    }
+}
+
+impl Subtitles {
+    /// Returns the changes that [`remove_sdh`](#method.remove_sdh) would make, without
+    /// modifying the subtitles.
+    pub fn sdh_report(&self, config: &SdhConfig) -> Vec<SdhChange> {
+        config.report(self)
+    }
+
+    /// Removes hearing-impaired annotations according to the [`SdhConfig`], dropping subtitles
+    /// that end up empty. If any subtitles are dropped, the collection is renumbered starting
+    /// from 1. Returns the changes that were made.
+    ///
+    /// [`SdhConfig`]: sdh/struct.SdhConfig.html
+    pub fn remove_sdh(&mut self, config: &SdhConfig) -> Vec<SdhChange> {
+        let changes = config.report(self);
+        let mut dropped = false;
+        for change in changes.iter().rev() {
+            match &change.after {
+                Some(after) => self.0[change.index] = after.clone(),
+                None => {
+                    self.0.remove(change.index);
+                    dropped = true;
+                }
+            }
+        }
+        if dropped {
+            self.renumber();
+        }
+        changes
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn clean_text() {
+        let config = SdhConfig::default();
+        assert_eq!(config.clean_text("♪ <i>La la la</i> ♪"), "<i>La la la</i>");
+        assert_eq!(
+            config.clean_text("[thunder]\nMAN #2: Get inside!"),
+            "Get inside!"
+        );
+        assert_eq!(
+            config.clean_text("- Note: it's 10:30.\n- DR. SMITH: Yes."),
+            "- Note: it's 10:30.\n- Yes."
+        );
+        assert_eq!(config.clean_text("<i>(whispering)</i>"), "");
+        assert_eq!(
+            config.clean_text("Wait...  what?\n?!"),
+            "Wait...  what?\n?!"
+        );
+        assert_eq!(
+            config.clean_text("[door slams] Wait...  what?\n?!"),
+            "Wait... what?\n?!"
+        );
+        assert_eq!(config.clean_text("Hi [door\nslams] there"), "Hi there");
+    }
+
+    #[test]
+    fn configurable_rules() {
+        let config = SdhConfig {
+            remove_parentheses: false,
+            remove_speaker_labels: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.clean_text("JOHN: (laughs) Sure. [music]"),
+            "JOHN: (laughs) Sure."
+        );
+    }
+}
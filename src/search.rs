@@ -0,0 +1,277 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Regular expression search and replace across a subtitle collection.
+//!
+//! This module is only available with the `regex` feature enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Subtitles;
+//! use srtlib::search::{Regex, SearchOptions};
+//!
+//! let mut subs = Subtitles::parse_from_str("1\n00:00:01,000 --> 00:00:02,000\n<i>Jhon</i>, wait!\n\n\
+//!                                           2\n00:00:03,000 --> 00:00:04,000\nJHON!".to_string()).unwrap();
+//! let pattern = Regex::new("(?i)jhon").unwrap();
+//! let options = SearchOptions { ignore_markup: true, preserve_case: true };
+//!
+//! let matches = subs.search(&pattern, &options);
+//! assert_eq!(matches[0].range, 3..7);
+//!
+//! // Check the affected subtitles before committing to the replacement.
+//! let preview = subs.preview_replace(&pattern, "John", &options);
+//! assert_eq!(preview[1].after, "JOHN!");
+//!
+//! subs.replace(&pattern, "John", &options);
+//! assert_eq!(subs[0].text, "<i>John</i>, wait!");
+//! ```
+
+use crate::{unformatted_spans, Subtitles};
+use std::ops::Range;
+
+pub use regex::Regex;
+
+/// Options controlling how searches and replacements are performed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    /// Match against the text without formatting tags. Reported ranges still refer to the
+    /// original text, and tags inside a matched range are kept, right after its replacement.
+    pub ignore_markup: bool,
+    /// Adapt the case of replacements to the matched text: all uppercase, capitalized or all
+    /// lowercase.
+    pub preserve_case: bool,
+}
+
+/// A match of a search within the text of a subtitle.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Match {
+    /// The position of the subtitle in the collection.
+    pub index: usize,
+    /// The number of the subtitle.
+    pub num: usize,
+    /// The byte range of the match within the text of the subtitle.
+    pub range: Range<usize>,
+    /// The matched text.
+    pub text: String,
+}
+
+/// The text of a subtitle before and after a replacement.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Replacement {
+    /// The position of the subtitle in the collection.
+    pub index: usize,
+    /// The number of the subtitle.
+    pub num: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// A text prepared for searching, along with the information needed to map byte offsets in the
+/// searched text back to the original one.
+struct Haystack {
+    text: String,
+    /// The original offset of every byte of the searched text.
+    offsets: Vec<usize>,
+}
+
+impl Haystack {
+    fn new(text: &str, ignore_markup: bool) -> Haystack {
+        if !ignore_markup {
+            return Haystack {
+                text: text.to_string(),
+                offsets: (0..text.len()).collect(),
+            };
+        }
+        let mut res = Haystack {
+            text: String::with_capacity(text.len()),
+            offsets: Vec::with_capacity(text.len()),
+        };
+        for span in unformatted_spans(text) {
+            res.text.push_str(&text[span.clone()]);
+            res.offsets.extend(span);
+        }
+        res
+    }
+
+    /// Maps a range of the searched text to the corresponding range of the original text.
+    fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = match self.offsets.get(range.start) {
+            Some(&start) => start,
+            None => self.offsets.last().map_or(0, |last| last + 1),
+        };
+        if range.is_empty() {
+            return start..start;
+        }
+        start..self.offsets[range.end - 1] + 1
+    }
+
+    /// Returns the tags of the original text that lie within a range of the searched text.
+    fn tags_within<'a>(&self, original: &'a str, range: Range<usize>) -> Vec<&'a str> {
+        let offsets = &self.offsets[range];
+        offsets
+            .windows(2)
+            .filter(|pair| pair[1] > pair[0] + 1)
+            .map(|pair| &original[pair[0] + 1..pair[1]])
+            .collect()
+    }
+}
+
+/// Adapts the case of a replacement to the case of the text it replaces.
+fn match_case(matched: &str, replacement: String) -> String {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        replacement
+    } else if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if letters[0].is_uppercase() {
+        let mut chars = replacement.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => replacement,
+        }
+    } else if letters.iter().all(|c| c.is_lowercase()) {
+        replacement.to_lowercase()
+    } else {
+        replacement
+    }
+}
+
+/// Replaces every match in a text, returning the new text.
+fn replace_text(text: &str, pattern: &Regex, replacement: &str, options: &SearchOptions) -> String {
+    let haystack = Haystack::new(text, options.ignore_markup);
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in pattern.captures_iter(&haystack.text) {
+        let m = caps.get(0).expect("capture group 0 always exists");
+        let range = haystack.original_range(m.range());
+        let mut expanded = String::new();
+        caps.expand(replacement, &mut expanded);
+        if options.preserve_case {
+            expanded = match_case(m.as_str(), expanded);
+        }
+        res.push_str(&text[last..range.start]);
+        res.push_str(&expanded);
+        for tag in haystack.tags_within(text, m.range()) {
+            res.push_str(tag);
+        }
+        last = range.end;
+    }
+    res.push_str(&text[last..]);
+    res
+}
+
+impl Subtitles {
+    /// Returns every match of the pattern in the text of the subtitles.
+    pub fn search(&self, pattern: &Regex, options: &SearchOptions) -> Vec<Match> {
+        let mut res = Vec::new();
+        for (index, sub) in self.into_iter().enumerate() {
+            let haystack = Haystack::new(&sub.text, options.ignore_markup);
+            for m in pattern.find_iter(&haystack.text) {
+                let range = haystack.original_range(m.range());
+                res.push(Match {
+                    index,
+                    num: sub.num,
+                    text: sub.text[range.clone()].to_string(),
+                    range,
+                });
+            }
+        }
+        res
+    }
+
+    /// Returns the subtitles that [`replace`](#method.replace) would change, along with their
+    /// text before and after the replacement, without modifying the subtitles.
+    ///
+    /// The replacement may refer to capture groups of the pattern, e.g. "$1" or "${name}".
+    pub fn preview_replace(
+        &self,
+        pattern: &Regex,
+        replacement: &str,
+        options: &SearchOptions,
+    ) -> Vec<Replacement> {
+        let mut res = Vec::new();
+        for (index, sub) in self.into_iter().enumerate() {
+            let after = replace_text(&sub.text, pattern, replacement, options);
+            if after != sub.text {
+                res.push(Replacement {
+                    index,
+                    num: sub.num,
+                    before: sub.text.clone(),
+                    after,
+                });
+            }
+        }
+        res
+    }
+
+    /// Replaces every match of the pattern in the text of the subtitles, returning the changed
+    /// subtitles as described in [`preview_replace`](#method.preview_replace).
+    pub fn replace(
+        &mut self,
+        pattern: &Regex,
+        replacement: &str,
+        options: &SearchOptions,
+    ) -> Vec<Replacement> {
+        let changes = self.preview_replace(pattern, replacement, options);
+        for change in &changes {
+            self.0[change.index].set_text(change.after.clone());
+        }
+        changes
+    }
+}
+
+mod tests {
+    #![allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn ranges_ignoring_markup() {
+        let subs = Subtitles::parse_from_str(
+            "1\n00:00:01,000 --> 00:00:02,000\n<b>Hel</b>lo {\\an8}world".to_string(),
+        )
+        .unwrap();
+        let pattern = Regex::new("Hello world").unwrap();
+        assert!(subs.search(&pattern, &SearchOptions::default()).is_empty());
+
+        let options = SearchOptions {
+            ignore_markup: true,
+            ..Default::default()
+        };
+        let matches = subs.search(&pattern, &options);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].text, "Hel</b>lo {\\an8}world");
+
+        let pattern = Regex::new("hello big").unwrap();
+        assert_eq!(
+            replace_text("hello <i>big</i> world", &pattern, "bye", &options),
+            "bye<i></i> world"
+        );
+        assert_eq!(
+            replace_text("<b>hello big</b>", &pattern, "bye", &options),
+            "<b>bye</b>"
+        );
+    }
+
+    #[test]
+    fn capture_groups_and_case() {
+        let options = SearchOptions {
+            preserve_case: true,
+            ..Default::default()
+        };
+        let pattern = Regex::new("(?i)colou?r(s?)").unwrap();
+        assert_eq!(
+            replace_text("Colours, COLOR, colors", &pattern, "hue$1", &options),
+            "Hues, HUE, hues"
+        );
+        assert_eq!(
+            replace_text("Colours", &pattern, "hue$1", &SearchOptions::default()),
+            "hues"
+        );
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Building subtitles from word-timed transcripts.
+//!
+//! A [`SegmentConfig`] groups a sequence of timed [`Word`]s, such as the output of a speech
+//! recogniser, into subtitles that respect the configured limits. Subtitles always break at long
+//! pauses, and preferably after the end of a sentence or clause. The text of every subtitle is
+//! split into lines of similar length.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::Timestamp;
+//! use srtlib::segment::SegmentConfig;
+//! use srtlib::words::Word;
+//!
+//! let transcript = [(0, 300, "Hi."), (2000, 2300, "How"), (2300, 2500, "are"), (2500, 3000, "you?")];
+//! let words = transcript.iter().map(|&(start, end, text)| {
+//!     Word::new(Timestamp::from_milliseconds(start), Timestamp::from_milliseconds(end), text.to_string())
+//! }).collect();
+//!
+//! let config = SegmentConfig { max_chars_per_line: 10, ..Default::default() };
+//! let subs = config.segment(words);
+//! assert_eq!(subs.to_string(), "1\n00:00:00,000 --> 00:00:00,300\nHi.\n\n\
+//!                               2\n00:00:02,000 --> 00:00:03,000\nHow are\nyou?");
+//! ```
+//!
+//! [`SegmentConfig`]: struct.SegmentConfig.html
+//! [`Word`]: ../words/struct.Word.html
+
+use crate::words::Word;
+use crate::{Subtitle, Subtitles, Timestamp};
+use std::cmp;
+
+/// Punctuation ending a sentence.
+const SENTENCE_END: [char; 4] = ['.', '?', '!', '…'];
+/// Punctuation ending a clause.
+const CLAUSE_END: [char; 3] = [',', ';', ':'];
+/// Closing quotes and brackets that may follow punctuation.
+const CLOSING: [char; 6] = ['"', '\'', ')', '”', '’', '»'];
+
+/// The limits used when grouping words into subtitles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentConfig {
+    /// The maximum number of characters in a line.
+    pub max_chars_per_line: usize,
+    /// The maximum number of lines in a subtitle.
+    pub max_lines: usize,
+    /// The maximum duration of a subtitle in milliseconds.
+    pub max_duration: u32,
+    /// The maximum reading speed in characters per second. Subtitles are extended into the
+    /// following silence where needed to stay below it, and split at a pause when that isn't
+    /// enough and splitting lets their first part be read in time. Subtitles of fast speech
+    /// without any silence to extend into may still exceed it, and are reported by the
+    /// [`MaxCharsPerSecond`] lint rule.
+    ///
+    /// [`MaxCharsPerSecond`]: ../lint/enum.Rule.html#variant.MaxCharsPerSecond
+    pub max_chars_per_second: f64,
+    /// The length in milliseconds of a pause between two words that always starts a new
+    /// subtitle.
+    pub min_pause: u32,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> SegmentConfig {
+        SegmentConfig {
+            max_chars_per_line: 42,
+            max_lines: 2,
+            max_duration: 7000,
+            max_chars_per_second: 20.0,
+            min_pause: 700,
+        }
+    }
+}
+
+/// Returns the number of characters of the words when joined with spaces.
+fn text_len(words: &[&str]) -> usize {
+    words.iter().map(|w| w.chars().count()).sum::<usize>() + words.len().saturating_sub(1)
+}
+
+/// Fills lines of at most **width** characters with the words, one line after the other.
+fn fill(words: &[&str], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits the words into at most **max_lines** lines of at most **max_chars** characters,
+/// keeping the longest line as short as possible. Returns None if the words don't fit.
+fn wrap(words: &[&str], max_chars: usize, max_lines: usize) -> Option<Vec<String>> {
+    let total = text_len(words);
+    if total <= max_chars {
+        return Some(vec![words.join(" ")]);
+    }
+    let longest = words.iter().map(|w| w.chars().count()).max().unwrap_or(0);
+    let narrowest = cmp::max(longest, total.div_ceil(cmp::max(max_lines, 1)));
+    (narrowest..=max_chars)
+        .map(|width| fill(words, width))
+        .find(|lines| lines.len() <= max_lines)
+}
+
+/// Checks if a word ends with one of the given punctuation marks.
+fn ends_with(word: &Word, punctuation: &[char]) -> bool {
+    word.text.trim_end_matches(CLOSING).ends_with(punctuation)
+}
+
+impl SegmentConfig {
+    /// Checks if the words fit in a single subtitle.
+    fn fits(&self, words: &[Word]) -> bool {
+        let (first, last) = match words {
+            [] | [_] => return true,
+            [first, .., last] => (first, last),
+        };
+        let duration = last
+            .end_time
+            .to_milliseconds()
+            .saturating_sub(first.start_time.to_milliseconds());
+        if duration > self.max_duration {
+            return false;
+        }
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        wrap(&texts, self.max_chars_per_line, self.max_lines).is_some()
+    }
+
+    /// Returns the number of words to keep in a subtitle that has to be broken, preferring to
+    /// break after a sentence and then after a clause within the second half of the words.
+    fn break_index(&self, words: &[Word]) -> usize {
+        let candidates = cmp::max(words.len() / 2, 1)..=words.len();
+        for punctuation in [&SENTENCE_END[..], &CLAUSE_END[..]] {
+            if let Some(k) = candidates
+                .clone()
+                .rev()
+                .find(|&k| ends_with(&words[k - 1], punctuation))
+            {
+                return k;
+            }
+        }
+        words.len()
+    }
+
+    /// Returns the number of milliseconds needed to read a text at the maximum reading speed.
+    fn reading_time(&self, text: &str) -> u32 {
+        let chars = text.chars().filter(|&c| c != '\n').count() as f64;
+        (chars * 1000.0 / self.max_chars_per_second).ceil() as u32
+    }
+
+    /// Returns the end time of a subtitle extended as far as needed to respect the reading
+    /// speed, without reaching the start of the next subtitle.
+    fn extended_end(&self, sub: &Subtitle, next: Option<Timestamp>) -> Timestamp {
+        let start = sub.start_time.to_milliseconds();
+        let needed = cmp::min(self.reading_time(&sub.text), self.max_duration);
+        let mut end = start.saturating_add(needed);
+        if let Some(next) = next {
+            end = cmp::min(end, next.to_milliseconds());
+        }
+        end = cmp::min(end, Timestamp::MAX_TIMESTAMP_MILLIS);
+        cmp::max(sub.end_time, Timestamp::from_milliseconds(end))
+    }
+
+    /// Checks if a subtitle can be read at the maximum reading speed once extended.
+    fn readable(&self, sub: &Subtitle, next: Option<Timestamp>) -> bool {
+        let duration =
+            self.extended_end(sub, next).to_milliseconds() - sub.start_time.to_milliseconds();
+        duration >= self.reading_time(&sub.text)
+    }
+
+    /// Splits a subtitle that is read too fast, if its first part can then be read in time.
+    ///
+    /// The split must fall on a pause, since otherwise the first part could only be read in time
+    /// by taking that time from the second one.
+    fn split_fast(&self, sub: &Subtitle) -> Option<(Subtitle, Subtitle)> {
+        if sub.words.len() < 2 {
+            return None;
+        }
+        let index = self.break_index(&sub.words[..sub.words.len() - 1]);
+        let (first, second) = sub.words.split_at(index);
+        let first = self.subtitle(0, first.to_vec())?;
+        let second = self.subtitle(0, second.to_vec())?;
+        (first.end_time < second.start_time && self.readable(&first, Some(second.start_time)))
+            .then_some((first, second))
+    }
+
+    /// Constructs a subtitle from a group of words.
+    fn subtitle(&self, num: usize, words: Vec<Word>) -> Option<Subtitle> {
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        let text = match wrap(&texts, self.max_chars_per_line, self.max_lines) {
+            Some(lines) => lines.join("\n"),
+            // a single word longer than a line
+            None => texts.join(" "),
+        };
+        let mut sub = Subtitle::from_words(num, words)?;
+        sub.text = text;
+        Some(sub)
+    }
+
+    /// Groups the words into subtitles numbered from 1.
+    ///
+    /// A subtitle is ended early when the next word would exceed the line or duration limits. A
+    /// single word that exceeds them on its own still gets a subtitle of its own. Each subtitle
+    /// then lasts until its last word ends, or longer if needed to respect the reading speed,
+    /// without reaching the next subtitle. Subtitles that can't be extended enough are split
+    /// where possible, as described in [`max_chars_per_second`].
+    ///
+    /// [`max_chars_per_second`]: #structfield.max_chars_per_second
+    pub fn segment(&self, mut words: Vec<Word>) -> Subtitles {
+        words.sort_by_key(|w| w.start_time);
+        let mut groups: Vec<Vec<Word>> = Vec::new();
+        let mut current: Vec<Word> = Vec::new();
+        for word in words {
+            if let Some(last) = current.last() {
+                let pause = word.start_time.to_milliseconds() as i64
+                    - last.end_time.to_milliseconds() as i64;
+                if pause >= self.min_pause as i64 {
+                    groups.push(std::mem::take(&mut current));
+                }
+            }
+            current.push(word);
+            while !self.fits(&current) {
+                let word = current.pop().expect("a single word always fits");
+                let rest = current.split_off(self.break_index(&current));
+                groups.push(std::mem::replace(&mut current, rest));
+                current.push(word);
+            }
+        }
+        groups.push(current);
+
+        let mut res: Vec<Subtitle> = groups
+            .into_iter()
+            .filter_map(|group| self.subtitle(0, group))
+            .collect();
+        let mut i = 0;
+        while i < res.len() {
+            let next = res.get(i + 1).map(|sub| sub.start_time);
+            if !self.readable(&res[i], next) {
+                if let Some((first, second)) = self.split_fast(&res[i]) {
+                    res[i] = first;
+                    res.insert(i + 1, second);
+                }
+            }
+            i += 1;
+        }
+        for i in 0..res.len() {
+            res[i].num = i + 1;
+            let next = res.get(i + 1).map(|sub| sub.start_time);
+            res[i].end_time = self.extended_end(&res[i], next);
+        }
+        Subtitles::new_from_vec(res)
+    }
+}
+
+impl Subtitles {
+    /// Groups the timed words of the subtitles into new subtitles, as described in
+    /// [`SegmentConfig::segment`]. Subtitles without timed words are dropped.
+    ///
+    /// [`SegmentConfig::segment`]: segment/struct.SegmentConfig.html#method.segment
+    pub fn resegment(&self, config: &SegmentConfig) -> Subtitles {
+        config.segment(self.words())
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+    use super::*;
+
+    fn words(timings: &[(u32, u32, &str)]) -> Vec<Word> {
+        timings
+            .iter()
+            .map(|&(start, end, text)| {
+                Word::new(
+                    Timestamp::from_milliseconds(start),
+                    Timestamp::from_milliseconds(end),
+                    text.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn balanced_lines() {
+        let text = ["This", "is", "a", "rather", "long", "line", "of", "text"];
+        assert_eq!(
+            wrap(&text, 30, 2),
+            Some(vec![
+                "This is a rather".to_string(),
+                "long line of text".to_string()
+            ])
+        );
+        assert_eq!(wrap(&text, 15, 2), None);
+        assert_eq!(wrap(&text[..3], 30, 2), Some(vec!["This is a".to_string()]));
+    }
+
+    #[test]
+    fn breaks_and_reading_speed() {
+        let config = SegmentConfig {
+            max_chars_per_line: 20,
+            max_lines: 1,
+            ..Default::default()
+        };
+        let subs = config.segment(words(&[
+            (0, 200, "Yes,"),
+            (200, 400, "sure."),
+            (400, 600, "Come"),
+            (600, 800, "with"),
+            (800, 1000, "me"),
+            (1000, 1200, "now."),
+        ]));
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, "Yes, sure.");
+        // extended to the start of the next subtitle to lower the reading speed
+        assert_eq!(subs[0].end_time, Timestamp::from_milliseconds(400));
+        assert_eq!(subs[1].text, "Come with me now.");
+        assert_eq!(subs[1].end_time, Timestamp::from_milliseconds(1250));
+
+        let resegmented = subs.resegment(&SegmentConfig::default());
+        assert_eq!(resegmented.len(), 1);
+        assert_eq!(resegmented[0].words.len(), 6);
+    }
+
+    #[test]
+    fn split_fast_speech() {
+        use crate::lint::{LintConfig, Rule, Severity};
+        let config = SegmentConfig {
+            max_chars_per_second: 8.0,
+            ..Default::default()
+        };
+        let subs = config.segment(words(&[
+            (0, 400, "One"),
+            (400, 800, "two"),
+            (800, 1400, "three."),
+            (1900, 2100, "Four"),
+            (2100, 2300, "five"),
+            (2300, 2500, "six"),
+            (3300, 3500, "Seven"),
+        ]));
+        // too fast as one subtitle, but the first sentence can be read in time on its own
+        assert_eq!(subs.len(), 3);
+        assert_eq!(subs[0].text, "One two three.");
+        assert_eq!(subs[0].end_time, Timestamp::from_milliseconds(1750));
+        assert_eq!(subs[1].text, "Four five six");
+        assert_eq!(subs[1].end_time, Timestamp::from_milliseconds(3300));
+
+        // the rest of the speech is too fast to fix, and is left to the linter
+        let lint = LintConfig::new().with_rule(Rule::MaxCharsPerSecond(8.0), Severity::Warning);
+        let findings = subs.lint(&lint);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].num, 2);
+    }
+
+    #[test]
+    fn overlapping_words_fit() {
+        let config = SegmentConfig::default();
+        assert!(config.fits(&words(&[(1000, 1500, "Late"), (0, 200, "early")])));
+    }
+}
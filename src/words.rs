@@ -0,0 +1,329 @@
+// Copyright 2020 Konstantinos Gavalas.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Word-level timing, as produced by speech recognition or used for karaoke.
+//!
+//! The [`words`] of a subtitle are moved together with it by every time shifting method, and are
+//! kept by the splitting, joining and clipping methods of [`Subtitles`] and by the [`EditSession`]
+//! split and merge operations. They are dropped when the text of the subtitle is changed with
+//! [`set_text`], which the editing methods of the crate use, since they would no longer match
+//! it. They can be written as ASS karaoke tags or WebVTT inline timestamps, and used to split
+//! subtitles at word boundaries.
+//!
+//! # Examples
+//!
+//! ```
+//! use srtlib::{Subtitle, Timestamp};
+//! use srtlib::words::Word;
+//!
+//! let mut sub = Subtitle::from_words(1, vec![
+//!     Word::new(Timestamp::new(0, 0, 1, 0), Timestamp::new(0, 0, 1, 400), "Hello".to_string()),
+//!     Word::new(Timestamp::new(0, 0, 1, 500), Timestamp::new(0, 0, 2, 0), "world".to_string()),
+//! ]).unwrap();
+//! sub.add_seconds(1);
+//!
+//! assert_eq!(sub.to_string(), "1\n00:00:02,000 --> 00:00:03,000\nHello world");
+//! assert_eq!(sub.to_ass_karaoke(), "{\\k40}Hello {\\k10}{\\k50}world");
+//! assert_eq!(sub.to_webvtt_karaoke(), "Hello <00:00:02.500>world");
+//! ```
+//!
+//! [`words`]: ../struct.Subtitle.html#method.words
+//! [`set_text`]: ../struct.Subtitle.html#method.set_text
+//! [`Subtitles`]: ../struct.Subtitles.html
+//! [`EditSession`]: ../history/struct.EditSession.html
+
+use crate::{Subtitle, Subtitles, Timestamp};
+use std::cmp;
+
+/// A single word along with the time it is spoken.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Word {
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub text: String,
+}
+
+impl Word {
+    /// Constructs a new Word.
+    pub fn new(start_time: Timestamp, end_time: Timestamp, text: String) -> Word {
+        Word {
+            start_time,
+            end_time,
+            text,
+        }
+    }
+}
+
+/// Joins the text of the words with spaces.
+fn join_text(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|w| w.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the words that overlap the time window from **start** to **end**, clipped to it.
+pub(crate) fn clip(words: &[Word], start: Timestamp, end: Timestamp) -> Vec<Word> {
+    words
+        .iter()
+        .filter(|w| w.end_time > start && w.start_time < end)
+        .map(|w| {
+            Word::new(
+                cmp::max(w.start_time, start),
+                cmp::min(w.end_time, end),
+                w.text.clone(),
+            )
+        })
+        .collect()
+}
+
+/// Removes the section of time from **start** to **end** from the words in the same way as
+/// [`Subtitles::remove_section`] does for subtitles.
+///
+/// [`Subtitles::remove_section`]: ../struct.Subtitles.html#method.remove_section
+pub(crate) fn remove_section(words: &[Word], start: Timestamp, end: Timestamp) -> Vec<Word> {
+    let length = (end.to_milliseconds() - start.to_milliseconds()) as i64;
+    let mut res = Vec::with_capacity(words.len());
+    for word in words {
+        let mut word = word.clone();
+        if word.end_time <= start {
+            res.push(word);
+        } else if word.start_time >= end {
+            word.start_time.add_milliseconds(-length);
+            word.end_time.add_milliseconds(-length);
+            res.push(word);
+        } else {
+            word.start_time = cmp::min(word.start_time, start);
+            if word.end_time > end {
+                word.end_time.add_milliseconds(-length);
+            } else {
+                word.end_time = start;
+            }
+            if word.start_time < word.end_time {
+                res.push(word);
+            }
+        }
+    }
+    res
+}
+
+/// Splits the words at the given time. Words starting before it go to the first part, ending at
+/// **time** at the latest, and the rest go to the second part.
+pub(crate) fn split(words: &[Word], time: Timestamp) -> (Vec<Word>, Vec<Word>) {
+    let (mut first, second): (Vec<Word>, Vec<Word>) =
+        words.iter().cloned().partition(|w| w.start_time < time);
+    for word in &mut first {
+        word.end_time = cmp::min(word.end_time, time);
+    }
+    (first, second)
+}
+
+/// Formats a timestamp as a WebVTT timestamp, e.g. "00:01:02.500".
+fn webvtt_timestamp(time: &Timestamp) -> String {
+    time.to_string().replace(',', ".")
+}
+
+impl Subtitle {
+    /// Constructs a new Subtitle spanning the given words, with their text joined by spaces.
+    /// Returns None if there are no words.
+    pub fn from_words(num: usize, words: Vec<Word>) -> Option<Subtitle> {
+        let start_time = words.iter().map(|w| w.start_time).min()?;
+        let end_time = words.iter().map(|w| w.end_time).max()?;
+        let mut sub = Subtitle::new(num, start_time, end_time, join_text(&words));
+        sub.words = words;
+        Some(sub)
+    }
+
+    /// Splits the subtitle in two before the word at **index**. The first part ends with the
+    /// last word before the split and the second part, numbered right after the first, starts
+    /// with the word at **index**. The text of both parts is rebuilt from their words.
+    ///
+    /// # Panics
+    ///
+    /// Panics if **index** is zero or not less than the number of words.
+    pub fn split_at_word(&self, index: usize) -> (Subtitle, Subtitle) {
+        assert!(
+            index > 0 && index < self.words.len(),
+            "word index out of bounds"
+        );
+        let (first_words, second_words) = self.words.split_at(index);
+        let mut first = Subtitle::new(
+            self.num,
+            self.start_time,
+            first_words[index - 1].end_time,
+            join_text(first_words),
+        );
+        first.words = first_words.to_vec();
+        let mut second = Subtitle::new(
+            self.num + 1,
+            second_words[0].start_time,
+            self.end_time,
+            join_text(second_words),
+        );
+        second.words = second_words.to_vec();
+        (first, second)
+    }
+
+    /// Returns the words as ASS karaoke text, where every word is preceded by a `\k` tag holding
+    /// its duration in centiseconds. Pauses between words get a `\k` tag of their own.
+    pub fn to_ass_karaoke(&self) -> String {
+        let start = self.start_time.to_milliseconds() as i64;
+        let centis = |time: &Timestamp| {
+            ((time.to_milliseconds() as i64 - start) as f64 / 10.0).round() as i64
+        };
+        let mut res = String::new();
+        // positions are rounded relative to the start, so that rounding errors don't accumulate
+        let mut pos = 0;
+        for (i, word) in self.words.iter().enumerate() {
+            let word_start = centis(&word.start_time);
+            if word_start > pos {
+                res.push_str(&format!("{{\\k{}}}", word_start - pos));
+                pos = word_start;
+            }
+            let word_end = cmp::max(centis(&word.end_time), pos);
+            res.push_str(&format!("{{\\k{}}}{}", word_end - pos, word.text));
+            if i + 1 < self.words.len() {
+                res.push(' ');
+            }
+            pos = word_end;
+        }
+        res
+    }
+
+    /// Returns the words as WebVTT cue text, where every word starting after the subtitle is
+    /// preceded by an inline timestamp.
+    pub fn to_webvtt_karaoke(&self) -> String {
+        let mut res = String::new();
+        for (i, word) in self.words.iter().enumerate() {
+            if i > 0 {
+                res.push(' ');
+            }
+            if word.start_time > self.start_time {
+                res.push_str(&format!("<{}>", webvtt_timestamp(&word.start_time)));
+            }
+            res.push_str(&word.text);
+        }
+        res
+    }
+}
+
+impl Subtitles {
+    /// Returns the timed words of every subtitle, ordered by their start time. Together with
+    /// [`Subtitle::from_words`] this allows the words to be grouped into new subtitles.
+    ///
+    /// [`Subtitle::from_words`]: struct.Subtitle.html#method.from_words
+    pub fn words(&self) -> Vec<Word> {
+        let mut res: Vec<Word> = self.into_iter().flat_map(|s| s.words.clone()).collect();
+        res.sort_by_key(|w| w.start_time);
+        res
+    }
+}
+
+mod tests {
+    #![allow(unused_imports, dead_code)]
+    use super::*;
+    use crate::history::EditSession;
+
+    fn word(start: u32, end: u32, text: &str) -> Word {
+        Word::new(
+            Timestamp::from_milliseconds(start),
+            Timestamp::from_milliseconds(end),
+            text.to_string(),
+        )
+    }
+
+    fn timings(sub: &Subtitle) -> Vec<(u32, u32)> {
+        sub.words
+            .iter()
+            .map(|w| (w.start_time.to_milliseconds(), w.end_time.to_milliseconds()))
+            .collect()
+    }
+
+    #[test]
+    fn survives_editing() {
+        let sub = Subtitle::from_words(
+            1,
+            vec![
+                word(1000, 1400, "One"),
+                word(1500, 1900, "two"),
+                word(2000, 2500, "three"),
+            ],
+        )
+        .unwrap();
+        let subs = Subtitles::new_from_vec(vec![sub.clone()]);
+
+        let clip = subs.extract_window(
+            Timestamp::from_milliseconds(1200),
+            Timestamp::from_milliseconds(2200),
+        );
+        assert_eq!(timings(&clip[0]), vec![(0, 200), (300, 700), (800, 1000)]);
+
+        let mut cut = subs.clone();
+        cut.remove_section(
+            Timestamp::from_milliseconds(1450),
+            Timestamp::from_milliseconds(1950),
+        );
+        assert_eq!(timings(&cut[0]), vec![(1000, 1400), (1500, 2000)]);
+
+        let mut session = EditSession::new(subs);
+        session.split(0, Timestamp::from_milliseconds(1700), 3);
+        assert_eq!(
+            timings(&session.subtitles()[0]),
+            vec![(1000, 1400), (1500, 1700)]
+        );
+        assert_eq!(timings(&session.subtitles()[1]), vec![(2000, 2500)]);
+        session.merge(0);
+        session.shift(0..1, -1000);
+        assert_eq!(
+            timings(&session.subtitles()[0]),
+            vec![(0, 400), (500, 700), (1000, 1500)]
+        );
+
+        let (first, second) = sub.split_at_word(2);
+        assert_eq!(first.text, "One two");
+        assert_eq!(first.end_time, Timestamp::from_milliseconds(1900));
+        assert_eq!(
+            second.to_string(),
+            "2\n00:00:02,000 --> 00:00:02,500\nthree"
+        );
+    }
+
+    #[test]
+    fn karaoke_rounding() {
+        let mut sub =
+            Subtitle::from_words(1, vec![word(5, 1004, "a"), word(1004, 2006, "b")]).unwrap();
+        sub.start_time = Timestamp::from_milliseconds(0);
+        assert_eq!(sub.to_ass_karaoke(), "{\\k1}{\\k99}a {\\k101}b");
+        assert_eq!(sub.to_webvtt_karaoke(), "<00:00:00.005>a <00:00:01.004>b");
+    }
+
+    #[test]
+    fn text_edits_and_limits() {
+        let sub =
+            Subtitle::from_words(1, vec![word(0, 400, "One"), word(500, 900, "two")]).unwrap();
+        let plain = Subtitle::new(1, sub.start_time, sub.end_time, sub.text.clone());
+        assert_eq!(sub, plain);
+
+        let mut edited = sub.clone();
+        edited.set_text("One two".to_string());
+        assert_eq!(edited.words().len(), 2);
+        edited.set_text("One, two".to_string());
+        assert!(edited.words().is_empty());
+
+        // a word before the subtitle can't be moved back, and nothing is moved
+        let mut early = sub.clone();
+        early.start_time = Timestamp::from_milliseconds(100);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            early.add_milliseconds(-50);
+        }));
+        assert!(result.is_err());
+        assert_eq!(early.start_time, Timestamp::from_milliseconds(100));
+        assert_eq!(timings(&early), vec![(0, 400), (500, 900)]);
+    }
+}